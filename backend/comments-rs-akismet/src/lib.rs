@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use comments_rs_core_backend::{
+    data::{Comment, RequestInfo},
+    error::Error,
+    spam::Verdict,
+    traits::{SpamFilter, SpamResult},
+};
+
+pub const AKISMET_URL: &str = "https://rest.akismet.com";
+
+/// Number of checked comments whose request is remembered for feedback.
+const REQUESTS: usize = 10_000;
+
+/// Checks comments with a service speaking the Akismet protocol and reports the decisions of
+/// moderators back to it.
+///
+/// Comments the service considers spam are held for moderation, unless it marks them as blatant
+/// spam to be discarded, those are rejected. Akismet wants the request of a comment for
+/// feedback as well, so the requests of recently checked comments are kept in memory.
+#[derive(Clone)]
+pub struct AkismetClient {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    blog: String,
+    test: bool,
+    requests: Arc<Mutex<Requests>>,
+}
+
+#[derive(Default)]
+struct Requests {
+    by_hash: HashMap<String, RequestInfo>,
+    order: VecDeque<String>,
+}
+
+impl AkismetClient {
+    /// Checks comments posted on `blog`, the url of the site embedding the comments.
+    pub fn new(api_key: &str, blog: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: AKISMET_URL.to_string(),
+            api_key: api_key.to_string(),
+            blog: blog.to_string(),
+            test: false,
+            requests: Arc::new(Mutex::new(Requests::default())),
+        }
+    }
+
+    /// Talks to the service at `base_url` instead of Akismet.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Marks all calls as tests, so they don't train the service.
+    pub fn with_test_mode(mut self) -> Self {
+        self.test = true;
+        self
+    }
+
+    /// Asks the service whether the key is valid for the blog.
+    pub async fn verify_key(&self) -> Result<bool, Error> {
+        let params = vec![("key", self.api_key.clone()), ("blog", self.blog.clone())];
+        let (body, _) = self.call("verify-key", &params).await?;
+
+        Ok(body == "valid")
+    }
+
+    async fn call(
+        &self,
+        method: &str,
+        params: &[(&str, String)],
+    ) -> Result<(String, Option<String>), Error> {
+        let response = self
+            .client
+            .post(format!("{}/1.1/{}", self.base_url, method))
+            .form(params)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(map_err)?;
+        let pro_tip = response
+            .headers()
+            .get("X-akismet-pro-tip")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let body = response.text().await.map_err(map_err)?;
+
+        Ok((body.trim().to_string(), pro_tip))
+    }
+
+    fn params(&self, comment: &Comment, request: &RequestInfo) -> Vec<(&'static str, String)> {
+        let comment_type = match comment.parent_hash {
+            Some(_) => "reply",
+            None => "comment",
+        };
+        let mut params = vec![
+            ("api_key", self.api_key.clone()),
+            ("blog", self.blog.clone()),
+            (
+                "user_ip",
+                request.ip.map(|ip| ip.to_string()).unwrap_or_default(),
+            ),
+            ("comment_type", comment_type.to_string()),
+            ("comment_author_email", comment.email.clone()),
+            ("comment_content", comment.content.clone()),
+        ];
+
+        if let Some(user_agent) = &request.user_agent {
+            params.push(("user_agent", user_agent.clone()));
+        }
+        if let Some(referrer) = &request.referrer {
+            params.push(("referrer", referrer.clone()));
+        }
+        if self.test {
+            params.push(("is_test", "1".to_string()));
+        }
+
+        params
+    }
+
+    fn remember(&self, hash: &str, request: &RequestInfo) {
+        let mut requests = self.requests.lock().unwrap();
+
+        if requests.order.len() == REQUESTS {
+            if let Some(oldest) = requests.order.pop_front() {
+                requests.by_hash.remove(&oldest);
+            }
+        }
+        if requests
+            .by_hash
+            .insert(hash.to_string(), request.clone())
+            .is_none()
+        {
+            requests.order.push_back(hash.to_string());
+        }
+    }
+
+    fn request(&self, hash: &str) -> RequestInfo {
+        let requests = self.requests.lock().unwrap();
+
+        requests.by_hash.get(hash).cloned().unwrap_or_default()
+    }
+}
+
+impl SpamFilter for AkismetClient {
+    fn check(&self, comment: &Comment, request: &RequestInfo) -> SpamResult<Verdict> {
+        let client = self.clone();
+        let params = self.params(comment, request);
+
+        self.remember(&comment.hash, request);
+
+        Box::pin(async move {
+            match client.call("comment-check", &params).await? {
+                (body, _) if body == "false" => Ok(Verdict::Approve),
+                (body, pro_tip) if body == "true" => match pro_tip.as_deref() {
+                    Some("discard") => Ok(Verdict::Reject),
+                    _ => Ok(Verdict::Hold),
+                },
+                // "invalid" along with an `X-akismet-debug-help` header, e.g. for a wrong key
+                _ => Err(Error::NewtorkError),
+            }
+        })
+    }
+
+    fn learn(&self, comment: &Comment, spam: bool) -> SpamResult<()> {
+        let client = self.clone();
+        let params = self.params(comment, &self.request(&comment.hash));
+        let method = if spam { "submit-spam" } else { "submit-ham" };
+
+        Box::pin(async move { client.call(method, &params).await.map(|_| ()) })
+    }
+}
+
+fn map_err(_error: reqwest::Error) -> Error {
+    Error::NewtorkError
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use comments_rs_core_backend::{
+        data::{Comment, RequestInfo},
+        error::Error,
+        spam::Verdict,
+        traits::SpamFilter,
+    };
+    use tide::listener::Listener;
+
+    use crate::AkismetClient;
+
+    /// Method and parameters of every call received.
+    type Calls = Arc<Mutex<Vec<(String, HashMap<String, String>)>>>;
+
+    /// Serves the Akismet protocol on a free local port, with the key `key`, every comment
+    /// mentioning "casino" being spam and every comment mentioning "viagra" being blatant spam.
+    /// Returns its url together with the calls received so far.
+    async fn akismet_stub() -> (String, Calls) {
+        let calls = Calls::default();
+        let mut app = tide::with_state(calls.clone());
+        app.at("/1.1/:method")
+            .post(|mut request: tide::Request<Calls>| async move {
+                let method = request.param("method")?.to_string();
+                let params: HashMap<String, String> = request.body_form().await?;
+                let key = params.get("api_key").or_else(|| params.get("key"));
+                let content = params.get("comment_content").cloned().unwrap_or_default();
+                let (body, pro_tip) = match method.as_str() {
+                    _ if key.map(String::as_str) != Some("key") => ("invalid", None),
+                    "verify-key" => ("valid", None),
+                    "comment-check" if content.contains("viagra") => ("true", Some("discard")),
+                    "comment-check" if content.contains("casino") => ("true", None),
+                    "comment-check" => ("false", None),
+                    _ => ("Thanks for making the web a better place.", None),
+                };
+
+                request.state().lock().unwrap().push((method, params));
+
+                let mut response = tide::Response::builder(200).body(body);
+                if let Some(pro_tip) = pro_tip {
+                    response = response.header("X-akismet-pro-tip", pro_tip);
+                }
+
+                Ok(response.build())
+            });
+
+        let mut listener = app.bind("127.0.0.1:0").await.unwrap();
+        let url = listener.info()[0].connection().to_string();
+        tokio::spawn(async move { listener.accept().await });
+
+        (url, calls)
+    }
+
+    fn request() -> RequestInfo {
+        RequestInfo {
+            ip: Some("203.0.113.7".parse().unwrap()),
+            user_agent: Some("test-agent".to_string()),
+            referrer: None,
+        }
+    }
+
+    fn comment(content: &str) -> Comment {
+        Comment::new(&"0".repeat(64), "test@mail.com", 17, content)
+    }
+
+    #[tokio::test]
+    async fn test_comment_check() {
+        let (url, calls) = akismet_stub().await;
+        let client = AkismetClient::new("key", "https://example.com").with_base_url(&url);
+
+        assert_eq!(
+            client.check(&comment("hello"), &request()).await,
+            Ok(Verdict::Approve)
+        );
+        assert_eq!(
+            client.check(&comment("cheap viagra"), &request()).await,
+            Ok(Verdict::Reject)
+        );
+        assert_eq!(
+            client.check(&comment("visit our casino"), &request()).await,
+            Ok(Verdict::Hold)
+        );
+
+        let calls = calls.lock().unwrap();
+        let (method, params) = &calls[0];
+
+        assert_eq!(method, "comment-check");
+        assert_eq!(params["blog"], "https://example.com");
+        assert_eq!(params["user_ip"], "203.0.113.7");
+        assert_eq!(params["user_agent"], "test-agent");
+        assert_eq!(params["comment_type"], "comment");
+        assert_eq!(params["comment_author_email"], "test@mail.com");
+        assert_eq!(params["comment_content"], "hello");
+        assert!(!params.contains_key("referrer"));
+        assert!(!params.contains_key("is_test"));
+    }
+
+    #[tokio::test]
+    async fn test_feedback() {
+        let (url, calls) = akismet_stub().await;
+        let client = AkismetClient::new("key", "https://example.com")
+            .with_base_url(&url)
+            .with_test_mode();
+        let ham = comment("cheap viagra");
+
+        client.check(&ham, &request()).await.unwrap();
+        client.learn(&ham, false).await.unwrap();
+        client.learn(&comment("unknown"), true).await.unwrap();
+
+        let calls = calls.lock().unwrap();
+
+        assert_eq!(calls[1].0, "submit-ham");
+        // the request is taken over from the check
+        assert_eq!(calls[1].1["user_ip"], "203.0.113.7");
+        assert_eq!(calls[1].1["is_test"], "1");
+        assert_eq!(calls[2].0, "submit-spam");
+        assert_eq!(calls[2].1["user_ip"], "");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key() {
+        let (url, _) = akismet_stub().await;
+        let client = AkismetClient::new("wrong", "https://example.com").with_base_url(&url);
+
+        assert_eq!(client.verify_key().await, Ok(false));
+        assert_eq!(
+            client.check(&comment("hello"), &request()).await,
+            Err(Error::NewtorkError)
+        );
+        assert_eq!(
+            AkismetClient::new("key", "https://example.com")
+                .with_base_url(&url)
+                .verify_key()
+                .await,
+            Ok(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_network_error() {
+        let client =
+            AkismetClient::new("key", "https://example.com").with_base_url("http://127.0.0.1:1");
+
+        assert_eq!(
+            client.check(&comment("hello"), &request()).await,
+            Err(Error::NewtorkError)
+        );
+    }
+}
@@ -1,4 +1,11 @@
-use std::str;
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    net::IpAddr,
+    str::{self, FromStr},
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -14,11 +21,18 @@ lazy_static! {
     static ref HASH_REGEX: Regex = Regex::new(r"[0-9a-f]{64}$").unwrap();
 }
 
+/// Shown in place of content and author of deleted comments kept for their replies.
+pub const TOMBSTONE: &str = "[deleted]";
+
 #[derive(Validate, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct Thread {
     pub name: String,
     #[validate(regex = "HASH_REGEX")]
     pub hash: String,
+    /// Whether new comments wait for approval, `None` follows the global policy
+    pub require_approval: Option<bool>,
+    /// Locked threads take no new comments
+    pub locked: bool,
 }
 
 #[derive(Validate, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -37,9 +51,173 @@ pub struct Comment {
     pub email: String,
     #[validate(regex = "HASH_REGEX")]
     pub hash: String,
+    /// Hash of the comment this one replies to, top level comments have none
+    #[validate(regex = "HASH_REGEX")]
+    pub parent_hash: Option<String>,
     pub date: u128,
     #[validate(regex = "CONTENT_REGEX")]
     pub content: String,
+    /// When the content was last changed, unedited comments have none
+    pub edited_at: Option<u128>,
+    /// Set on deleted comments which are kept as tombstone since they have replies
+    pub deletion: Option<Deletion>,
+    pub status: CommentStatus,
+    /// Posted under a shadowban, only visible to its author and admins
+    pub shadowed: bool,
+}
+
+/// What a user may do, every role may do what the roles before it may.
+///
+/// Signed in users without any role granted are commenters.
+#[derive(
+    Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub enum Role {
+    #[default]
+    Commenter,
+    /// Approves, rejects, deletes and restores comments
+    Moderator,
+    /// Manages threads and bans and grants roles below their own
+    Admin,
+    /// Grants any role
+    Owner,
+}
+
+/// Where a role applies.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Every thread
+    Site,
+    /// The thread with this hash
+    Thread(String),
+}
+
+/// A role of a user in a scope.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RoleGrant {
+    pub user_name: String,
+    pub role: Role,
+    pub scope: Scope,
+}
+
+/// Where a comment stands in moderation, only approved comments are public.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Default)]
+pub enum CommentStatus {
+    Pending,
+    #[default]
+    Approved,
+    Rejected,
+    Spam,
+}
+
+/// Who deleted a comment, when and why.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Deletion {
+    /// Name of the user who deleted the comment
+    pub deleted_by: String,
+    pub reason: Option<String>,
+    pub date: u128,
+}
+
+/// A former content of an edited comment.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Revision {
+    pub comment_hash: String,
+    pub content: String,
+    /// When this content was posted or, for later revisions, edited in
+    pub date: u128,
+}
+
+/// A reader flagging a comment as abusive.
+#[derive(Validate, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Report {
+    #[validate(regex = "HASH_REGEX")]
+    pub comment_hash: String,
+    /// Name of the reporting user
+    pub reporter: String,
+    #[validate(length(min = 1, max = 512))]
+    pub reason: String,
+    pub date: u128,
+}
+
+/// A moderation or administrative action, as recorded in the audit log.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub action: AuditAction,
+    /// Name of the user who took the action
+    pub actor: String,
+    /// Hash of the comment or thread, id of the ban or name of the user acted on
+    pub target: String,
+    /// Why the action was taken, the granted role and its scope for role changes
+    pub reason: Option<String>,
+    pub date: u128,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+pub enum AuditAction {
+    DeleteComment,
+    RestoreComment,
+    ApproveComment,
+    RejectComment,
+    MarkSpam,
+    UpdateThread,
+    LockThread,
+    UnlockThread,
+    DeleteThread,
+    Ban,
+    Unban,
+    GrantRole,
+    RevokeRole,
+    /// An attempted action that failed, the reason names it and the error
+    Failed,
+}
+
+/// What is known about the HTTP request a comment or signup arrives with.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct RequestInfo {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub referrer: Option<String>,
+}
+
+/// Keeps a user, everyone with an email address of a domain or everyone posting from a range of
+/// addresses from commenting.
+///
+/// Shadowbanned commenters can still post, but their comments are only shown to themselves.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Ban {
+    pub id: String,
+    pub target: BanTarget,
+    pub shadow: bool,
+    pub reason: Option<String>,
+    /// Name of the user who issued the ban
+    pub banned_by: String,
+    pub date: u128,
+    /// When the ban ends, permanent bans have none
+    pub expires_at: Option<u128>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum BanTarget {
+    /// The user with this name
+    User(String),
+    /// Email addresses of this domain and its subdomains
+    EmailDomain(String),
+    IpRange(IpRange),
+}
+
+/// A network like `192.0.2.0/24`, a single address without prefix length is a network of its own.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct IpRange {
+    pub address: IpAddr,
+    pub prefix_len: u8,
+}
+
+/// A comment together with the replies to it and, recursively, the replies to those.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct CommentTree {
+    pub comment: Comment,
+    pub replies: Vec<CommentTree>,
 }
 
 impl User {
@@ -65,7 +243,9 @@ impl Thread {
     pub fn try_new(name: &str) -> Result<Self, Error> {
         let thread = Self {
             name: name.into(),
-            hash: hash(name.as_bytes()).into(),
+            hash: hash(name.as_bytes()),
+            require_approval: None,
+            locked: false,
         };
 
         Ok(thread.validate().map(|_| thread)?)
@@ -94,12 +274,443 @@ impl Comment {
             thread_hash: thread_hash.into(),
             email: email.into(),
             hash: hash(bytes.as_slice()),
+            parent_hash: None,
             date,
             content: content.into(),
+            edited_at: None,
+            deletion: None,
+            status: CommentStatus::Approved,
+            shadowed: false,
+        };
+
+        Ok(comment.validate().map(|_| comment)?)
+    }
+
+    pub fn new_reply(
+        thread_hash: &str,
+        parent_hash: &str,
+        email: &str,
+        date: u128,
+        content: &str,
+    ) -> Self {
+        Comment::try_new_reply(thread_hash, parent_hash, email, date, content)
+            .expect("Invalid comment!")
+    }
+
+    /// Creates a reply to the comment with hash `parent_hash`, which the store expects in the
+    /// same thread.
+    pub fn try_new_reply(
+        thread_hash: &str,
+        parent_hash: &str,
+        email: &str,
+        date: u128,
+        content: &str,
+    ) -> Result<Self, Error> {
+        let comment = Comment {
+            parent_hash: Some(parent_hash.into()),
+            ..Comment::try_new(thread_hash, email, date, content)?
+        };
+
+        Ok(comment.validate().map(|_| comment)?)
+    }
+
+    /// Replaces the content as of `date`, the hash stays the same so the comment keeps its
+    /// identity.
+    pub fn try_edit(&self, content: &str, date: u128) -> Result<Self, Error> {
+        let comment = Comment {
+            content: content.into(),
+            edited_at: Some(date),
+            ..self.clone()
         };
 
         Ok(comment.validate().map(|_| comment)?)
     }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deletion.is_some()
+    }
+
+    /// The same comment in moderation status `status`.
+    pub fn with_status(self, status: CommentStatus) -> Self {
+        Comment { status, ..self }
+    }
+
+    /// The current content as revision, to be kept when the comment gets edited.
+    pub fn revision(&self) -> Revision {
+        Revision {
+            comment_hash: self.hash.clone(),
+            content: self.content.clone(),
+            date: self.edited_at.unwrap_or(self.date),
+        }
+    }
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Commenter => "commenter",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "commenter" => Ok(Role::Commenter),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            "owner" => Ok(Role::Owner),
+            _ => Err(format!("Unknown role: {}", role)),
+        }
+    }
+}
+
+impl Scope {
+    /// Whether a role in this scope applies in `scope` as well.
+    pub fn covers(&self, scope: &Scope) -> bool {
+        self == &Scope::Site || self == scope
+    }
+}
+
+/// Formats as `site` or `thread:<hash>`.
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Site => f.write_str("site"),
+            Scope::Thread(hash) => write!(f, "thread:{}", hash),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope.split_once(':') {
+            None if scope == "site" => Ok(Scope::Site),
+            Some(("thread", hash)) => Ok(Scope::Thread(hash.into())),
+            _ => Err(format!("Unknown scope: {}", scope)),
+        }
+    }
+}
+
+impl RoleGrant {
+    pub fn new(user_name: &str, role: Role, scope: Scope) -> Self {
+        Self {
+            user_name: user_name.into(),
+            role,
+            scope,
+        }
+    }
+}
+
+impl CommentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentStatus::Pending => "pending",
+            CommentStatus::Approved => "approved",
+            CommentStatus::Rejected => "rejected",
+            CommentStatus::Spam => "spam",
+        }
+    }
+}
+
+impl Display for CommentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CommentStatus {
+    type Err = String;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "pending" => Ok(CommentStatus::Pending),
+            "approved" => Ok(CommentStatus::Approved),
+            "rejected" => Ok(CommentStatus::Rejected),
+            "spam" => Ok(CommentStatus::Spam),
+            _ => Err(format!("Unknown comment status: {}", status)),
+        }
+    }
+}
+
+impl Report {
+    pub fn try_new(
+        comment_hash: &str,
+        reporter: &str,
+        reason: &str,
+        date: u128,
+    ) -> Result<Self, Error> {
+        let report = Report {
+            comment_hash: comment_hash.into(),
+            reporter: reporter.into(),
+            reason: reason.into(),
+            date,
+        };
+
+        Ok(report.validate().map(|_| report)?)
+    }
+}
+
+impl AuditEntry {
+    pub fn new(action: AuditAction, actor: &str, target: &str, date: u128) -> Self {
+        Self {
+            action,
+            actor: actor.into(),
+            target: target.into(),
+            reason: None,
+            date,
+        }
+    }
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::DeleteComment => "delete_comment",
+            AuditAction::RestoreComment => "restore_comment",
+            AuditAction::ApproveComment => "approve_comment",
+            AuditAction::RejectComment => "reject_comment",
+            AuditAction::MarkSpam => "mark_spam",
+            AuditAction::UpdateThread => "update_thread",
+            AuditAction::LockThread => "lock_thread",
+            AuditAction::UnlockThread => "unlock_thread",
+            AuditAction::DeleteThread => "delete_thread",
+            AuditAction::Ban => "ban",
+            AuditAction::Unban => "unban",
+            AuditAction::GrantRole => "grant_role",
+            AuditAction::RevokeRole => "revoke_role",
+            AuditAction::Failed => "failed",
+        }
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "delete_comment" => Ok(AuditAction::DeleteComment),
+            "restore_comment" => Ok(AuditAction::RestoreComment),
+            "approve_comment" => Ok(AuditAction::ApproveComment),
+            "reject_comment" => Ok(AuditAction::RejectComment),
+            "mark_spam" => Ok(AuditAction::MarkSpam),
+            "update_thread" => Ok(AuditAction::UpdateThread),
+            "lock_thread" => Ok(AuditAction::LockThread),
+            "unlock_thread" => Ok(AuditAction::UnlockThread),
+            "delete_thread" => Ok(AuditAction::DeleteThread),
+            "ban" => Ok(AuditAction::Ban),
+            "unban" => Ok(AuditAction::Unban),
+            "grant_role" => Ok(AuditAction::GrantRole),
+            "revoke_role" => Ok(AuditAction::RevokeRole),
+            "failed" => Ok(AuditAction::Failed),
+            _ => Err(format!("Unknown audit action: {}", action)),
+        }
+    }
+}
+
+/// Counts bans created by this process, so ids stay distinct within the same millisecond.
+static BAN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl Ban {
+    pub fn new(target: BanTarget, banned_by: &str, date: u128) -> Self {
+        let count = BAN_COUNTER.fetch_add(1, Ordering::Relaxed);
+        // randomly keyed, so processes sharing a store don't produce the same ids
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(count);
+        let bytes = [
+            target.kind().as_bytes(),
+            target.value().as_bytes(),
+            date.to_be_bytes().as_slice(),
+            count.to_be_bytes().as_slice(),
+            hasher.finish().to_be_bytes().as_slice(),
+        ]
+        .concat();
+
+        Self {
+            id: hash(bytes.as_slice()),
+            target,
+            shadow: false,
+            reason: None,
+            banned_by: banned_by.into(),
+            date,
+            expires_at: None,
+        }
+    }
+
+    pub fn is_active(&self, now: u128) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    /// Whether the ban covers someone posting under `email`, signed in as the user named
+    /// `user_name` and from `ip` as far as known.
+    pub fn applies_to(&self, user_name: Option<&str>, email: &str, ip: Option<IpAddr>) -> bool {
+        match &self.target {
+            BanTarget::User(name) => user_name == Some(name.as_str()),
+            BanTarget::EmailDomain(domain) => {
+                let email_domain = email
+                    .rsplit_once('@')
+                    .map(|(_, email_domain)| email_domain.to_lowercase())
+                    .unwrap_or_default();
+                let domain = domain.to_lowercase();
+
+                email_domain == domain || email_domain.ends_with(&format!(".{}", domain))
+            }
+            BanTarget::IpRange(range) => ip.is_some_and(|ip| range.contains(ip)),
+        }
+    }
+}
+
+impl BanTarget {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BanTarget::User(_) => "user",
+            BanTarget::EmailDomain(_) => "email_domain",
+            BanTarget::IpRange(_) => "ip_range",
+        }
+    }
+
+    pub fn value(&self) -> String {
+        match self {
+            BanTarget::User(name) => name.clone(),
+            BanTarget::EmailDomain(domain) => domain.clone(),
+            BanTarget::IpRange(range) => range.to_string(),
+        }
+    }
+
+    /// The inverse of [`BanTarget::kind`] and [`BanTarget::value`].
+    pub fn parse(kind: &str, value: &str) -> Result<Self, String> {
+        match kind {
+            "user" => Ok(BanTarget::User(value.into())),
+            "email_domain" => Ok(BanTarget::EmailDomain(value.into())),
+            "ip_range" => value.parse().map(BanTarget::IpRange),
+            _ => Err(format!("Unknown ban kind: {}", kind)),
+        }
+    }
+}
+
+impl IpRange {
+    /// Whether `ip` lies in the range. IPv4-mapped IPv6 addresses like `::ffff:192.0.2.17`, as
+    /// seen from dual-stack sockets, count as the IPv4 address they map, other addresses of the
+    /// other IP version never lie in the range.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let canonical = self.canonical();
+
+        match (canonical.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(network) as u128,
+                u32::from(ip) as u128,
+                32,
+                canonical.prefix_len,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(network), u128::from(ip), 128, canonical.prefix_len)
+            }
+            _ => false,
+        }
+    }
+
+    /// The range with an IPv4-mapped network turned into the IPv4 network, provided it doesn't
+    /// extend beyond the mapped addresses.
+    fn canonical(&self) -> Self {
+        match self.address.to_canonical() {
+            IpAddr::V4(network) if self.address.is_ipv6() && self.prefix_len >= 96 => Self {
+                address: IpAddr::V4(network),
+                prefix_len: self.prefix_len - 96,
+            },
+            _ => *self,
+        }
+    }
+}
+
+fn prefix_matches(network: u128, ip: u128, bits: u32, prefix_len: u8) -> bool {
+    let host_bits = bits - prefix_len as u32;
+
+    network.checked_shr(host_bits).unwrap_or(0) == ip.checked_shr(host_bits).unwrap_or(0)
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid IP range: {}", range);
+        let (address, prefix_len) = match range.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (range, None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| invalid())?,
+            None => bits,
+        };
+
+        if prefix_len > bits {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            address,
+            prefix_len,
+        })
+    }
+}
+
+impl CommentTree {
+    /// Nests `comments` below the comment with hash `hash`, which has to be among them.
+    ///
+    /// Replies keep the order they have in `comments`, comments not descending from the root
+    /// are left out.
+    pub fn build(hash: &str, comments: Vec<Comment>) -> Option<Self> {
+        let mut root = None;
+        let mut replies: HashMap<String, Vec<Comment>> = HashMap::new();
+
+        for comment in comments {
+            if comment.hash == hash {
+                root = Some(comment);
+            } else if let Some(parent_hash) = &comment.parent_hash {
+                replies.entry(parent_hash.clone()).or_default().push(comment);
+            }
+        }
+
+        root.map(|root| Self::nest(root, &mut replies))
+    }
+
+    fn nest(comment: Comment, replies: &mut HashMap<String, Vec<Comment>>) -> Self {
+        let children = replies.remove(&comment.hash).unwrap_or_default();
+
+        Self {
+            replies: children
+                .into_iter()
+                .map(|child| Self::nest(child, replies))
+                .collect(),
+            comment,
+        }
+    }
 }
 
 impl From<ValidationErrors> for Error {
@@ -118,7 +729,10 @@ fn hash(input: &[u8]) -> String {
 mod tests {
     use crate::data::hash;
 
-    use super::{Comment, User};
+    use super::{
+        AuditAction, Ban, BanTarget, Comment, CommentStatus, CommentTree, IpRange, Report,
+        Revision, Role, Scope, User,
+    };
 
     #[test]
     fn test_validate_user() {
@@ -151,4 +765,203 @@ mod tests {
             "2dc5498cc534404cf74626a1ec1b1035055c57e22a2ab6abb25de8af910dba4e".to_string()
         );
     }
+
+    #[test]
+    fn test_validate_reply() {
+        let thread_hash = hash("test".as_bytes());
+
+        assert!(Comment::try_new_reply(&thread_hash, "parent", "test@mail.de", 1, "reply").is_err());
+
+        let reply = Comment::try_new_reply(&thread_hash, &thread_hash, "test@mail.de", 1, "reply");
+
+        assert_eq!(reply.unwrap().parent_hash, Some(thread_hash));
+    }
+
+    #[test]
+    fn test_edit_comment() {
+        let comment = Comment::new(&hash("test".as_bytes()), "test@mail.de", 1, "content");
+        let edited = comment.try_edit("edited", 2).unwrap();
+
+        assert_eq!(edited.hash, comment.hash);
+        assert_eq!(edited.content, "edited");
+        assert_eq!(edited.edited_at, Some(2));
+        assert!(edited.try_edit("", 3).is_err());
+        assert_eq!(
+            comment.revision(),
+            Revision {
+                comment_hash: comment.hash.clone(),
+                content: "content".to_string(),
+                date: 1,
+            }
+        );
+        assert_eq!(edited.revision().date, 2);
+    }
+
+    #[test]
+    fn test_validate_report() {
+        let comment_hash = hash("test".as_bytes());
+
+        assert!(Report::try_new(&comment_hash, "name", "spam", 1).is_ok());
+        assert!(Report::try_new(&comment_hash, "name", "", 1).is_err());
+        assert!(Report::try_new(&comment_hash, "name", &"a".repeat(513), 1).is_err());
+        assert!(Report::try_new("comment", "name", "spam", 1).is_err());
+    }
+
+    #[test]
+    fn test_comment_status() {
+        for status in [
+            CommentStatus::Pending,
+            CommentStatus::Approved,
+            CommentStatus::Rejected,
+            CommentStatus::Spam,
+        ] {
+            assert_eq!(status.as_str().parse(), Ok(status));
+        }
+        assert!("published".parse::<CommentStatus>().is_err());
+    }
+
+    #[test]
+    fn test_roles_and_scopes() {
+        assert!(Role::Commenter < Role::Moderator);
+        assert!(Role::Admin < Role::Owner);
+
+        for role in [Role::Commenter, Role::Moderator, Role::Admin, Role::Owner] {
+            assert_eq!(role.as_str().parse(), Ok(role));
+        }
+        assert!("root".parse::<Role>().is_err());
+
+        let thread = Scope::Thread(hash("test".as_bytes()));
+
+        assert_eq!(thread.to_string().parse(), Ok(thread.clone()));
+        assert_eq!("site".parse(), Ok(Scope::Site));
+        assert!("forum:1".parse::<Scope>().is_err());
+        assert!(Scope::Site.covers(&thread));
+        assert!(thread.covers(&thread));
+        assert!(!thread.covers(&Scope::Site));
+        assert!(!thread.covers(&Scope::Thread(hash("other".as_bytes()))));
+    }
+
+    #[test]
+    fn test_parse_audit_action() {
+        for action in [
+            AuditAction::DeleteComment,
+            AuditAction::MarkSpam,
+            AuditAction::GrantRole,
+        ] {
+            assert_eq!(action.to_string().parse(), Ok(action));
+        }
+        assert!("drop_table".parse::<AuditAction>().is_err());
+    }
+
+    #[test]
+    fn test_build_comment_tree() {
+        let thread_hash = hash("test".as_bytes());
+        let root = Comment::new(&thread_hash, "test@mail.de", 1, "root");
+        let reply = Comment::new_reply(&thread_hash, &root.hash, "test@mail.de", 2, "reply");
+        let nested = Comment::new_reply(&thread_hash, &reply.hash, "test@mail.de", 3, "nested");
+        let reply_2 = Comment::new_reply(&thread_hash, &root.hash, "test@mail.de", 4, "reply 2");
+        let other = Comment::new(&thread_hash, "test@mail.de", 5, "other");
+
+        let tree = CommentTree::build(
+            &root.hash,
+            vec![other, root.clone(), reply.clone(), nested.clone(), reply_2.clone()],
+        );
+
+        assert_eq!(
+            tree,
+            Some(CommentTree {
+                comment: root,
+                replies: vec![
+                    CommentTree {
+                        comment: reply,
+                        replies: vec![CommentTree {
+                            comment: nested,
+                            replies: vec![],
+                        }],
+                    },
+                    CommentTree {
+                        comment: reply_2,
+                        replies: vec![],
+                    },
+                ],
+            })
+        );
+        assert_eq!(CommentTree::build("unknown", vec![]), None);
+    }
+
+    #[test]
+    fn test_ip_range() {
+        let range: IpRange = "192.0.2.0/24".parse().unwrap();
+
+        assert!(range.contains("192.0.2.17".parse().unwrap()));
+        assert!(!range.contains("192.0.3.17".parse().unwrap()));
+        assert!(range.contains("::ffff:192.0.2.17".parse().unwrap()));
+        assert!(!range.contains("::ffff:192.0.3.17".parse().unwrap()));
+        assert!("::ffff:192.0.2.0/120"
+            .parse::<IpRange>()
+            .unwrap()
+            .contains("192.0.2.17".parse().unwrap()));
+        assert!(!"2001:db8::/32"
+            .parse::<IpRange>()
+            .unwrap()
+            .contains("192.0.2.17".parse().unwrap()));
+        assert_eq!(range.to_string(), "192.0.2.0/24");
+
+        let single: IpRange = "2001:db8::1".parse().unwrap();
+
+        assert_eq!(single.prefix_len, 128);
+        assert!(single.contains("2001:db8::1".parse().unwrap()));
+        assert!(!single.contains("2001:db8::2".parse().unwrap()));
+        assert!("0.0.0.0/0"
+            .parse::<IpRange>()
+            .unwrap()
+            .contains("203.0.113.7".parse().unwrap()));
+        assert!("192.0.2.0/33".parse::<IpRange>().is_err());
+        assert!("example.com".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn test_ban_applies_to() {
+        let ip = Some("192.0.2.17".parse().unwrap());
+        let user = Ban::new(BanTarget::User("name".to_string()), "admin", 1);
+        let domain = Ban::new(
+            BanTarget::EmailDomain("Example.com".to_string()),
+            "admin",
+            1,
+        );
+        let range = Ban::new(
+            BanTarget::parse("ip_range", "192.0.2.0/24").unwrap(),
+            "admin",
+            1,
+        );
+
+        assert!(user.applies_to(Some("name"), "test@mail.de", None));
+        assert!(!user.applies_to(None, "name@mail.de", ip));
+        assert!(domain.applies_to(None, "test@example.com", None));
+        assert!(domain.applies_to(None, "test@mail.EXAMPLE.com", None));
+        assert!(!domain.applies_to(None, "test@notexample.com", None));
+        assert!(range.applies_to(None, "test@mail.de", ip));
+        assert!(!range.applies_to(Some("name"), "test@mail.de", None));
+        assert_ne!(user.id, domain.id);
+    }
+
+    #[test]
+    fn test_ban_ids_are_unique() {
+        let first = Ban::new(BanTarget::User("name".to_string()), "admin", 1);
+        let second = Ban::new(BanTarget::User("name".to_string()), "admin", 1);
+
+        assert_ne!(first.id, second.id);
+    }
+
+    #[test]
+    fn test_ban_expiry() {
+        let mut ban = Ban::new(BanTarget::User("name".to_string()), "admin", 1);
+
+        assert!(ban.is_active(u128::MAX));
+
+        ban.expires_at = Some(10);
+
+        assert!(ban.is_active(9));
+        assert!(!ban.is_active(10));
+    }
 }
@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
 use validator::ValidationErrors;
 
@@ -13,13 +13,29 @@ pub enum Error {
     StoreError(StoreError),
     SignupError,
     NewtorkError,
-    SignatureError(Vec<String>)
+    SignatureError(Vec<String>),
+    AuthenticationError,
+    PermissionError,
+    /// A guest comment came without a valid solution to a proof of work challenge
+    ProofOfWorkError,
+    /// The commenter is banned, until the time given if the ban expires
+    Banned(Option<u128>),
+    EditWindowClosed,
+    ThreadLocked,
+    /// Too many requests, the next one is allowed after the duration
+    RateLimited(Duration),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum StoreError {
     NameNotUnique,
-    ThreadNotExists(String)
+    ThreadNotExists(String),
+    DatabaseError(String),
+    ParentNotExists(String),
+    /// Another user registered with the email address already
+    EmailNotUnique,
+    /// A ban with the id exists already
+    BanNotUnique(String),
 }
 
 impl<'a> CommentError<'a> for Error {
@@ -31,11 +47,22 @@ impl<'a> CommentError<'a> for Error {
                 match store_error {
                     StoreError::NameNotUnique => "E-01-01",
                     StoreError::ThreadNotExists(_) => "E-01-02",
+                    StoreError::DatabaseError(_) => "E-01-03",
+                    StoreError::ParentNotExists(_) => "E-01-04",
+                    StoreError::EmailNotUnique => "E-01-05",
+                    StoreError::BanNotUnique(_) => "E-01-06",
                 }
             },
-            Error::NewtorkError => todo!(),
-            Error::SignupError => todo!(),
-            Error::SignatureError(_) => todo!(),
+            Error::NewtorkError => "E-02-01",
+            Error::SignupError => "E-03-01",
+            Error::SignatureError(_) => "E-03-02",
+            Error::AuthenticationError => "E-03-03",
+            Error::PermissionError => "E-03-04",
+            Error::ProofOfWorkError => "E-03-05",
+            Error::Banned(_) => "E-03-06",
+            Error::EditWindowClosed => "E-04-01",
+            Error::ThreadLocked => "E-04-02",
+            Error::RateLimited(_) => "E-05-01",
         }
     }
 
@@ -49,14 +76,52 @@ struct PrettyReport {
 }
 
 impl Display for PrettyReport {
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        todo!()
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
     }
 }
 
 impl From<&Error> for PrettyReport {
-    fn from(_: &Error) -> Self {
-        todo!()
+    fn from(error: &Error) -> Self {
+        let message = match error {
+            Error::ValidationError { validation_errors } => {
+                format!("Validation failed: {}", validation_errors)
+            }
+            Error::StoreError(StoreError::NameNotUnique) => "Name is already taken".to_string(),
+            Error::StoreError(StoreError::ThreadNotExists(hash)) => {
+                format!("Thread {} does not exist", hash)
+            }
+            Error::StoreError(StoreError::DatabaseError(message)) => {
+                format!("Database error: {}", message)
+            }
+            Error::StoreError(StoreError::ParentNotExists(hash)) => {
+                format!("Comment {} does not exist in this thread", hash)
+            }
+            Error::StoreError(StoreError::EmailNotUnique) => {
+                "Email is already registered".to_string()
+            }
+            Error::StoreError(StoreError::BanNotUnique(id)) => format!("Ban {} exists already", id),
+            Error::SignupError => "Signup failed".to_string(),
+            Error::NewtorkError => "Network error".to_string(),
+            Error::SignatureError(errors) => format!("Invalid signature: {}", errors.join(", ")),
+            Error::AuthenticationError => "Authentication required".to_string(),
+            Error::PermissionError => "Permission denied".to_string(),
+            Error::ProofOfWorkError => "Missing or invalid proof of work".to_string(),
+            Error::Banned(_) => "You are banned from commenting".to_string(),
+            Error::EditWindowClosed => "Comment can no longer be edited".to_string(),
+            Error::ThreadLocked => "Thread is locked".to_string(),
+            Error::RateLimited(retry_after) => {
+                format!("Too many requests, retry in {} seconds", retry_after.as_secs_f64().ceil())
+            }
+        };
+
+        PrettyReport { message }
+    }
+}
+
+impl From<StoreError> for Error {
+    fn from(e: StoreError) -> Self {
+        Error::StoreError(e)
     }
 }
 
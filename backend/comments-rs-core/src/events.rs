@@ -0,0 +1,61 @@
+use std::pin::Pin;
+
+use futures_core::Stream;
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+use crate::data::{Comment, CommentStatus};
+
+/// Number of events buffered per subscriber, slower subscribers miss the oldest events.
+const CAPACITY: usize = 256;
+
+pub type EventStream = Pin<Box<dyn Stream<Item = CommentEvent> + Send>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommentEvent {
+    Added(Comment),
+    Edited(Comment),
+    Deleted(Comment),
+    /// The moderation status changed from `previous`, so the comment may have become public or
+    /// hidden
+    StatusChanged {
+        comment: Comment,
+        previous: CommentStatus,
+    },
+}
+
+impl CommentEvent {
+    pub fn comment(&self) -> &Comment {
+        match self {
+            CommentEvent::Added(comment)
+            | CommentEvent::Edited(comment)
+            | CommentEvent::Deleted(comment)
+            | CommentEvent::StatusChanged { comment, .. } => comment,
+        }
+    }
+}
+
+/// Fans out the changes of a store to all of its subscribers.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<CommentEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: CommentEvent) {
+        // sending only fails if nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> EventStream {
+        Box::pin(BroadcastStream::new(self.sender.subscribe()).filter_map(|event| event.ok()))
+    }
+}
@@ -4,8 +4,13 @@ use traits::{Frontend, UserStore};
 
 pub mod data;
 pub mod error;
+pub mod events;
+pub mod spam;
 pub mod traits;
 
+/// Shared with the frontend, which solves the challenges
+pub use comments_rs_pow as pow;
+
 pub struct Components {
     pub frontend: Option<Arc<dyn Frontend + Send + Sync>>,
     pub user_store: Option<Arc<dyn UserStore + Send + Sync>>
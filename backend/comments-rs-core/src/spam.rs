@@ -0,0 +1,610 @@
+//! Filters deciding whether new comments are published, held for moderation or rejected as
+//! spam before they are saved.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::ready,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    data::{Comment, RequestInfo},
+    traits::{SpamFilter, SpamResult},
+};
+
+lazy_static! {
+    static ref LINK_REGEX: Regex = Regex::new(r"(?i)\b(?:https?://|www\.)").unwrap();
+    static ref TOKEN_REGEX: Regex = Regex::new(r"[\p{L}\p{N}$€£']{2,32}").unwrap();
+}
+
+/// What happens to a comment, ordered by severity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Verdict {
+    /// Published as far as the approval policy allows
+    Approve,
+    /// Held for moderation
+    Hold,
+    /// Saved as spam, hidden from everyone but moderators
+    Reject,
+}
+
+/// Runs comments through filters in order and settles on the most severe verdict.
+///
+/// A rejecting filter ends the run, filters failing to reach a verdict, e.g. on network errors,
+/// hold the comment so it isn't lost.
+#[derive(Clone, Default)]
+pub struct SpamFilterChain {
+    filters: Vec<Arc<dyn SpamFilter>>,
+}
+
+impl SpamFilterChain {
+    pub fn new(filters: Vec<Arc<dyn SpamFilter>>) -> Self {
+        Self { filters }
+    }
+
+    pub fn with_filter(mut self, filter: Arc<dyn SpamFilter>) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub async fn check(&self, comment: &Comment, request: &RequestInfo) -> Verdict {
+        let mut verdict = Verdict::Approve;
+
+        for filter in &self.filters {
+            verdict = verdict.max(
+                filter
+                    .check(comment, request)
+                    .await
+                    .unwrap_or(Verdict::Hold),
+            );
+
+            if verdict == Verdict::Reject {
+                break;
+            }
+        }
+
+        verdict
+    }
+
+    /// Tells every filter that `comment` was saved, failures are ignored since the comment is
+    /// saved either way.
+    pub async fn saved(&self, comment: &Comment) {
+        for filter in &self.filters {
+            filter.saved(comment).await.ok();
+        }
+    }
+
+    /// Passes a moderator's decision on to every filter, failures are ignored since the decision
+    /// stands either way.
+    pub async fn learn(&self, comment: &Comment, spam: bool) {
+        for filter in &self.filters {
+            filter.learn(comment, spam).await.ok();
+        }
+    }
+}
+
+/// Gives `verdict` for comments with more than `max_links` links.
+pub struct LinkLimit {
+    max_links: usize,
+    verdict: Verdict,
+}
+
+impl LinkLimit {
+    /// Holds comments with more than `max_links` links.
+    pub fn new(max_links: usize) -> Self {
+        Self {
+            max_links,
+            verdict: Verdict::Hold,
+        }
+    }
+
+    pub fn with_verdict(mut self, verdict: Verdict) -> Self {
+        self.verdict = verdict;
+        self
+    }
+}
+
+impl SpamFilter for LinkLimit {
+    fn check(&self, comment: &Comment, _request: &RequestInfo) -> SpamResult<Verdict> {
+        let links = LINK_REGEX.find_iter(&comment.content).count();
+        let verdict = if links > self.max_links {
+            self.verdict
+        } else {
+            Verdict::Approve
+        };
+
+        Box::pin(ready(Ok(verdict)))
+    }
+}
+
+/// Gives `verdict` for comments containing a blocked word, regardless of case, or matching a
+/// blocked pattern.
+pub struct Blocklist {
+    words: HashSet<String>,
+    patterns: Vec<Regex>,
+    verdict: Verdict,
+}
+
+impl Blocklist {
+    /// Rejects comments containing any of `words` or matching any of `patterns`.
+    pub fn new<W: AsRef<str>, P: AsRef<str>>(
+        words: &[W],
+        patterns: &[P],
+    ) -> Result<Self, regex::Error> {
+        Ok(Self {
+            words: words
+                .iter()
+                .map(|word| word.as_ref().to_lowercase())
+                .collect(),
+            patterns: patterns
+                .iter()
+                .map(|pattern| Regex::new(pattern.as_ref()))
+                .collect::<Result<_, _>>()?,
+            verdict: Verdict::Reject,
+        })
+    }
+
+    pub fn with_verdict(mut self, verdict: Verdict) -> Self {
+        self.verdict = verdict;
+        self
+    }
+}
+
+impl SpamFilter for Blocklist {
+    fn check(&self, comment: &Comment, _request: &RequestInfo) -> SpamResult<Verdict> {
+        let blocked = tokens(&comment.content).any(|token| self.words.contains(&token))
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&comment.content));
+        let verdict = if blocked {
+            self.verdict
+        } else {
+            Verdict::Approve
+        };
+
+        Box::pin(ready(Ok(verdict)))
+    }
+}
+
+/// Rejects comments repeating the content of one of the last `capacity` comments saved, no
+/// matter who posted it or where.
+///
+/// Case and whitespace are ignored, contents shorter than `min_length` characters are never
+/// considered duplicates so short answers like "Thanks!" can be posted by everyone. A `capacity`
+/// of 0 remembers nothing and thereby disables the filter.
+pub struct DuplicateContent {
+    capacity: usize,
+    min_length: usize,
+    recent: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl DuplicateContent {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            min_length: 16,
+            recent: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// The digest of the normalized content, `None` for contents too short to be considered.
+    fn digest(&self, content: &str) -> Option<Vec<u8>> {
+        let normalized = content
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+
+        (normalized.chars().count() >= self.min_length)
+            .then(|| Sha256::digest(normalized.as_bytes()).to_vec())
+    }
+}
+
+impl SpamFilter for DuplicateContent {
+    fn check(&self, comment: &Comment, _request: &RequestInfo) -> SpamResult<Verdict> {
+        let duplicate = self
+            .digest(&comment.content)
+            .is_some_and(|digest| self.recent.lock().unwrap().contains(&digest));
+        let verdict = if duplicate {
+            Verdict::Reject
+        } else {
+            Verdict::Approve
+        };
+
+        Box::pin(ready(Ok(verdict)))
+    }
+
+    fn saved(&self, comment: &Comment) -> SpamResult<()> {
+        if let Some(digest) = self
+            .digest(&comment.content)
+            .filter(|_| self.capacity > 0)
+        {
+            let mut recent = self.recent.lock().unwrap();
+
+            if !recent.contains(&digest) {
+                while recent.len() >= self.capacity {
+                    recent.pop_front();
+                }
+                recent.push_back(digest);
+            }
+        }
+
+        Box::pin(ready(Ok(())))
+    }
+}
+
+/// A naive Bayes classifier trained on the decisions of moderators.
+///
+/// Comments are held from a spam probability of `hold_threshold` and rejected from
+/// `reject_threshold` on. Until it has seen both spam and ham the classifier approves
+/// everything.
+///
+/// Decisions on the last `learned_limit` comments learned can be reversed by moderators, older
+/// ones stay counted as they were.
+pub struct NaiveBayes {
+    hold_threshold: f64,
+    reject_threshold: f64,
+    learned_limit: usize,
+    model: Mutex<BayesModel>,
+}
+
+#[derive(Default)]
+struct BayesModel {
+    /// Occurrences of every token in spam and in ham
+    tokens: HashMap<String, (u32, u32)>,
+    spam_tokens: u32,
+    ham_tokens: u32,
+    spam_documents: u32,
+    ham_documents: u32,
+    /// Whether the comment with a hash was learned as spam, so decisions count once per comment
+    learned: HashMap<String, bool>,
+    /// The hashes in `learned`, oldest first
+    learned_order: VecDeque<String>,
+}
+
+impl BayesModel {
+    /// Adds the tokens of `text` to the counts of spam or ham, or removes them again.
+    fn count(&mut self, text: &str, spam: bool, add: bool) {
+        let update = |count: &mut u32| {
+            *count = if add {
+                *count + 1
+            } else {
+                count.saturating_sub(1)
+            }
+        };
+
+        for token in tokens(text) {
+            let (spam_count, ham_count) = self.tokens.entry(token).or_default();
+
+            if spam {
+                update(spam_count);
+                update(&mut self.spam_tokens);
+            } else {
+                update(ham_count);
+                update(&mut self.ham_tokens);
+            }
+        }
+
+        if spam {
+            update(&mut self.spam_documents);
+        } else {
+            update(&mut self.ham_documents);
+        }
+    }
+}
+
+impl Default for NaiveBayes {
+    fn default() -> Self {
+        Self {
+            hold_threshold: 0.7,
+            reject_threshold: 0.95,
+            learned_limit: 10_000,
+            model: Mutex::new(BayesModel::default()),
+        }
+    }
+}
+
+impl NaiveBayes {
+    pub fn with_thresholds(mut self, hold_threshold: f64, reject_threshold: f64) -> Self {
+        self.hold_threshold = hold_threshold;
+        self.reject_threshold = reject_threshold;
+        self
+    }
+
+    pub fn with_learned_limit(mut self, learned_limit: usize) -> Self {
+        self.learned_limit = learned_limit;
+        self
+    }
+
+    pub fn train(&self, text: &str, spam: bool) {
+        self.model.lock().unwrap().count(text, spam, true);
+    }
+
+    /// The probability of `text` being spam, `None` while untrained.
+    pub fn spam_probability(&self, text: &str) -> Option<f64> {
+        let model = self.model.lock().unwrap();
+
+        if model.spam_documents == 0 || model.ham_documents == 0 {
+            return None;
+        }
+
+        // Laplace smoothing keeps unseen tokens from ruling out either class
+        let vocabulary = model.tokens.len() as f64;
+        let documents = (model.spam_documents + model.ham_documents) as f64;
+        let mut spam_score = (model.spam_documents as f64 / documents).ln();
+        let mut ham_score = (model.ham_documents as f64 / documents).ln();
+
+        for token in tokens(text) {
+            let (spam_count, ham_count) = model.tokens.get(&token).copied().unwrap_or_default();
+
+            spam_score +=
+                ((spam_count as f64 + 1.0) / (model.spam_tokens as f64 + vocabulary)).ln();
+            ham_score += ((ham_count as f64 + 1.0) / (model.ham_tokens as f64 + vocabulary)).ln();
+        }
+
+        Some(1.0 / (1.0 + (ham_score - spam_score).exp()))
+    }
+}
+
+impl SpamFilter for NaiveBayes {
+    fn check(&self, comment: &Comment, _request: &RequestInfo) -> SpamResult<Verdict> {
+        let verdict = match self.spam_probability(&comment.content) {
+            Some(probability) if probability >= self.reject_threshold => Verdict::Reject,
+            Some(probability) if probability >= self.hold_threshold => Verdict::Hold,
+            _ => Verdict::Approve,
+        };
+
+        Box::pin(ready(Ok(verdict)))
+    }
+
+    /// Counts every comment once, a moderator reversing a decision moves the comment over to
+    /// the other class.
+    fn learn(&self, comment: &Comment, spam: bool) -> SpamResult<()> {
+        let mut model = self.model.lock().unwrap();
+
+        match model.learned.insert(comment.hash.clone(), spam) {
+            Some(learned) if learned == spam => {}
+            Some(learned) => {
+                model.count(&comment.content, learned, false);
+                model.count(&comment.content, spam, true);
+            }
+            None => {
+                model.count(&comment.content, spam, true);
+                model.learned_order.push_back(comment.hash.clone());
+
+                while model.learned_order.len() > self.learned_limit {
+                    if let Some(hash) = model.learned_order.pop_front() {
+                        model.learned.remove(&hash);
+                    }
+                }
+            }
+        }
+
+        Box::pin(ready(Ok(())))
+    }
+}
+
+/// Splits `text` into lowercase words and numbers.
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    TOKEN_REGEX
+        .find_iter(text)
+        .map(|token| token.as_str().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        data::{Comment, RequestInfo},
+        error::Error,
+        spam::{Blocklist, DuplicateContent, LinkLimit, NaiveBayes, SpamFilterChain, Verdict},
+        traits::{SpamFilter, SpamResult},
+    };
+
+    fn comment(content: &str) -> Comment {
+        Comment::new(&"0".repeat(64), "test@mail.com", 17, content)
+    }
+
+    async fn check(filter: &dyn SpamFilter, content: &str) -> Verdict {
+        filter
+            .check(&comment(content), &RequestInfo::default())
+            .await
+            .unwrap()
+    }
+
+    struct FailingFilter;
+
+    impl SpamFilter for FailingFilter {
+        fn check(&self, _comment: &Comment, _request: &RequestInfo) -> SpamResult<Verdict> {
+            Box::pin(async { Err(Error::NewtorkError) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_limit() {
+        let filter = LinkLimit::new(1);
+
+        assert_eq!(
+            check(&filter, "see https://example.com").await,
+            Verdict::Approve
+        );
+        assert_eq!(
+            check(&filter, "see https://example.com and www.example.org").await,
+            Verdict::Hold
+        );
+        assert_eq!(
+            check(&filter.with_verdict(Verdict::Reject), "HTTP://a http://b").await,
+            Verdict::Reject
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocklist() {
+        let filter = Blocklist::new(&["Casino"], &[r"(?i)v[i1]agr[a@]"]).unwrap();
+
+        assert_eq!(check(&filter, "Visit our CASINO!").await, Verdict::Reject);
+        assert_eq!(check(&filter, "cheap v1agr@ here").await, Verdict::Reject);
+        assert_eq!(
+            check(&filter, "casinos aren't blocked").await,
+            Verdict::Approve
+        );
+        assert!(Blocklist::new::<&str, _>(&[], &["("]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_content() {
+        let filter = DuplicateContent::new(2);
+        let save = |content: &str| filter.saved(&comment(content));
+
+        assert_eq!(
+            check(&filter, "Buy cheap watches today").await,
+            Verdict::Approve
+        );
+        // only saved comments count
+        assert_eq!(
+            check(&filter, "Buy cheap watches today").await,
+            Verdict::Approve
+        );
+
+        save("Buy cheap watches today").await.unwrap();
+
+        assert_eq!(
+            check(&filter, "buy  cheap watches\ntoday").await,
+            Verdict::Reject
+        );
+
+        save("Thanks!").await.unwrap();
+
+        assert_eq!(check(&filter, "Thanks!").await, Verdict::Approve);
+
+        // only the last two contents are remembered
+        save("A second, longer comment").await.unwrap();
+        save("A third, longer comment").await.unwrap();
+
+        assert_eq!(
+            check(&filter, "Buy cheap watches today").await,
+            Verdict::Approve
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_content_disabled() {
+        let filter = DuplicateContent::new(0);
+
+        filter
+            .saved(&comment("Buy cheap watches today"))
+            .await
+            .unwrap();
+
+        assert!(filter.recent.lock().unwrap().is_empty());
+        assert_eq!(
+            check(&filter, "Buy cheap watches today").await,
+            Verdict::Approve
+        );
+    }
+
+    #[tokio::test]
+    async fn test_naive_bayes() {
+        let filter = NaiveBayes::default();
+
+        assert_eq!(filter.spam_probability("cheap pills"), None);
+        assert_eq!(check(&filter, "cheap pills").await, Verdict::Approve);
+
+        for _ in 0..5 {
+            filter.train("buy cheap pills online now", true);
+            filter.train("great article, thanks for the explanation", false);
+        }
+        filter
+            .learn(&comment("cheap pills, best prices online"), true)
+            .await
+            .unwrap();
+
+        assert!(filter.spam_probability("cheap pills online").unwrap() > 0.95);
+        assert!(filter.spam_probability("thanks for the article").unwrap() < 0.05);
+        assert_eq!(check(&filter, "cheap pills online").await, Verdict::Reject);
+        assert_eq!(
+            check(&filter, "thanks for the article").await,
+            Verdict::Approve
+        );
+    }
+
+    #[tokio::test]
+    async fn test_naive_bayes_learns_comments_once() {
+        let filter = NaiveBayes::default();
+        let spam = comment("cheap pills online");
+        let ham = comment("thanks for the article");
+
+        filter.learn(&ham, false).await.unwrap();
+        for _ in 0..5 {
+            filter.learn(&spam, true).await.unwrap();
+        }
+
+        // a single spam comment against a single ham comment
+        assert!((filter.spam_probability("").unwrap() - 0.5).abs() < 1e-9);
+
+        filter.learn(&spam, false).await.unwrap();
+
+        assert_eq!(filter.spam_probability("cheap pills online"), None);
+    }
+
+    #[tokio::test]
+    async fn test_naive_bayes_forgets_old_decisions() {
+        let filter = NaiveBayes::default().with_learned_limit(1);
+        let spam = comment("cheap pills online");
+        let ham = comment("thanks for the article");
+
+        filter.learn(&spam, true).await.unwrap();
+        filter.learn(&ham, false).await.unwrap();
+
+        assert_eq!(filter.model.lock().unwrap().learned.len(), 1);
+
+        // the spam decision is no longer remembered, so it counts a second time
+        filter.learn(&spam, true).await.unwrap();
+
+        assert_eq!(filter.model.lock().unwrap().spam_documents, 2);
+        assert_eq!(filter.model.lock().unwrap().learned.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_chain() {
+        let chain = SpamFilterChain::default()
+            .with_filter(Arc::new(LinkLimit::new(0)))
+            .with_filter(Arc::new(
+                Blocklist::new(&["casino"], &[] as &[&str]).unwrap(),
+            ));
+        let request = RequestInfo::default();
+
+        assert_eq!(
+            chain.check(&comment("hello"), &request).await,
+            Verdict::Approve
+        );
+        assert_eq!(
+            chain.check(&comment("www.example.com"), &request).await,
+            Verdict::Hold
+        );
+        assert_eq!(
+            chain
+                .check(&comment("www.example.com casino"), &request)
+                .await,
+            Verdict::Reject
+        );
+
+        let chain = chain.with_filter(Arc::new(FailingFilter));
+
+        assert_eq!(
+            chain.check(&comment("hello"), &request).await,
+            Verdict::Hold
+        );
+    }
+}
@@ -1,37 +1,176 @@
-use std::{future::Future, pin::Pin, process::Output};
+use std::{future::Future, pin::Pin};
 
-use crate::{data::{User, Thread, Comment}, error::{StoreError, Error}};
+use crate::{data::{AuditEntry, Ban, User, Thread, Comment, Report, RoleGrant, Scope, CommentStatus, CommentTree, Deletion, RequestInfo, Revision}, error::{StoreError, Error}, events::EventStream, spam::Verdict};
 
 pub trait Frontend {
     fn run(&self) -> Pin<Box<dyn Future<Output = Result<(), Error>>>>;
 }
 
-pub type StoreResult<T> = Pin<Box<dyn Future<Output = Result<T, StoreError>> + Send + Sync>>;
-pub type SignupResult = Pin<Box<dyn Future<Output = Result<User, Error>> + Send + Sync>>;
+pub type StoreResult<T> = Pin<Box<dyn Future<Output = Result<T, StoreError>> + Send>>;
+pub type SignupResult = Pin<Box<dyn Future<Output = Result<User, Error>> + Send>>;
+pub type SpamResult<T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send>>;
 
 pub trait UserStore: Send + Sync {
-    fn save_user(& mut self, user: User) -> StoreResult<User>;
-    fn delete_user(& mut self, name: &str) -> StoreResult<Option<User>>;
+    /// Saves a new user, failing with [`StoreError::NameNotUnique`] or
+    /// [`StoreError::EmailNotUnique`] if another user has the same name or email.
+    fn save_user(&self, user: User) -> StoreResult<User>;
+    fn delete_user(&self, name: &str) -> StoreResult<Option<User>>;
     fn find_user(&self, name: &str) -> StoreResult<Option<User>>;
+    fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>>;
     fn find_all_users(&self) -> StoreResult<Vec<User>>;
 }
 
 pub trait ThreadStore: Send + Sync {
-    fn save_thread(& mut self, thread: Thread) -> StoreResult<Thread>;
-    fn delete_thread(& mut self, hash: &str) -> StoreResult<Option<Thread>>;
+    /// Saves a new thread and returns it. Saving a thread with the hash of a saved thread leaves
+    /// the saved thread as it is and returns that instead.
+    fn save_thread(&self, thread: Thread) -> StoreResult<Thread>;
+    /// Takes over the settings of `thread` for the saved thread with the same hash.
+    fn update_thread(&self, thread: Thread) -> StoreResult<Option<Thread>>;
+    fn delete_thread(&self, hash: &str) -> StoreResult<Option<Thread>>;
     fn find_thread_by_hash(&self, hash: &str) -> StoreResult<Option<Thread>>;
     fn find_all_threads(&self) -> StoreResult<Vec<Thread>>;
     
 }
 
 pub trait CommentStore: Send + Sync {
-    fn save_comment(& mut self, comment: Comment) -> StoreResult<Comment>;
-    fn delete_comment(& mut self, hash: &str) -> StoreResult<Option<Comment>>;
+    /// Fails with [`StoreError::ParentNotExists`] for replies to comments missing from the thread.
+    fn save_comment(&self, comment: Comment) -> StoreResult<Comment>;
+    /// Takes over content and edit time of `comment` for the saved comment with the same hash,
+    /// keeping the content it had so far as [`Revision`].
+    fn edit_comment(&self, comment: Comment) -> StoreResult<Option<Comment>>;
+    /// Deletes the comment along with all replies below it.
+    fn delete_comment(&self, hash: &str) -> StoreResult<Option<Comment>>;
+    /// Deletes a comment without replies, a comment with replies is kept as tombstone instead so
+    /// the discussion below it stays in place. Either way the comment is returned with
+    /// `deletion` set.
+    fn soft_delete_comment(&self, hash: &str, deletion: Deletion) -> StoreResult<Option<Comment>>;
+    /// Turns a tombstone back into a regular comment, returns `None` for any other comment.
+    fn restore_comment(&self, hash: &str) -> StoreResult<Option<Comment>>;
+    /// Sets the status of a comment, approving it deletes the reports on it.
+    fn set_comment_status(&self, hash: &str, status: CommentStatus) -> StoreResult<Option<Comment>>;
+    fn find_comment(&self, hash: &str) -> StoreResult<Option<Comment>>;
+    /// Returns the former contents of a comment, oldest first.
+    fn find_revisions(&self, hash: &str) -> StoreResult<Vec<Revision>>;
     fn find_thread_comments(&self, thread_hash: &str) -> StoreResult<Vec<Comment>>;
+    /// Counts the approved comments of a thread visible to the user with email `viewer`, shadowed
+    /// comments are only visible to their author.
+    fn count_thread_comments(&self, thread_hash: &str, viewer: Option<&str>) -> StoreResult<usize>;
+    /// Returns at most `limit` approved comments of a thread visible to the user with email
+    /// `viewer`, skipping the first `offset` ones.
+    fn find_thread_comments_page(
+        &self,
+        thread_hash: &str,
+        viewer: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Comment>>;
+    fn count_comments_by_status(&self, status: CommentStatus) -> StoreResult<usize>;
+    /// Returns at most `limit` comments of all threads in moderation status `status`, oldest
+    /// first, skipping the first `offset` ones.
+    fn find_comments_by_status(
+        &self,
+        status: CommentStatus,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Comment>>;
+    /// Returns the direct replies to the comment with hash `parent_hash` in the order they were
+    /// posted.
+    fn find_replies(&self, parent_hash: &str) -> StoreResult<Vec<Comment>>;
+    /// Returns the comment with hash `hash` along with all replies below it.
+    fn find_reply_tree(&self, hash: &str) -> StoreResult<Option<CommentTree>>;
+    /// Streams every comment saved or deleted after subscribing.
+    fn subscribe(&self) -> EventStream;
+}
+
+pub trait RoleStore: Send + Sync {
+    /// Grants a role, replacing the role the user had in the same scope.
+    fn save_role(&self, grant: RoleGrant) -> StoreResult<RoleGrant>;
+    fn delete_role(&self, user_name: &str, scope: &Scope) -> StoreResult<Option<RoleGrant>>;
+    /// Returns the roles of a user in all scopes.
+    fn find_roles(&self, user_name: &str) -> StoreResult<Vec<RoleGrant>>;
+    /// Returns the roles of all users in the order they were granted.
+    fn find_all_roles(&self) -> StoreResult<Vec<RoleGrant>>;
+}
+
+pub trait BanStore: Send + Sync {
+    /// Saves a new ban, failing with [`StoreError::BanNotUnique`] if its id is taken.
+    fn save_ban(&self, ban: Ban) -> StoreResult<Ban>;
+    fn delete_ban(&self, id: &str) -> StoreResult<Option<Ban>>;
+    /// Returns all bans including expired ones, oldest first.
+    fn find_all_bans(&self) -> StoreResult<Vec<Ban>>;
+}
+
+pub trait ReportStore: Send + Sync {
+    /// Saves a report, replacing an earlier report of the same reporter on the same comment, and
+    /// returns the number of distinct reporters of the comment.
+    fn save_report(&self, report: Report) -> StoreResult<usize>;
+    /// Returns the reports on a comment, oldest first.
+    fn find_reports(&self, comment_hash: &str) -> StoreResult<Vec<Report>>;
+    fn count_reported_comments(&self) -> StoreResult<usize>;
+    /// Returns at most `limit` reported comments together with their number of reports, most
+    /// reported first, skipping the first `offset` ones. Comments with as many reports come in
+    /// the order they were first reported.
+    fn find_reported_comments(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<(Comment, usize)>>;
+}
+
+/// Append-only record of moderation and administrative actions, entries are never changed or
+/// removed.
+pub trait AuditStore: Send + Sync {
+    fn append_audit_entry(&self, entry: AuditEntry) -> StoreResult<AuditEntry>;
+    /// Counts the entries acting on `target`, or all entries without one.
+    fn count_audit_entries(&self, target: Option<&str>) -> StoreResult<usize>;
+    /// Returns at most `limit` entries acting on `target`, or any entries without one, newest
+    /// first, skipping the first `offset` ones.
+    fn find_audit_entries(
+        &self,
+        target: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<AuditEntry>>;
+}
+
+/// Where to send a user signing in, see [`SignupProvider::authorize`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Authorization {
+    pub url: String,
+    /// Secret the caller keeps to itself, e.g. in a cookie, and hands back to finish the sign
+    /// in, so that it can't be finished by anyone else
+    pub binding: String,
 }
 
 pub trait SignupProvider: Send + Sync {
     fn name(&self) -> &'static str;
     fn signup(&self, email: &str) -> SignupResult;
     fn confirm(&self, token: &str, user_name: &str) -> SignupResult;
-}
\ No newline at end of file
+    /// Starts a sign in at providers the user has to be sent to first. The provider redirects
+    /// them to `redirect_uri` afterwards, the sign in is finished with
+    /// [`SignupProvider::signup_bound`] and [`SignupProvider::confirm_bound`].
+    fn authorize(&self, _redirect_uri: &str) -> Result<Authorization, Error> {
+        Err(Error::SignupError)
+    }
+    /// Like `signup`, for sign ins started with `authorize` which handed out `binding`.
+    fn signup_bound(&self, token: &str, _binding: &str) -> SignupResult {
+        self.signup(token)
+    }
+    /// Like `confirm`, for sign ins started with `authorize` which handed out `binding`.
+    fn confirm_bound(&self, token: &str, _binding: &str, user_name: &str) -> SignupResult {
+        self.confirm(token, user_name)
+    }
+}
+
+/// Judges new comments before they are saved, see [`crate::spam::SpamFilterChain`].
+pub trait SpamFilter: Send + Sync {
+    fn check(&self, comment: &Comment, request: &RequestInfo) -> SpamResult<Verdict>;
+    /// Notes that `comment` was saved after the check, with the status it got.
+    fn saved(&self, _comment: &Comment) -> SpamResult<()> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+    /// Learns from a moderator marking `comment` as spam or approving it.
+    fn learn(&self, _comment: &Comment, _spam: bool) -> SpamResult<()> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
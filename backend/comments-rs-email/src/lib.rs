@@ -0,0 +1,352 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use comments_rs_core_backend::{
+    data::User,
+    error::Error,
+    traits::{SignupProvider, SignupResult, UserStore},
+};
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, RngCore};
+use sha2::Sha256;
+use validator::{validate_email, ValidationError, ValidationErrors};
+
+pub use transport::{Mail, MailTransport, SmtpTransport};
+
+pub mod transport;
+
+/// How long a link is valid by default.
+const VALIDITY: Duration = Duration::from_secs(15 * 60);
+
+/// Signs users up by mailing them a link with a signed one-time token.
+///
+/// `signup` sends the link and returns the user as far as known, that is with an empty name,
+/// `confirm` takes the token from the link and creates the user under `user_name` unless the
+/// email belongs to a user already, who is returned then.
+#[derive(Clone)]
+pub struct EmailSignupProvider {
+    user_store: Arc<dyn UserStore>,
+    transport: Arc<dyn MailTransport>,
+    secret: Vec<u8>,
+    from: String,
+    link: String,
+    validity: Duration,
+    /// Nonces of redeemed tokens with the time they expire at.
+    redeemed: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl SignupProvider for EmailSignupProvider {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn signup(&self, email: &str) -> SignupResult {
+        let provider = self.clone();
+        let email = email.to_string();
+
+        Box::pin(async move {
+            if !validate_email(&email) {
+                let mut validation_errors = ValidationErrors::new();
+                validation_errors.add("email", ValidationError::new("email"));
+
+                return Err(Error::ValidationError { validation_errors });
+            }
+
+            let token = provider.token(&email, now() + provider.validity.as_secs());
+            let mail = Mail {
+                from: provider.from.clone(),
+                to: email.clone(),
+                subject: "Confirm your email".to_string(),
+                body: format!(
+                    "Open the following link to sign in:\r\n\r\n{}{}\r\n\r\nThe link expires in {} minutes.\r\n",
+                    provider.link,
+                    token,
+                    provider.validity.as_secs() / 60
+                ),
+            };
+
+            provider.transport.send(mail).await?;
+
+            Ok(User {
+                email,
+                name: String::new(),
+            })
+        })
+    }
+
+    fn confirm(&self, token: &str, user_name: &str) -> SignupResult {
+        let provider = self.clone();
+        let token = token.to_string();
+        let user_name = user_name.to_string();
+
+        Box::pin(async move {
+            let email = provider.redeem(&token)?;
+
+            match provider.user_store.find_user_by_email(&email).await? {
+                Some(user) => Ok(user),
+                None => {
+                    let user = User::try_new(&email, &user_name)?;
+
+                    Ok(provider.user_store.save_user(user).await?)
+                }
+            }
+        })
+    }
+}
+
+impl EmailSignupProvider {
+    /// Sends mails from `from` via `transport` and signs their tokens with `secret`.
+    ///
+    /// The token is appended to `link`, e.g. `https://example.com/confirm?token=`.
+    pub fn new(
+        user_store: Arc<dyn UserStore>,
+        transport: Arc<dyn MailTransport>,
+        secret: &[u8],
+        from: &str,
+        link: &str,
+    ) -> Self {
+        Self {
+            user_store,
+            transport,
+            secret: secret.to_vec(),
+            from: from.to_string(),
+            link: link.to_string(),
+            validity: VALIDITY,
+            redeemed: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Lets links expire `validity` after they were sent instead of after 15 minutes.
+    pub fn with_validity(mut self, validity: Duration) -> Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Creates a token `<payload>.<signature>` where the payload holds email, expiry and a nonce.
+    fn token(&self, email: &str, expires: u64) -> String {
+        let mut nonce = [0u8; 16];
+        thread_rng().fill_bytes(&mut nonce);
+
+        let payload = encode(format!("{}\n{}\n{}", email, expires, encode(nonce)).as_bytes());
+        let signature = encode(self.mac(&payload).finalize().into_bytes());
+
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Checks the token and returns its email, the token can't be redeemed again afterwards.
+    fn redeem(&self, token: &str) -> Result<String, Error> {
+        let (payload, signature) = token.split_once('.').ok_or_else(invalid_signature)?;
+        let signature = decode(signature).ok_or_else(invalid_signature)?;
+
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid_signature())?;
+
+        let payload = decode(payload)
+            .and_then(|payload| String::from_utf8(payload).ok())
+            .ok_or_else(invalid_signature)?;
+        let mut parts = payload.splitn(3, '\n');
+        let (email, expires, nonce) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(email), Some(expires), Some(nonce)) => (email, expires, nonce),
+            _ => return Err(invalid_signature()),
+        };
+        let expires: u64 = expires.parse().map_err(|_| invalid_signature())?;
+        let now = now();
+
+        if expires <= now {
+            return Err(Error::SignatureError(vec!["ExpiredSignature".to_string()]));
+        }
+
+        let mut redeemed = self.redeemed.lock().unwrap();
+        redeemed.retain(|_, expires| *expires > now);
+
+        if redeemed.insert(nonce.to_string(), expires).is_some() {
+            return Err(Error::SignupError);
+        }
+
+        Ok(email.to_string())
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+fn encode<T: AsRef<[u8]>>(input: T) -> String {
+    base64::encode_config(input, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(input: &str) -> Option<Vec<u8>> {
+    base64::decode_config(input, base64::URL_SAFE_NO_PAD).ok()
+}
+
+fn invalid_signature() -> Error {
+    Error::SignatureError(vec!["InvalidSignature".to_string()])
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before unix epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use comments_rs_core_backend::{
+        data::User,
+        error::Error,
+        traits::{SignupProvider, UserStore},
+    };
+    use comments_rs_memdb_backend::MemDB;
+
+    use crate::{transport::tests::SmtpSink, EmailSignupProvider, SmtpTransport};
+
+    const LINK: &str = "https://example.com/confirm?token=";
+
+    async fn provider(sink: &SmtpSink, user_store: Arc<MemDB>) -> EmailSignupProvider {
+        let transport = SmtpTransport::from_url(&sink.url()).unwrap();
+
+        EmailSignupProvider::new(
+            user_store,
+            Arc::new(transport),
+            b"secret",
+            "comments@example.com",
+            LINK,
+        )
+    }
+
+    /// Takes the token from the link in the last mail the sink received.
+    fn token(sink: &SmtpSink) -> String {
+        // undo the quoted-printable encoding, the only escaped character is the `=` in the link
+        let mail = sink
+            .mails()
+            .pop()
+            .expect("No mail received")
+            .replace("=\r\n", "")
+            .replace("=3D", "=");
+        let start = mail.find(LINK).expect("No link in mail") + LINK.len();
+
+        mail[start..].split_whitespace().next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_signup_and_confirm() {
+        let sink = SmtpSink::start().await;
+        let memdb = Arc::new(MemDB::default());
+        let provider = provider(&sink, memdb.clone()).await;
+
+        let pending = provider.signup("test@mail.com").await.unwrap();
+
+        assert_eq!(pending.email, "test@mail.com");
+        assert_eq!(sink.recipients(), vec!["test@mail.com".to_string()]);
+
+        let user = provider.confirm(&token(&sink), "test").await.unwrap();
+
+        assert_eq!(user, User::new("test@mail.com", "test"));
+        assert_eq!(memdb.find_user("test").await.unwrap(), Some(user));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_existing_user() {
+        let sink = SmtpSink::start().await;
+        let memdb = Arc::new(MemDB::default());
+        let user = memdb
+            .save_user(User::new("test@mail.com", "test"))
+            .await
+            .unwrap();
+        let provider = provider(&sink, memdb.clone()).await;
+
+        provider.signup("test@mail.com").await.unwrap();
+
+        assert_eq!(provider.confirm(&token(&sink), "other").await, Ok(user));
+    }
+
+    #[tokio::test]
+    async fn test_token_used_once() {
+        let sink = SmtpSink::start().await;
+        let provider = provider(&sink, Arc::new(MemDB::default())).await;
+
+        provider.signup("test@mail.com").await.unwrap();
+        let token = token(&sink);
+        provider.confirm(&token, "test").await.unwrap();
+
+        assert_eq!(
+            provider.confirm(&token, "test").await,
+            Err(Error::SignupError)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_expired() {
+        let sink = SmtpSink::start().await;
+        let provider = provider(&sink, Arc::new(MemDB::default()))
+            .await
+            .with_validity(Duration::from_secs(0));
+
+        provider.signup("test@mail.com").await.unwrap();
+
+        assert_eq!(
+            provider.confirm(&token(&sink), "test").await,
+            Err(Error::SignatureError(vec!["ExpiredSignature".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_tampered() {
+        let sink = SmtpSink::start().await;
+        let provider = provider(&sink, Arc::new(MemDB::default())).await;
+        let forged = provider.token("admin@mail.com", u64::MAX);
+        let other_provider = EmailSignupProvider::new(
+            Arc::new(MemDB::default()),
+            Arc::new(SmtpTransport::from_url(&sink.url()).unwrap()),
+            b"other secret",
+            "comments@example.com",
+            LINK,
+        );
+
+        assert_eq!(
+            other_provider.confirm(&forged, "admin").await,
+            Err(Error::SignatureError(vec!["InvalidSignature".to_string()]))
+        );
+        assert_eq!(
+            provider.confirm("garbage", "admin").await,
+            Err(Error::SignatureError(vec!["InvalidSignature".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signup_invalid_email() {
+        let sink = SmtpSink::start().await;
+        let provider = provider(&sink, Arc::new(MemDB::default())).await;
+
+        let error = provider.signup("mail.com").await.unwrap_err();
+
+        assert!(matches!(error, Error::ValidationError { .. }));
+        assert!(sink.mails().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_signup_transport_error() {
+        let provider = EmailSignupProvider::new(
+            Arc::new(MemDB::default()),
+            Arc::new(SmtpTransport::from_url("smtp://127.0.0.1:1").unwrap()),
+            b"secret",
+            "comments@example.com",
+            LINK,
+        );
+
+        assert_eq!(
+            provider.signup("test@mail.com").await,
+            Err(Error::NewtorkError)
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use comments_rs_core_backend::error::Error;
+use jsonwebtoken::DecodingKey;
+use reqwest::header::{HeaderMap, CACHE_CONTROL};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Caches the RSA keys of a JSON Web Key Set by their `kid`.
+///
+/// The keys are kept as long as the `Cache-Control: max-age` of the last response allows. A
+/// missing `kid` triggers a refetch as well, since the issuer may have rotated its keys. After a
+/// fetch that fails or does not turn up the key, no fetch happens for a backoff period which
+/// doubles every time, the keys fetched last are used in the meantime.
+pub struct JwksCache {
+    url: String,
+    client: reqwest::Client,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    state: Mutex<CacheState>,
+}
+
+struct CacheState {
+    keys: HashMap<String, (String, String)>,
+    expires_at: Option<Instant>,
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    /// Whether the last fetch failed
+    failed: bool,
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+}
+
+impl JwksCache {
+    pub fn new(url: &str) -> Self {
+        Self::with_backoff(url, MIN_BACKOFF, MAX_BACKOFF)
+    }
+
+    /// Waits at least `min_backoff` and at most `max_backoff` before refetching for an unknown
+    /// `kid`.
+    pub fn with_backoff(url: &str, min_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+            min_backoff,
+            max_backoff,
+            state: Mutex::new(CacheState {
+                keys: HashMap::new(),
+                expires_at: None,
+                retry_at: None,
+                backoff: min_backoff,
+                failed: false,
+            }),
+        }
+    }
+
+    /// Returns the key with id `kid`, fetching the key set if the cached one is stale or lacks
+    /// the key and no backoff is in effect.
+    pub async fn key(&self, kid: &str) -> Result<DecodingKey<'static>, Error> {
+        let mut state = self.state.lock().await;
+        let now = Instant::now();
+        let fresh = state.expires_at.is_some_and(|expires_at| now < expires_at);
+        let backing_off = state.retry_at.is_some_and(|retry_at| now < retry_at);
+
+        if fresh || backing_off {
+            if let Some(key) = state.keys.get(kid) {
+                return Ok(to_decoding_key(key));
+            }
+        }
+        if backing_off {
+            return Err(if state.failed {
+                Error::NewtorkError
+            } else {
+                unknown_kid()
+            });
+        }
+
+        let fetched = retrieve_keys(&self.client, &self.url).await;
+        let now = Instant::now();
+        let (keys, max_age) = match fetched {
+            Ok(fetched) => fetched,
+            Err(error) => {
+                state.failed = true;
+                state.back_off(now, self.max_backoff);
+
+                return Err(error);
+            }
+        };
+
+        state.keys = keys;
+        state.expires_at = Some(now + max_age);
+        state.failed = false;
+
+        match state.keys.get(kid).map(to_decoding_key) {
+            Some(key) => {
+                state.backoff = self.min_backoff;
+                state.retry_at = None;
+
+                Ok(key)
+            }
+            None => {
+                state.back_off(now, self.max_backoff);
+
+                Err(unknown_kid())
+            }
+        }
+    }
+}
+
+impl CacheState {
+    /// Holds off fetching for the current backoff period and doubles it, up to `max_backoff`.
+    fn back_off(&mut self, now: Instant, max_backoff: Duration) {
+        self.retry_at = Some(now + self.backoff);
+        self.backoff = (self.backoff * 2).min(max_backoff);
+    }
+}
+
+fn to_decoding_key((n, e): &(String, String)) -> DecodingKey<'static> {
+    DecodingKey::from_rsa_components(n, e).into_static()
+}
+
+fn unknown_kid() -> Error {
+    Error::SignatureError(vec!["UnknownKeyId".to_string()])
+}
+
+/// Fetches the RSA keys by `kid` along with how long they may be cached.
+async fn retrieve_keys(
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<(HashMap<String, (String, String)>, Duration), Error> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(map_err)?;
+    let max_age = max_age(response.headers());
+    let jwks: Jwks = response.json().await.map_err(map_err)?;
+
+    let keys = jwks
+        .keys
+        .into_iter()
+        .filter(|key| key.kty == "RSA")
+        .filter_map(|key| Some((key.kid?, (key.n?, key.e?))))
+        .collect();
+
+    Ok((keys, max_age))
+}
+
+/// Reads `max-age` from the `Cache-Control` header, responses without it are not cached.
+fn max_age(headers: &HeaderMap) -> Duration {
+    headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|directive| directive.trim().strip_prefix("max-age="))
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_default()
+}
+
+fn map_err(_error: reqwest::Error) -> Error {
+    Error::NewtorkError
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use comments_rs_core_backend::error::Error;
+    use tide::listener::Listener;
+
+    use crate::jwks::{unknown_kid, JwksCache};
+
+    /// Serves `test/keys/jwks.json` on a free local port, optionally cacheable for `max_age`
+    /// seconds, and returns its url together with the number of requests served so far.
+    pub(crate) async fn key_server(max_age: Option<u64>) -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let mut app = tide::with_state(requests.clone());
+        app.at("/certs")
+            .get(move |request: tide::Request<Arc<AtomicUsize>>| async move {
+                request.state().fetch_add(1, Ordering::SeqCst);
+
+                let mut response = tide::Response::builder(200)
+                    .body(include_str!("../test/keys/jwks.json"))
+                    .content_type(tide::http::mime::JSON)
+                    .build();
+                if let Some(max_age) = max_age {
+                    response.insert_header(
+                        "Cache-Control",
+                        format!("public, max-age={}, must-revalidate", max_age),
+                    );
+                }
+
+                Ok(response)
+            });
+
+        let mut listener = app.bind("127.0.0.1:0").await.unwrap();
+        let url = format!("{}/certs", listener.info()[0].connection());
+        tokio::spawn(async move { listener.accept().await });
+
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_key_cached_for_max_age() {
+        let (url, requests) = key_server(Some(3600)).await;
+        let cache = JwksCache::new(&url);
+
+        cache.key("test-key").await.unwrap();
+        cache.key("test-key").await.unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_key_refetched_without_max_age() {
+        let (url, requests) = key_server(None).await;
+        let cache = JwksCache::new(&url);
+
+        cache.key("test-key").await.unwrap();
+        cache.key("test-key").await.unwrap();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid_refetched_with_backoff() {
+        let (url, requests) = key_server(Some(3600)).await;
+        let cache =
+            JwksCache::with_backoff(&url, Duration::from_millis(100), Duration::from_secs(1));
+
+        cache.key("test-key").await.unwrap();
+        assert_eq!(cache.key("rotated-key").await.err(), Some(unknown_kid()));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // still backing off
+        assert_eq!(cache.key("rotated-key").await.err(), Some(unknown_kid()));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(cache.key("rotated-key").await.err(), Some(unknown_kid()));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // the backoff doubled
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(cache.key("rotated-key").await.err(), Some(unknown_kid()));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // known keys are still served from the cache
+        cache.key("test-key").await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid_backoff_without_max_age() {
+        let (url, requests) = key_server(None).await;
+        let cache =
+            JwksCache::with_backoff(&url, Duration::from_millis(100), Duration::from_secs(1));
+
+        assert_eq!(cache.key("made-up-key").await.err(), Some(unknown_kid()));
+        assert_eq!(cache.key("other-key").await.err(), Some(unknown_kid()));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // the keys fetched last serve while backing off
+        cache.key("test-key").await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        cache.key("test-key").await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_network_error() {
+        let cache = JwksCache::new("http://127.0.0.1:1/certs");
+
+        assert_eq!(cache.key("test-key").await.err(), Some(Error::NewtorkError));
+        // failed fetches back off as well
+        assert_eq!(cache.key("test-key").await.err(), Some(Error::NewtorkError));
+        assert!(cache.state.lock().await.retry_at.is_some());
+    }
+}
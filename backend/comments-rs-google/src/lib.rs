@@ -1,18 +1,43 @@
-use std::future::Future;
+use std::sync::Arc;
 
-use comments_rs_core_backend::{error::Error, traits::{SignupProvider, SignupResult}};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, TokenData, Validation};
+use comments_rs_core_backend::{
+    data::User,
+    error::Error,
+    traits::{SignupProvider, SignupResult, UserStore},
+};
+use jsonwebtoken::{decode, decode_header, Algorithm, Validation};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
-struct GoogleSignupProvider {
-    pub_keys: Vec<(String, String)>,
+use crate::jwks::JwksCache;
+
+pub mod jwks;
+
+pub const GOOGLE_JWKS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+
+const GOOGLE_ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+
+/// Signs users in with the ID token Google hands out to the client.
+///
+/// Both steps take that ID token: `signup` looks up the user owning the token's email and
+/// `confirm` additionally creates the user under `user_name` if there is none yet.
+#[derive(Clone)]
+pub struct GoogleSignupProvider {
+    user_store: Arc<dyn UserStore>,
+    client_id: String,
+    jwks: Arc<JwksCache>,
 }
 
+/// The claims of a Google ID token this provider relies on.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
-struct Token {
-    email: String,
-    picture: String,
+pub struct Token {
+    pub iss: String,
+    pub aud: String,
+    pub exp: u64,
+    pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
+    pub picture: Option<String>,
 }
 
 impl SignupProvider for GoogleSignupProvider {
@@ -20,106 +45,270 @@ impl SignupProvider for GoogleSignupProvider {
         "google"
     }
 
-    fn signup(&self, email: &str) -> SignupResult {
-        todo!()
+    fn signup(&self, id_token: &str) -> SignupResult {
+        let provider = self.clone();
+        let id_token = id_token.to_string();
+
+        Box::pin(async move {
+            let token = provider.verify(&id_token).await?;
+
+            provider
+                .user_store
+                .find_user_by_email(&token.email)
+                .await?
+                .ok_or(Error::SignupError)
+        })
     }
 
-    fn confirm(&self, hash: &str, user_name: &str) -> SignupResult {
-        todo!()
+    fn confirm(&self, id_token: &str, user_name: &str) -> SignupResult {
+        let provider = self.clone();
+        let id_token = id_token.to_string();
+        let user_name = user_name.to_string();
+
+        Box::pin(async move {
+            let token = provider.verify(&id_token).await?;
+
+            match provider.user_store.find_user_by_email(&token.email).await? {
+                Some(user) => Ok(user),
+                None => {
+                    let user = User::try_new(&token.email, &user_name)?;
+
+                    Ok(provider.user_store.save_user(user).await?)
+                }
+            }
+        })
     }
 }
 
 impl GoogleSignupProvider {
-    async fn new(pub_keys: Vec<(String, String)>) -> Self {
-        Self { pub_keys }
-    }
-
-    async fn verify(&self, token: &str) -> Result<Token, Error> {
-        let mut results = self
-            .pub_keys
-            .iter()
-            .map(|key| DecodingKey::from_rsa_components(&key.0, &key.1))
-            .map(|key| decode::<Token>(token, &key, &Validation::new(Algorithm::RS256)));
-
-        let result = results.clone().find(|token| token.is_ok());
-
-        if result.is_none() {
-            Err(Error::SignatureError(
-                results
-                    .map(|r| {
-                        if let Err(e) = r {
-                            format!("{}", e)
-                        } else {
-                            panic!("No valid token found yet no error either")
-                        }
-                    })
-                    .collect(),
-            ))
-        } else {
-            Ok(result.unwrap().unwrap().claims)
+    /// Accepts ID tokens issued for the OAuth client `client_id`.
+    pub fn new(user_store: Arc<dyn UserStore>, client_id: &str) -> Self {
+        Self {
+            user_store,
+            client_id: client_id.to_string(),
+            jwks: Arc::new(JwksCache::new(GOOGLE_JWKS_URL)),
         }
     }
+
+    /// Fetches the signing keys from `jwks_url` instead of Google.
+    pub fn with_jwks_url(mut self, jwks_url: &str) -> Self {
+        self.jwks = Arc::new(JwksCache::new(jwks_url));
+        self
+    }
+
+    /// Checks the signature, audience, issuer and expiry of `id_token` and returns its claims.
+    pub async fn verify(&self, id_token: &str) -> Result<Token, Error> {
+        let header = decode_header(id_token).map_err(signature_error)?;
+        let kid = header
+            .kid
+            .ok_or_else(|| Error::SignatureError(vec!["MissingKeyId".to_string()]))?;
+        let key = self.jwks.key(&kid).await?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&self.client_id]);
+
+        let token = decode::<Token>(id_token, &key, &validation).map_err(signature_error)?;
+
+        check_claims(token.claims)
+    }
 }
 
-async fn retrieve_keys() -> Result<Vec<(String, String)>, Error> {
-    let json = reqwest::get("https://www.googleapis.com/oauth2/v3/certs")
-        .await
-        .expect("Could not retrieve google public keys")
-        .text()
-        .await
-        .expect("Could not retrieve google public keys");
-
-    let json_value: Value = serde_json::from_str(json.as_str())
-        .expect("Json Web Keys could not be retrieved from json");
-
-    let pub_keys = vec![
-        (
-            json_value["keys"][0]["n"].as_str().unwrap().to_string(),
-            json_value["keys"][0]["e"].as_str().unwrap().to_string(),
-        ),
-        (
-            json_value["keys"][1]["n"].as_str().unwrap().to_string(),
-            json_value["keys"][1]["e"].as_str().unwrap().to_string(),
-        ),
-    ];
-
-    Ok(pub_keys)
+fn check_claims(token: Token) -> Result<Token, Error> {
+    if !GOOGLE_ISSUERS.contains(&token.iss.as_str()) {
+        return Err(Error::SignatureError(vec!["InvalidIssuer".to_string()]));
+    }
+    if !token.email_verified {
+        return Err(Error::SignupError);
+    }
+
+    Ok(token)
 }
 
-fn map_err(error: reqwest::Error) -> Error {
-    todo!()
+fn signature_error(error: jsonwebtoken::errors::Error) -> Error {
+    Error::SignatureError(vec![format!("{:?}", error.kind())])
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::Arc,
+        time::{SystemTime, UNIX_EPOCH},
+    };
 
-    use comments_rs_core_backend::error::Error;
+    use comments_rs_core_backend::{
+        data::User,
+        error::Error,
+        traits::{SignupProvider, UserStore},
+    };
+    use comments_rs_memdb_backend::MemDB;
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde_json::{json, Value};
 
-    use crate::{retrieve_keys, GoogleSignupProvider};
+    use crate::{jwks::tests::key_server, GoogleSignupProvider};
 
-    #[tokio::test]
-    async fn test_retrieve_keys() {
-        let pub_keys = retrieve_keys().await.unwrap();
+    const CLIENT_ID: &str = "client.apps.googleusercontent.com";
+
+    fn claims(email: &str) -> Value {
+        let exp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+
+        json!({
+            "iss": "https://accounts.google.com",
+            "aud": CLIENT_ID,
+            "exp": exp,
+            "email": email,
+            "email_verified": true,
+        })
+    }
+
+    fn sign(claims: &Value) -> String {
+        sign_with_kid(claims, Some("test-key"))
+    }
+
+    fn sign_with_kid(claims: &Value, kid: Option<&str>) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = kid.map(|kid| kid.to_string());
+        let key = EncodingKey::from_rsa_pem(include_bytes!("../test/keys/private.pem")).unwrap();
 
-        assert_eq!(pub_keys.len(), 2);
+        encode(&header, claims, &key).unwrap()
+    }
+
+    async fn provider(user_store: Arc<MemDB>) -> GoogleSignupProvider {
+        GoogleSignupProvider::new(user_store, CLIENT_ID)
+            .with_jwks_url(&key_server(Some(3600)).await.0)
     }
 
     #[tokio::test]
     async fn test_validate_signature() {
-        // TODO 
-        // let pub_keys = retrieve_keys().await.unwrap();
-        // let provider = GoogleSignupProvider {
-        //     pub_keys: pub_keys.clone(),
-        // };
-
-        // let result = provider.verify(token).await;
-
-        // assert_eq!(
-        //     result,
-        //     Err(Error::SignatureError(vec![
-        //         "InvalidSignature".to_string(),
-        //         "ExpiredSignature".to_string()
-        //     ]))
-        // );
+        let provider = provider(Arc::new(MemDB::default())).await;
+
+        let token = provider
+            .verify(&sign(&claims("test@gmail.com")))
+            .await
+            .unwrap();
+
+        assert_eq!(token.email, "test@gmail.com");
+        assert_eq!(token.aud, CLIENT_ID);
+    }
+
+    #[tokio::test]
+    async fn test_validate_tampered_token() {
+        let provider = provider(Arc::new(MemDB::default())).await;
+        let token = sign(&claims("test@gmail.com"));
+        let forged = sign(&claims("admin@gmail.com"));
+        let mut parts: Vec<&str> = token.split('.').collect();
+        parts[1] = forged.split('.').nth(1).unwrap();
+
+        assert_eq!(
+            provider.verify(&parts.join(".")).await,
+            Err(Error::SignatureError(vec!["InvalidSignature".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_key_id() {
+        let provider = provider(Arc::new(MemDB::default())).await;
+        let claims = claims("test@gmail.com");
+
+        assert_eq!(
+            provider
+                .verify(&sign_with_kid(&claims, Some("other-key")))
+                .await,
+            Err(Error::SignatureError(vec!["UnknownKeyId".to_string()]))
+        );
+        assert_eq!(
+            provider.verify(&sign_with_kid(&claims, None)).await,
+            Err(Error::SignatureError(vec!["MissingKeyId".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_expired() {
+        let provider = provider(Arc::new(MemDB::default())).await;
+        let mut claims = claims("test@gmail.com");
+        claims["exp"] = json!(1);
+
+        assert_eq!(
+            provider.verify(&sign(&claims)).await,
+            Err(Error::SignatureError(vec!["ExpiredSignature".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_audience() {
+        let provider = provider(Arc::new(MemDB::default())).await;
+        let mut claims = claims("test@gmail.com");
+        claims["aud"] = json!("other.apps.googleusercontent.com");
+
+        assert_eq!(
+            provider.verify(&sign(&claims)).await,
+            Err(Error::SignatureError(vec!["InvalidAudience".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_issuer() {
+        let provider = provider(Arc::new(MemDB::default())).await;
+        let mut claims = claims("test@gmail.com");
+        claims["iss"] = json!("https://accounts.example.com");
+
+        assert_eq!(
+            provider.verify(&sign(&claims)).await,
+            Err(Error::SignatureError(vec!["InvalidIssuer".to_string()]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_email_unverified() {
+        let provider = provider(Arc::new(MemDB::default())).await;
+        let mut claims = claims("test@gmail.com");
+        claims["email_verified"] = json!(false);
+
+        assert_eq!(
+            provider.verify(&sign(&claims)).await,
+            Err(Error::SignupError)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_confirm_creates_user() {
+        let memdb = Arc::new(MemDB::default());
+        let provider = provider(memdb.clone()).await;
+
+        let user = provider
+            .confirm(&sign(&claims("test@gmail.com")), "test")
+            .await
+            .unwrap();
+
+        assert_eq!(user, User::new("test@gmail.com", "test"));
+        assert_eq!(memdb.find_user("test").await.unwrap(), Some(user));
+    }
+
+    #[tokio::test]
+    async fn test_signup_and_confirm_find_existing_user() {
+        let memdb = Arc::new(MemDB::default());
+        let user = memdb
+            .save_user(User::new("test@gmail.com", "test"))
+            .await
+            .unwrap();
+        let provider = provider(memdb.clone()).await;
+        let token = sign(&claims("test@gmail.com"));
+
+        assert_eq!(provider.signup(&token).await, Ok(user.clone()));
+        assert_eq!(provider.confirm(&token, "other").await, Ok(user.clone()));
+        assert_eq!(memdb.find_all_users().await.unwrap(), vec![user]);
+    }
+
+    #[tokio::test]
+    async fn test_signup_unknown_user() {
+        let provider = provider(Arc::new(MemDB::default())).await;
+
+        assert_eq!(
+            provider.signup(&sign(&claims("test@gmail.com"))).await,
+            Err(Error::SignupError)
+        );
     }
 }
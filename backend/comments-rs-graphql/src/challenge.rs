@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use comments_rs_core_backend::{error::Error, pow};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// How long a challenge may take to solve.
+const LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// Issues signed proof of work challenges and checks their solutions, see [`pow`].
+///
+/// Challenges are signed with a secret generated on startup and can be redeemed once, both
+/// only hold within a single process.
+pub struct Challenges {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey<'static>,
+    difficulty: u32,
+    lifetime: Duration,
+    /// Ids of redeemed challenges with the time they expire at
+    redeemed: Mutex<HashMap<String, u64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    jti: String,
+    difficulty: u32,
+    exp: u64,
+}
+
+impl Challenges {
+    /// Issues challenges taking about `2^difficulty` hashes to solve.
+    pub fn new(difficulty: u32) -> Self {
+        let mut secret = [0u8; 32];
+        thread_rng().fill_bytes(&mut secret);
+
+        Self {
+            encoding_key: EncodingKey::from_secret(&secret),
+            decoding_key: DecodingKey::from_secret(&secret).into_static(),
+            difficulty,
+            lifetime: LIFETIME,
+            redeemed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn difficulty(&self) -> u32 {
+        self.difficulty
+    }
+
+    pub fn issue(&self) -> String {
+        let mut id = [0u8; 16];
+        thread_rng().fill_bytes(&mut id);
+
+        let claims = Claims {
+            jti: id.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            difficulty: self.difficulty,
+            exp: now() + self.lifetime.as_secs(),
+        };
+
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)
+            .expect("Challenge claims can always be encoded")
+    }
+
+    /// Checks that `nonce` solves `challenge`, which can't be redeemed again afterwards.
+    pub fn redeem(&self, challenge: &str, nonce: u64) -> Result<(), Error> {
+        let claims = decode::<Claims>(
+            challenge,
+            &self.decoding_key,
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|_| Error::ProofOfWorkError)?
+        .claims;
+
+        if !pow::verify(challenge, claims.difficulty, nonce) {
+            return Err(Error::ProofOfWorkError);
+        }
+
+        let now = now();
+        let mut redeemed = self.redeemed.lock().unwrap();
+        redeemed.retain(|_, expires| *expires > now);
+
+        if redeemed.insert(claims.jti, claims.exp).is_some() {
+            return Err(Error::ProofOfWorkError);
+        }
+
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before unix epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use comments_rs_core_backend::{error::Error, pow};
+
+    use crate::challenge::Challenges;
+
+    #[test]
+    fn test_issue_and_redeem() {
+        let challenges = Challenges::new(8);
+        let challenge = challenges.issue();
+        let nonce = pow::solve(&challenge, 8);
+
+        assert_eq!(challenges.redeem(&challenge, nonce), Ok(()));
+        assert_eq!(
+            challenges.redeem(&challenge, nonce),
+            Err(Error::ProofOfWorkError)
+        );
+    }
+
+    #[test]
+    fn test_redeem_invalid() {
+        let challenges = Challenges::new(8);
+        let challenge = challenges.issue();
+        let wrong = (0..)
+            .find(|nonce| !pow::verify(&challenge, 8, *nonce))
+            .unwrap();
+
+        assert_eq!(
+            challenges.redeem(&challenge, wrong),
+            Err(Error::ProofOfWorkError)
+        );
+
+        // challenges of other servers don't count, however easy they are
+        let foreign = Challenges::new(0).issue();
+
+        assert_eq!(challenges.redeem(&foreign, 0), Err(Error::ProofOfWorkError));
+    }
+
+    #[test]
+    fn test_redeem_expired() {
+        let challenges = Challenges::new(0).with_lifetime(Duration::from_secs(0));
+        let challenge = challenges.issue();
+        // jsonwebtoken allows no leeway by default, but checks `exp` in whole seconds
+        std::thread::sleep(Duration::from_millis(1100));
+
+        assert_eq!(
+            challenges.redeem(&challenge, 0),
+            Err(Error::ProofOfWorkError)
+        );
+    }
+}
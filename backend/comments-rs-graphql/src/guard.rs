@@ -0,0 +1,120 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::{Context, Guard, Result};
+use comments_rs_core_backend::{
+    data::{Role, Scope, User},
+    error::Error,
+    traits::{CommentStore, RoleStore},
+};
+
+use crate::{graphql_error, store_error};
+
+/// Site wide roles given by the configuration rather than the store, so someone can grant the
+/// first roles. Keyed by lowercase email.
+pub(crate) struct ConfiguredRoles(pub HashMap<String, Role>);
+
+/// The thread with hash `thread_hash`, the whole site for `None`.
+pub fn thread_scope(thread_hash: Option<&str>) -> Scope {
+    thread_hash.map_or(Scope::Site, |thread_hash| {
+        Scope::Thread(thread_hash.to_string())
+    })
+}
+
+/// The highest role of the signed in user in `scope`, `None` for guests.
+pub async fn role_in(ctx: &Context<'_>, scope: &Scope) -> Result<Option<Role>> {
+    let user = match ctx.data_opt::<User>() {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let configured = ctx
+        .data_opt::<ConfiguredRoles>()
+        .and_then(|ConfiguredRoles(roles)| roles.get(&user.email.to_lowercase()))
+        .copied();
+    let granted = ctx
+        .data::<Arc<dyn RoleStore>>()?
+        .find_roles(&user.name)
+        .await
+        .map_err(store_error)?;
+
+    Ok(Some(
+        granted
+            .into_iter()
+            .filter(|grant| grant.scope.covers(scope))
+            .map(|grant| grant.role)
+            .chain(configured)
+            .fold(Role::Commenter, Role::max),
+    ))
+}
+
+/// Whether the signed in user holds at least `role` in `scope`.
+pub async fn has_role(ctx: &Context<'_>, role: Role, scope: &Scope) -> Result<bool> {
+    Ok(role_in(ctx, scope).await?.is_some_and(|held| held >= role))
+}
+
+/// Fails for guests and users holding less than `role` in `scope`.
+pub async fn require_role(ctx: &Context<'_>, role: Role, scope: &Scope) -> Result<()> {
+    match role_in(ctx, scope).await? {
+        None => Err(graphql_error(Error::AuthenticationError)),
+        Some(held) if held < role => Err(graphql_error(Error::PermissionError)),
+        Some(_) => Ok(()),
+    }
+}
+
+enum GuardScope {
+    Scope(Scope),
+    /// The thread of the comment with this hash
+    Comment(String),
+}
+
+/// Lets signed in users resolve a field if they hold at least a role in the scope the field
+/// acts on, site wide unless given otherwise.
+///
+/// ```ignore
+/// #[graphql(guard = "RoleGuard::new(Role::Moderator).on_comment(&hash)")]
+/// ```
+pub struct RoleGuard {
+    role: Role,
+    scope: GuardScope,
+}
+
+impl RoleGuard {
+    pub fn new(role: Role) -> Self {
+        Self {
+            role,
+            scope: GuardScope::Scope(Scope::Site),
+        }
+    }
+
+    pub fn in_scope(mut self, scope: Scope) -> Self {
+        self.scope = GuardScope::Scope(scope);
+        self
+    }
+
+    pub fn in_thread(self, thread_hash: &str) -> Self {
+        self.in_scope(Scope::Thread(thread_hash.to_string()))
+    }
+
+    /// Checks the role in the thread of the comment with hash `hash`, site wide for unknown
+    /// comments.
+    pub fn on_comment(mut self, hash: &str) -> Self {
+        self.scope = GuardScope::Comment(hash.to_string());
+        self
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let scope = match &self.scope {
+            GuardScope::Scope(scope) => scope.clone(),
+            GuardScope::Comment(hash) => ctx
+                .data::<Arc<dyn CommentStore>>()?
+                .find_comment(hash)
+                .await
+                .map_err(store_error)?
+                .map_or(Scope::Site, |comment| Scope::Thread(comment.thread_hash)),
+        };
+
+        require_role(ctx, self.role, &scope).await
+    }
+}
@@ -1,19 +1,51 @@
-use std::{env, pin::Pin, sync::Arc};
+use std::{
+    collections::HashMap,
+    env,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use async_graphql::{
-    futures_util::TryFutureExt, Context, EmptyMutation, EmptySubscription, Object, Schema,
-    SimpleObject,
+    connection::{query, Connection, Edge},
+    futures_util::{future, Stream, StreamExt, TryFutureExt},
+    indexmap::IndexMap,
+    ComplexObject, Context, Enum, ErrorExtensions, InputObject, Name, Object, OutputType, Result,
+    Schema, SimpleObject, Subscription, Value,
 };
+use async_graphql_tide::GraphQLSubscription;
+use challenge::Challenges;
 use comments_rs_core_backend::{
-    data::{Thread, User},
-    error::Error,
-    traits::{Frontend, ThreadStore, UserStore},
+    data::{
+        AuditAction, AuditEntry, Ban, BanTarget, Comment, CommentStatus, Deletion, Report,
+        RequestInfo, Revision, Role, RoleGrant, Scope, Thread, User, TOMBSTONE,
+    },
+    error::{CommentError, Error, StoreError},
+    events::CommentEvent,
+    spam::{SpamFilterChain, Verdict},
+    traits::{
+        AuditStore, BanStore, CommentStore, Frontend, ReportStore, RoleStore, SignupProvider,
+        SpamFilter, ThreadStore, UserStore,
+    },
 };
+use guard::{has_role, role_in, thread_scope, ConfiguredRoles, RoleGuard};
+use rate_limit::{Client, Operation, QueryRateLimit, RateLimit, RateLimiter};
+use session::{session_token, signin_binding, Sessions, SigninBinding};
+
+pub mod challenge;
+pub mod guard;
+pub mod rate_limit;
+pub mod session;
+
+pub type CommentsSchema = Schema<Query, Mutation, Subscription>;
 
 pub struct Query;
 
 pub struct Mutation;
 
+pub struct Subscription;
+
 #[derive(SimpleObject)]
 pub struct GraphQLUser {
     name: String,
@@ -21,22 +53,226 @@ pub struct GraphQLUser {
 }
 
 #[derive(SimpleObject)]
+pub struct GraphQLSession {
+    /// Send as `Authorization: Bearer <token>`, browsers get it as cookie as well
+    token: String,
+    user: GraphQLUser,
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
 pub struct GraphQLThread {
     hash: String,
     name: String,
+    /// Whether new comments wait for approval, `null` follows the server wide policy
+    require_approval: Option<bool>,
+    /// Locked threads only take comments of their moderators
+    locked: bool,
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct GraphQLComment {
+    thread_hash: String,
+    hash: String,
+    /// Hash of the comment this one replies to
+    parent_hash: Option<String>,
+    #[graphql(skip)]
+    email: String,
+    /// Milliseconds since the unix epoch, as a string since it exceeds the range of `Int`
+    date: String,
+    content: String,
+    /// When the content was last edited, milliseconds since the unix epoch as well
+    edited_at: Option<String>,
+    /// Deleted comments with replies stay in place, with content and email replaced by
+    /// `[deleted]`
+    deleted: bool,
+    status: GraphQLCommentStatus,
+    #[graphql(skip)]
+    deletion: Option<Deletion>,
+    #[graphql(skip)]
+    shadowed: bool,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(
+    name = "CommentStatus",
+    remote = "comments_rs_core_backend::data::CommentStatus"
+)]
+pub enum GraphQLCommentStatus {
+    /// Waiting for a moderator
+    Pending,
+    Approved,
+    Rejected,
+    Spam,
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct GraphQLReportedComment {
+    comment: GraphQLComment,
+    /// Number of distinct readers who reported the comment
+    report_count: usize,
+}
+
+#[derive(SimpleObject)]
+pub struct GraphQLReport {
+    /// Name of the reporting user
+    reporter: String,
+    reason: String,
+    /// Milliseconds since the unix epoch
+    date: String,
+}
+
+#[derive(SimpleObject)]
+pub struct GraphQLBan {
+    id: String,
+    kind: GraphQLBanKind,
+    /// The banned user name, email domain or IP range
+    value: String,
+    /// Shadowbanned commenters can still post, but only they see their comments
+    shadow: bool,
+    reason: Option<String>,
+    /// Name of the admin who issued the ban
+    banned_by: String,
+    /// Milliseconds since the unix epoch
+    date: String,
+    /// When the ban ends, `null` for permanent bans
+    expires_at: Option<String>,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "BanKind")]
+pub enum GraphQLBanKind {
+    User,
+    /// Email addresses of the domain and its subdomains
+    EmailDomain,
+    /// A network like `192.0.2.0/24` or a single address
+    IpRange,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "Role", remote = "comments_rs_core_backend::data::Role")]
+pub enum GraphQLRole {
+    Commenter,
+    /// Approves, rejects, deletes and restores comments
+    Moderator,
+    /// Manages threads and bans and grants roles below their own
+    Admin,
+    /// Grants any role
+    Owner,
+}
+
+#[derive(SimpleObject)]
+pub struct GraphQLAuditEntry {
+    action: GraphQLAuditAction,
+    /// Name of the user who took the action
+    actor: String,
+    /// Hash of the comment or thread, id of the ban or name of the user acted on
+    target: String,
+    /// Why the action was taken, the granted role and its scope for role changes
+    reason: Option<String>,
+    /// Milliseconds since the unix epoch
+    date: String,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(
+    name = "AuditAction",
+    remote = "comments_rs_core_backend::data::AuditAction"
+)]
+pub enum GraphQLAuditAction {
+    DeleteComment,
+    RestoreComment,
+    ApproveComment,
+    RejectComment,
+    MarkSpam,
+    /// Changing whether comments of a thread wait for approval
+    UpdateThread,
+    LockThread,
+    UnlockThread,
+    DeleteThread,
+    Ban,
+    Unban,
+    GrantRole,
+    RevokeRole,
+    /// An attempted action that failed, the reason names it and the error
+    Failed,
 }
 
+#[derive(SimpleObject)]
+pub struct GraphQLRoleGrant {
+    user_name: String,
+    role: GraphQLRole,
+    /// The thread the role applies to, `null` for site wide roles
+    thread_hash: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct GraphQLDeletion {
+    /// Name of the user who deleted the comment
+    deleted_by: String,
+    reason: Option<String>,
+    /// Milliseconds since the unix epoch
+    date: String,
+}
+
+/// A proof of work to do before commenting as guest: find a nonce such that the SHA-256 hash of
+/// `<challenge>:<nonce>` starts with `difficulty` zero bits.
+#[derive(SimpleObject)]
+pub struct GraphQLChallenge {
+    challenge: String,
+    difficulty: u32,
+}
+
+#[derive(InputObject)]
+pub struct GraphQLProof {
+    challenge: String,
+    /// The solving nonce in decimal, as a string since it may exceed the range of `Int`
+    nonce: String,
+}
+
+#[derive(SimpleObject)]
+pub struct GraphQLRevision {
+    /// When this content was posted or edited in, milliseconds since the unix epoch
+    date: String,
+    content: String,
+}
+
+/// How long after posting comments may be edited, `None` allows edits at any time.
+#[derive(Clone, Copy)]
+struct EditWindow(Option<Duration>);
+
+/// Whether comments wait for approval in threads that don't decide themselves.
+struct ApprovalPolicy(bool);
+
+/// Number of distinct reporters which sends a comment back to moderation, `None` never does.
+struct ReportThreshold(Option<usize>);
+
 #[Object]
 impl Query {
-    async fn users(&self, ctx: &Context<'_>) -> Vec<GraphQLUser> {
-        ctx.data::<Arc<dyn UserStore>>()
-            .unwrap()
+    /// All registered users, site wide admins only.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn users(&self, ctx: &Context<'_>) -> Result<Vec<GraphQLUser>> {
+        ctx.data::<Arc<dyn UserStore>>()?
             .find_all_users()
             .await
-            .unwrap()
-            .into_iter()
-            .map(|user| user.into())
-            .collect()
+            .map(|users| users.into_iter().map(|user| user.into()).collect())
+            .map_err(store_error)
+    }
+
+    /// The signed in user
+    async fn me(&self, ctx: &Context<'_>) -> Option<GraphQLUser> {
+        ctx.data_opt::<User>().map(|user| user.clone().into())
+    }
+
+    /// A fresh challenge for posting a comment as guest, `null` unless guests may comment.
+    async fn challenge(&self, ctx: &Context<'_>) -> Option<GraphQLChallenge> {
+        ctx.data_opt::<Arc<Challenges>>()
+            .map(|challenges| GraphQLChallenge {
+                challenge: challenges.issue(),
+                difficulty: challenges.difficulty(),
+            })
     }
 
     async fn thread(&self, ctx: &Context<'_>, hash: String) -> Option<GraphQLThread> {
@@ -47,114 +283,3813 @@ impl Query {
             .unwrap()
             .map(|user| user.into())
     }
+
+    /// All bans in the order they were issued, admins only.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn bans(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] include_expired: bool,
+    ) -> Result<Vec<GraphQLBan>> {
+        let now = now();
+        let bans = ctx
+            .data::<Arc<dyn BanStore>>()?
+            .find_all_bans()
+            .await
+            .map_err(store_error)?;
+
+        Ok(bans
+            .into_iter()
+            .filter(|ban| include_expired || ban.is_active(now))
+            .map(|ban| ban.into())
+            .collect())
+    }
+
+    /// Roles granted to the user named `user_name` or to anyone, site wide admins only. Roles
+    /// given by the server configuration aren't listed.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn roles(
+        &self,
+        ctx: &Context<'_>,
+        user_name: Option<String>,
+    ) -> Result<Vec<GraphQLRoleGrant>> {
+        let store = ctx.data::<Arc<dyn RoleStore>>()?;
+        let grants = match user_name {
+            Some(user_name) => store.find_roles(&user_name).await,
+            None => store.find_all_roles().await,
+        };
+
+        grants
+            .map(|grants| grants.into_iter().map(|grant| grant.into()).collect())
+            .map_err(store_error)
+    }
+
+    /// Moderation and administrative actions on `target`, a comment or thread hash, ban id or
+    /// user name, or on anything, newest first. Site wide admins only.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn audit_log(
+        &self,
+        ctx: &Context<'_>,
+        target: Option<String>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, GraphQLAuditEntry, CommentConnectionFields>> {
+        let store = ctx.data::<Arc<dyn AuditStore>>()?;
+        let target = target.as_deref();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let total = store
+                    .count_audit_entries(target)
+                    .await
+                    .map_err(store_error)?;
+                let (start, end) = page_bounds(after, before, first, last, total);
+                let entries = store
+                    .find_audit_entries(target, start, end - start)
+                    .await
+                    .map_err(store_error)?;
+
+                Ok::<_, async_graphql::Error>(connection(entries, start, end, total))
+            },
+        )
+        .await
+    }
+
+    /// Reported comments across all threads, most reported first, site wide moderators only.
+    #[graphql(guard = "RoleGuard::new(Role::Moderator)")]
+    async fn reported_comments(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, GraphQLReportedComment, CommentConnectionFields>> {
+        let store = ctx.data::<Arc<dyn ReportStore>>()?;
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let total = store.count_reported_comments().await.map_err(store_error)?;
+                let (start, end) = page_bounds(after, before, first, last, total);
+                let comments = store
+                    .find_reported_comments(start, end - start)
+                    .await
+                    .map_err(store_error)?;
+
+                Ok::<_, async_graphql::Error>(connection(comments, start, end, total))
+            },
+        )
+        .await
+    }
+
+    /// Comments with `status` across all threads in the order they were posted, site wide
+    /// moderators only.
+    #[graphql(guard = "RoleGuard::new(Role::Moderator)")]
+    async fn moderation_queue(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "GraphQLCommentStatus::Pending")] status: GraphQLCommentStatus,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, GraphQLComment, CommentConnectionFields>> {
+        let store = ctx.data::<Arc<dyn CommentStore>>()?;
+        let status = status.into();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let total = store
+                    .count_comments_by_status(status)
+                    .await
+                    .map_err(store_error)?;
+                let (start, end) = page_bounds(after, before, first, last, total);
+                let comments = store
+                    .find_comments_by_status(status, start, end - start)
+                    .await
+                    .map_err(store_error)?;
+
+                Ok::<_, async_graphql::Error>(connection(comments, start, end, total))
+            },
+        )
+        .await
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct CommentConnectionFields {
+    total_count: usize,
 }
 
-// #[Object]
-// impl Mutation {
-//     async fn signup(&self, username: String, password: String) {
+#[ComplexObject]
+impl GraphQLThread {
+    /// Approved comments of this thread in the order they were posted, cursors are comment
+    /// positions. Comments posted under a shadowban only show up for their author.
+    async fn comments(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<usize, GraphQLComment, CommentConnectionFields>> {
+        let store = ctx.data::<Arc<dyn CommentStore>>()?;
+        let viewer = ctx.data_opt::<User>().map(|user| user.email.as_str());
 
-//     }
-// }
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let total = store
+                    .count_thread_comments(&self.hash, viewer)
+                    .await
+                    .map_err(store_error)?;
+                let (start, end) = page_bounds(after, before, first, last, total);
+                let comments = store
+                    .find_thread_comments_page(&self.hash, viewer, start, end - start)
+                    .await
+                    .map_err(store_error)?;
 
-pub struct GraphQLFrontend {
-    pub user_store: Arc<dyn UserStore>,
+                Ok::<_, async_graphql::Error>(connection(comments, start, end, total))
+            },
+        )
+        .await
+    }
 }
 
-impl From<User> for GraphQLUser {
-    fn from(u: User) -> Self {
-        GraphQLUser {
-            name: u.name,
-            email: u.email,
-        }
+#[ComplexObject]
+impl GraphQLReportedComment {
+    /// The reports on the comment, oldest first.
+    async fn reports(&self, ctx: &Context<'_>) -> Result<Vec<GraphQLReport>> {
+        ctx.data::<Arc<dyn ReportStore>>()?
+            .find_reports(&self.comment.hash)
+            .await
+            .map(|reports| reports.into_iter().map(|report| report.into()).collect())
+            .map_err(store_error)
     }
 }
 
-impl From<Thread> for GraphQLThread {
-    fn from(t: Thread) -> Self {
-        Self {
-            hash: t.hash,
-            name: t.name,
+#[ComplexObject]
+impl GraphQLComment {
+    /// Approved direct replies to this comment in the order they were posted, replies posted
+    /// under a shadowban only show up for their author.
+    async fn replies(&self, ctx: &Context<'_>) -> Result<Vec<GraphQLComment>> {
+        let viewer = ctx.data_opt::<User>().map(|user| &user.email);
+
+        ctx.data::<Arc<dyn CommentStore>>()?
+            .find_replies(&self.hash)
+            .await
+            .map(|replies| {
+                replies
+                    .into_iter()
+                    .filter(|reply| reply.status == CommentStatus::Approved)
+                    .filter(|reply| !reply.shadowed || viewer == Some(&reply.email))
+                    .map(|reply| reply.into())
+                    .collect()
+            })
+            .map_err(store_error)
+    }
+
+    /// Email address of the commenter, only visible to the commenter and to moderators of the
+    /// thread.
+    async fn email(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let viewer = ctx.data_opt::<User>().map(|user| &user.email);
+
+        if viewer == Some(&self.email)
+            || has_role(
+                ctx,
+                Role::Moderator,
+                &Scope::Thread(self.thread_hash.clone()),
+            )
+            .await?
+        {
+            return Ok(Some(self.email.clone()));
         }
+
+        Ok(None)
     }
-}
 
-impl Frontend for GraphQLFrontend {
-    fn run(&self) -> Pin<Box<dyn std::future::Future<Output = Result<(), Error>>>> {
-        let listen_addr = env::var("LISTEN_ADDR").unwrap_or_else(|_| "localhost:8000".to_owned());
+    /// Name of the user who posted this comment, `null` for guests and deleted comments.
+    async fn author(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        if self.deleted {
+            return Ok(None);
+        }
 
-        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
-            .data(self.user_store.clone())
-            .finish();
+        ctx.data::<Arc<dyn UserStore>>()?
+            .find_user_by_email(&self.email)
+            .await
+            .map(|user| user.map(|user| user.name))
+            .map_err(store_error)
+    }
 
-        println!("Hostet at: http://{}", listen_addr);
+    /// Who deleted this comment and why, only visible to moderators of its thread.
+    async fn deletion(&self, ctx: &Context<'_>) -> Result<Option<GraphQLDeletion>> {
+        if !has_role(
+            ctx,
+            Role::Moderator,
+            &Scope::Thread(self.thread_hash.clone()),
+        )
+        .await?
+        {
+            return Ok(None);
+        }
 
-        let mut app = tide::new();
+        Ok(self.deletion.clone().map(|deletion| deletion.into()))
+    }
+
+    /// Whether this comment was posted under a shadowban, only visible to moderators of its
+    /// thread.
+    async fn shadowed(&self, ctx: &Context<'_>) -> Result<Option<bool>> {
+        Ok(has_role(
+            ctx,
+            Role::Moderator,
+            &Scope::Thread(self.thread_hash.clone()),
+        )
+        .await?
+        .then_some(self.shadowed))
+    }
 
-        app.at("/").post(async_graphql_tide::graphql(schema));
+    /// Former contents of this comment, oldest first.
+    async fn revisions(&self, ctx: &Context<'_>) -> Result<Vec<GraphQLRevision>> {
+        if self.deleted {
+            return Ok(vec![]);
+        }
 
-        Box::pin(app.listen(listen_addr).map_err(|_| Error::NewtorkError))
+        ctx.data::<Arc<dyn CommentStore>>()?
+            .find_revisions(&self.hash)
+            .await
+            .map(|revisions| {
+                revisions
+                    .into_iter()
+                    .map(|revision| revision.into())
+                    .collect()
+            })
+            .map_err(store_error)
     }
 }
 
-#[cfg(test)]
-mod test {
-    use std::sync::Arc;
+#[Object]
+impl Mutation {
+    /// Starts signing up with `provider`, what `email` holds depends on the provider.
+    async fn signup(
+        &self,
+        ctx: &Context<'_>,
+        provider: String,
+        email: String,
+    ) -> Result<GraphQLUser> {
+        rate_limit(ctx, Operation::Signup)?;
 
-    use crate::GraphQLFrontend;
-    use comments_rs_core_backend::{
-        data::User,
-        traits::{Frontend, UserStore},
-    };
-    use comments_rs_memdb_backend::MemDB;
-    use graphql_client::GraphQLQuery;
-    use reqwest::Response;
-    use serde_json::Value;
-    use tokio::select;
+        let provider = signup_provider(ctx, &provider)?;
+        let user = match ctx.data_opt::<SigninBinding>() {
+            Some(SigninBinding(binding)) => provider.signup_bound(&email, binding).await,
+            None => provider.signup(&email).await,
+        };
 
-    #[derive(GraphQLQuery)]
-    #[graphql(
-        schema_path = "schema.graphql",
-        query_path = "test/users-query.graphql",
-        response_derives = "Debug"
-    )]
-    pub struct Query;
+        user.map(|user| user.into()).map_err(graphql_error)
+    }
 
-    #[tokio::test]
-    async fn test_find_all_comments() {
-        let mut memdb = MemDB::default();
+    /// Starts signing in with a `provider` the user has to be sent to first, returns the url to
+    /// send them to. The provider sends them back to `redirect_uri` with the token for `signup`
+    /// and `confirm` in the query string.
+    ///
+    /// The sign in can only be finished by the browser which started it, which receives a
+    /// cookie for it.
+    async fn authorize(
+        &self,
+        ctx: &Context<'_>,
+        provider: String,
+        redirect_uri: String,
+    ) -> Result<String> {
+        rate_limit(ctx, Operation::Signup)?;
 
-        let _user = memdb
-            .save_user(User::new("test@mail.com", "test"))
+        let authorization = signup_provider(ctx, &provider)?
+            .authorize(&redirect_uri)
+            .map_err(graphql_error)?;
+        let sessions = ctx.data::<Arc<Sessions>>()?;
+
+        ctx.append_http_header("Set-Cookie", sessions.signin_cookie(&authorization.binding));
+
+        Ok(authorization.url)
+    }
+
+    /// Finishes signing up with `provider` and signs the user in.
+    async fn confirm(
+        &self,
+        ctx: &Context<'_>,
+        provider: String,
+        token: String,
+        user_name: String,
+    ) -> Result<GraphQLSession> {
+        rate_limit(ctx, Operation::Signup)?;
+
+        let provider = signup_provider(ctx, &provider)?;
+        let user = match ctx.data_opt::<SigninBinding>() {
+            Some(SigninBinding(binding)) => {
+                provider.confirm_bound(&token, binding, &user_name).await
+            }
+            None => provider.confirm(&token, &user_name).await,
+        }
+        .map_err(graphql_error)?;
+        let sessions = ctx.data::<Arc<Sessions>>()?;
+        let token = sessions.issue(&user);
+
+        ctx.append_http_header("Set-Cookie", sessions.cookie(&token));
+
+        Ok(GraphQLSession {
+            token,
+            user: user.into(),
+        })
+    }
+
+    /// Removes the session cookie, bearer tokens stay valid until they expire.
+    async fn sign_out(&self, ctx: &Context<'_>) -> Result<bool> {
+        let sessions = ctx.data::<Arc<Sessions>>()?;
+
+        ctx.append_http_header("Set-Cookie", sessions.removal_cookie());
+
+        Ok(true)
+    }
+
+    /// Creates a thread, site wide admins only.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn create_thread(&self, ctx: &Context<'_>, name: String) -> Result<GraphQLThread> {
+        let thread = Thread::try_new(&name).map_err(graphql_error)?;
+
+        ctx.data::<Arc<dyn ThreadStore>>()?
+            .save_thread(thread)
             .await
-            .expect("Could not save user!");
-        let _user1 = memdb
-            .save_user(User::new("test2@mail.com", "test2"))
+            .map(|thread| thread.into())
+            .map_err(store_error)
+    }
+
+    /// Deletes a thread along with its comments, admins of the thread only.
+    #[graphql(guard = "RoleGuard::new(Role::Admin).in_thread(&hash)")]
+    async fn delete_thread(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+    ) -> Result<Option<GraphQLThread>> {
+        let store = ctx.data::<Arc<dyn ThreadStore>>()?;
+
+        if store
+            .find_thread_by_hash(&hash)
+            .await
+            .map_err(store_error)?
+            .is_none()
+        {
+            return Ok(None);
+        }
+
+        let deleted = store.delete_thread(&hash).await;
+
+        audit(ctx, AuditAction::DeleteThread, &hash, None, deleted)
+            .await
+            .map(|thread| thread.map(|thread| thread.into()))
+    }
+
+    /// Decides whether new comments of a thread wait for approval, `null` follows the server
+    /// wide policy. Admins of the thread only.
+    #[graphql(guard = "RoleGuard::new(Role::Admin).in_thread(&hash)")]
+    async fn update_thread(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+        require_approval: Option<bool>,
+    ) -> Result<Option<GraphQLThread>> {
+        let store = ctx.data::<Arc<dyn ThreadStore>>()?;
+        let thread = match store
+            .find_thread_by_hash(&hash)
             .await
-            .expect("Could not save user!");
+            .map_err(store_error)?
+        {
+            Some(thread) => thread,
+            None => return Ok(None),
+        };
 
-        let frontend = GraphQLFrontend {
-            user_store: Arc::new(memdb),
+        let setting = match require_approval {
+            Some(require_approval) => require_approval.to_string(),
+            None => "default".to_string(),
         };
+        let reason = format!("require approval: {}", setting);
 
-        let request_body = Query::build_query(query::Variables {});
+        let updated = store
+            .update_thread(Thread {
+                require_approval,
+                ..thread
+            })
+            .await;
 
-        let client = reqwest::Client::new();
-        let response = client
-            .post("http://localhost:8000/")
-            .json(&request_body)
-            .send();
+        audit(ctx, AuditAction::UpdateThread, &hash, Some(reason), updated)
+            .await
+            .map(|thread| thread.map(|thread| thread.into()))
+    }
+
+    /// Locks a thread against new comments or unlocks it again, moderators of the thread only.
+    #[graphql(guard = "RoleGuard::new(Role::Moderator).in_thread(&hash)")]
+    async fn lock_thread(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+        locked: bool,
+        reason: Option<String>,
+    ) -> Result<Option<GraphQLThread>> {
+        let store = ctx.data::<Arc<dyn ThreadStore>>()?;
+        let thread = match store
+            .find_thread_by_hash(&hash)
+            .await
+            .map_err(store_error)?
+        {
+            Some(thread) => thread,
+            None => return Ok(None),
+        };
+        let action = if locked {
+            AuditAction::LockThread
+        } else {
+            AuditAction::UnlockThread
+        };
+
+        let updated = store.update_thread(Thread { locked, ..thread }).await;
+
+        audit(ctx, action, &hash, reason, updated)
+            .await
+            .map(|thread| thread.map(|thread| thread.into()))
+    }
+
+    /// Posts a comment to a thread or, given `parent_hash`, a reply to a comment of the thread.
+    ///
+    /// Comments to threads requiring approval or held by the spam filters stay pending until a
+    /// moderator approves them, comments the spam filters reject are saved as spam. Comments of
+    /// moderators of the thread skip both. Banned commenters can't post, shadowbanned ones post
+    /// comments only they see. Locked threads only take comments of their moderators.
+    ///
+    /// Where guests may comment, requests without session post under `guest_email` along with
+    /// the `proof` of work for a challenge.
+    async fn post_comment(
+        &self,
+        ctx: &Context<'_>,
+        thread_hash: String,
+        parent_hash: Option<String>,
+        content: String,
+        guest_email: Option<String>,
+        proof: Option<GraphQLProof>,
+    ) -> Result<GraphQLComment> {
+        let user = ctx.data_opt::<User>();
+        let challenges = ctx.data_opt::<Arc<Challenges>>();
+        let email = match (user, challenges) {
+            (Some(user), _) => user.email.clone(),
+            (None, Some(_)) => guest_email.unwrap_or_default(),
+            (None, None) => return Err(graphql_error(Error::AuthenticationError)),
+        };
+        rate_limit(ctx, Operation::Post)?;
+
+        let comment = match parent_hash {
+            Some(parent_hash) => {
+                Comment::try_new_reply(&thread_hash, &parent_hash, &email, now(), &content)
+            }
+            None => Comment::try_new(&thread_hash, &email, now(), &content),
+        }
+        .map_err(graphql_error)?;
+        let shadowed = check_bans(ctx, &email).await?;
+
+        let thread = ctx
+            .data::<Arc<dyn ThreadStore>>()?
+            .find_thread_by_hash(&thread_hash)
+            .await
+            .map_err(store_error)?
+            .ok_or_else(|| store_error(StoreError::ThreadNotExists(thread_hash.clone())))?;
+        let ApprovalPolicy(approval_required) = ctx.data::<ApprovalPolicy>()?;
+        let scope = Scope::Thread(thread_hash.clone());
+        let spam_filters = ctx.data::<SpamFilterChain>()?;
+        let moderator = has_role(ctx, Role::Moderator, &scope).await?;
+
+        if thread.locked && !moderator {
+            return Err(graphql_error(Error::ThreadLocked));
+        }
+
+        if let (None, Some(challenges)) = (user, challenges) {
+            // guests can't claim the email of a user, who could edit the comment otherwise
+            let registered = ctx
+                .data::<Arc<dyn UserStore>>()?
+                .find_user_by_email(&email)
+                .await
+                .map_err(store_error)?;
+
+            if registered.is_some() {
+                return Err(graphql_error(Error::PermissionError));
+            }
+
+            let proof = proof.ok_or_else(|| graphql_error(Error::ProofOfWorkError))?;
+            let nonce = proof
+                .nonce
+                .parse()
+                .map_err(|_| graphql_error(Error::ProofOfWorkError))?;
+
+            // only redeemed once everything else checks out, so a failed post keeps its solution
+            challenges
+                .redeem(&proof.challenge, nonce)
+                .map_err(graphql_error)?;
+        }
+
+        let status = if moderator {
+            CommentStatus::Approved
+        } else {
+            let request = ctx.data_opt::<RequestInfo>().cloned().unwrap_or_default();
+
+            match spam_filters.check(&comment, &request).await {
+                Verdict::Reject => CommentStatus::Spam,
+                Verdict::Hold => CommentStatus::Pending,
+                Verdict::Approve if thread.require_approval.unwrap_or(*approval_required) => {
+                    CommentStatus::Pending
+                }
+                Verdict::Approve => CommentStatus::Approved,
+            }
+        };
+
+        let comment = ctx
+            .data::<Arc<dyn CommentStore>>()?
+            .save_comment(Comment {
+                shadowed,
+                ..comment.with_status(status)
+            })
+            .await
+            .map_err(store_error)?;
+
+        spam_filters.saved(&comment).await;
+
+        Ok(comment.into())
+    }
+
+    /// Replaces the content of a comment of the signed in user, the former content is kept as
+    /// revision.
+    #[graphql(guard = "RoleGuard::new(Role::Commenter)")]
+    async fn edit_comment(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+        content: String,
+    ) -> Result<Option<GraphQLComment>> {
+        let user = current_user(ctx)?;
+        let store = ctx.data::<Arc<dyn CommentStore>>()?;
+        let comment = match store.find_comment(&hash).await.map_err(store_error)? {
+            Some(comment) if !comment.is_deleted() => comment,
+            _ => return Ok(None),
+        };
+        let now = now();
+
+        if comment.email != user.email {
+            return Err(graphql_error(Error::PermissionError));
+        }
+        check_bans(ctx, &user.email).await?;
+        if let EditWindow(Some(window)) = ctx.data::<EditWindow>()? {
+            if now > comment.date + window.as_millis() {
+                return Err(graphql_error(Error::EditWindowClosed));
+            }
+        }
+
+        let comment = comment.try_edit(&content, now).map_err(graphql_error)?;
+
+        store
+            .edit_comment(comment)
+            .await
+            .map(|comment| comment.map(|comment| comment.into()))
+            .map_err(store_error)
+    }
+
+    /// Deletes a comment of the signed in user or, for moderators of its thread, any comment.
+    /// Comments with replies are kept as tombstone.
+    #[graphql(guard = "RoleGuard::new(Role::Commenter)")]
+    async fn delete_comment(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+        reason: Option<String>,
+    ) -> Result<Option<GraphQLComment>> {
+        let user = current_user(ctx)?;
+        let store = ctx.data::<Arc<dyn CommentStore>>()?;
+        let comment = match store.find_comment(&hash).await.map_err(store_error)? {
+            Some(comment) if !comment.is_deleted() => comment,
+            _ => return Ok(None),
+        };
+
+        if comment.email != user.email
+            && !has_role(
+                ctx,
+                Role::Moderator,
+                &Scope::Thread(comment.thread_hash.clone()),
+            )
+            .await?
+        {
+            return Err(graphql_error(Error::PermissionError));
+        }
+
+        let deletion = Deletion {
+            deleted_by: user.name.clone(),
+            reason: reason.clone(),
+            date: now(),
+        };
+
+        let deleted = store.soft_delete_comment(&hash, deletion).await;
+
+        audit(ctx, AuditAction::DeleteComment, &hash, reason, deleted)
+            .await
+            .map(|comment| comment.map(|comment| comment.into()))
+    }
+
+    /// Brings back a comment kept as tombstone, moderators of its thread only.
+    #[graphql(guard = "RoleGuard::new(Role::Moderator).on_comment(&hash)")]
+    async fn restore_comment(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+    ) -> Result<Option<GraphQLComment>> {
+        let store = ctx.data::<Arc<dyn CommentStore>>()?;
+
+        match store.find_comment(&hash).await.map_err(store_error)? {
+            Some(comment) if comment.is_deleted() => {}
+            _ => return Ok(None),
+        }
+
+        let restored = store.restore_comment(&hash).await;
+
+        audit(ctx, AuditAction::RestoreComment, &hash, None, restored)
+            .await
+            .map(|comment| comment.map(|comment| comment.into()))
+    }
+
+    /// Publishes a comment, moderators of its thread only. The spam filters learn from it as ham.
+    #[graphql(guard = "RoleGuard::new(Role::Moderator).on_comment(&hash)")]
+    async fn approve_comment(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+    ) -> Result<Option<GraphQLComment>> {
+        set_comment_status(ctx, &hash, CommentStatus::Approved).await
+    }
+
+    /// Hides a comment, marked as spam if `spam` is set, which the spam filters learn from.
+    /// Moderators of its thread only.
+    #[graphql(guard = "RoleGuard::new(Role::Moderator).on_comment(&hash)")]
+    async fn reject_comment(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+        #[graphql(default)] spam: bool,
+    ) -> Result<Option<GraphQLComment>> {
+        let status = if spam {
+            CommentStatus::Spam
+        } else {
+            CommentStatus::Rejected
+        };
+
+        set_comment_status(ctx, &hash, status).await
+    }
+
+    /// Reports a comment as abusive on behalf of the signed in user, reporting it again replaces
+    /// the former report.
+    ///
+    /// Only approved comments can be reported. They go back to moderation once reported by the
+    /// configured number of distinct users, approving them again dismisses the reports so far.
+    async fn report_comment(
+        &self,
+        ctx: &Context<'_>,
+        hash: String,
+        reason: String,
+    ) -> Result<Option<GraphQLComment>> {
+        let user = current_user(ctx)?;
+        rate_limit(ctx, Operation::Report)?;
+        let comments = ctx.data::<Arc<dyn CommentStore>>()?;
+        let comment = match comments.find_comment(&hash).await.map_err(store_error)? {
+            Some(comment) if !comment.is_deleted() && comment.status == CommentStatus::Approved => {
+                comment
+            }
+            _ => return Ok(None),
+        };
+        let report = Report::try_new(&hash, &user.name, &reason, now()).map_err(graphql_error)?;
+        let reporters = ctx
+            .data::<Arc<dyn ReportStore>>()?
+            .save_report(report)
+            .await
+            .map_err(store_error)?;
+
+        if let ReportThreshold(Some(threshold)) = ctx.data::<ReportThreshold>()? {
+            if reporters >= *threshold {
+                return comments
+                    .set_comment_status(&hash, CommentStatus::Pending)
+                    .await
+                    .map(|comment| comment.map(|comment| comment.into()))
+                    .map_err(store_error);
+            }
+        }
+
+        Ok(Some(comment.into()))
+    }
+
+    /// Bans the user named `value`, the email domain `value` or the IP range `value` from
+    /// commenting, for `duration` seconds or for good. Site wide admins only.
+    ///
+    /// Shadowbans let the commenter go on posting, but only they see the comments posted from
+    /// now on.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn ban(
+        &self,
+        ctx: &Context<'_>,
+        kind: GraphQLBanKind,
+        value: String,
+        #[graphql(default)] shadow: bool,
+        reason: Option<String>,
+        duration: Option<u32>,
+    ) -> Result<GraphQLBan> {
+        let target = match kind {
+            GraphQLBanKind::User => BanTarget::User(value),
+            GraphQLBanKind::EmailDomain => BanTarget::EmailDomain(value),
+            GraphQLBanKind::IpRange => {
+                BanTarget::IpRange(value.parse().map_err(async_graphql::Error::new)?)
+            }
+        };
+        let now = now();
+        let ban = Ban {
+            shadow,
+            reason,
+            expires_at: duration.map(|duration| now + duration as u128 * 1000),
+            ..Ban::new(target, &current_user(ctx)?.name, now)
+        };
+
+        let (id, reason) = (ban.id.clone(), ban.reason.clone());
+        let saved = ctx.data::<Arc<dyn BanStore>>()?.save_ban(ban).await;
+
+        audit(ctx, AuditAction::Ban, &id, reason, saved)
+            .await
+            .map(|ban| ban.into())
+    }
+
+    /// Lifts a ban, site wide admins only.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn unban(&self, ctx: &Context<'_>, id: String) -> Result<Option<GraphQLBan>> {
+        let store = ctx.data::<Arc<dyn BanStore>>()?;
+
+        if !store
+            .find_all_bans()
+            .await
+            .map_err(store_error)?
+            .iter()
+            .any(|ban| ban.id == id)
+        {
+            return Ok(None);
+        }
+
+        let deleted = store.delete_ban(&id).await;
+
+        audit(ctx, AuditAction::Unban, &id, None, deleted)
+            .await
+            .map(|ban| ban.map(|ban| ban.into()))
+    }
+
+    /// Gives the user named `user_name` `role` in the thread with hash `thread_hash` or site
+    /// wide, replacing the role they held there. `null` if the user or thread doesn't exist.
+    ///
+    /// Admins of the scope may grant and replace roles below their own, owners any role.
+    #[graphql(guard = "RoleGuard::new(Role::Admin).in_scope(thread_scope(thread_hash.as_deref()))")]
+    async fn grant_role(
+        &self,
+        ctx: &Context<'_>,
+        user_name: String,
+        role: GraphQLRole,
+        thread_hash: Option<String>,
+    ) -> Result<Option<GraphQLRoleGrant>> {
+        let scope = thread_scope(thread_hash.as_deref());
+        let store = ctx.data::<Arc<dyn RoleStore>>()?;
+        let user = ctx
+            .data::<Arc<dyn UserStore>>()?
+            .find_user(&user_name)
+            .await
+            .map_err(store_error)?;
+        let thread = match &thread_hash {
+            Some(thread_hash) => ctx
+                .data::<Arc<dyn ThreadStore>>()?
+                .find_thread_by_hash(thread_hash)
+                .await
+                .map_err(store_error)?
+                .is_some(),
+            None => true,
+        };
+
+        if user.is_none() || !thread {
+            return Ok(None);
+        }
+
+        let held = store
+            .find_roles(&user_name)
+            .await
+            .map_err(store_error)?
+            .into_iter()
+            .find(|grant| grant.scope == scope);
+
+        require_rank_over(ctx, role.into(), &scope).await?;
+        if let Some(held) = held {
+            require_rank_over(ctx, held.role, &scope).await?;
+        }
+
+        let grant = RoleGrant::new(&user_name, role.into(), scope);
+        let reason = format!("{} in {}", grant.role, grant.scope);
+
+        let saved = store.save_role(grant).await;
+
+        audit(ctx, AuditAction::GrantRole, &user_name, Some(reason), saved)
+            .await
+            .map(|grant| Some(grant.into()))
+    }
+
+    /// Takes the role of the user named `user_name` in the thread with hash `thread_hash` or
+    /// site wide, `null` if they held none there.
+    ///
+    /// Admins of the scope may revoke roles below their own, owners any role.
+    #[graphql(guard = "RoleGuard::new(Role::Admin).in_scope(thread_scope(thread_hash.as_deref()))")]
+    async fn revoke_role(
+        &self,
+        ctx: &Context<'_>,
+        user_name: String,
+        thread_hash: Option<String>,
+    ) -> Result<Option<GraphQLRoleGrant>> {
+        let scope = thread_scope(thread_hash.as_deref());
+        let store = ctx.data::<Arc<dyn RoleStore>>()?;
+        let held = store
+            .find_roles(&user_name)
+            .await
+            .map_err(store_error)?
+            .into_iter()
+            .find(|grant| grant.scope == scope);
+
+        let held = match held {
+            Some(held) => held,
+            None => return Ok(None),
+        };
+
+        require_rank_over(ctx, held.role, &scope).await?;
+
+        let reason = format!("{} in {}", held.role, held.scope);
+
+        let deleted = store.delete_role(&user_name, &scope).await;
+
+        audit(
+            ctx,
+            AuditAction::RevokeRole,
+            &user_name,
+            Some(reason),
+            deleted,
+        )
+        .await
+        .map(|grant| grant.map(|grant| grant.into()))
+    }
+}
+
+/// Fails if the commenter posting under `email` is banned, returns whether they are shadowbanned
+/// otherwise.
+///
+/// Bans apply to the signed in user and the client address of the request as well.
+async fn check_bans(ctx: &Context<'_>, email: &str) -> Result<bool> {
+    let user_name = ctx.data_opt::<User>().map(|user| user.name.as_str());
+    let ip = ctx.data_opt::<RequestInfo>().and_then(|request| request.ip);
+    let now = now();
+    let (shadowbans, bans): (Vec<Ban>, Vec<Ban>) = ctx
+        .data::<Arc<dyn BanStore>>()?
+        .find_all_bans()
+        .await
+        .map_err(store_error)?
+        .into_iter()
+        .filter(|ban| ban.is_active(now) && ban.applies_to(user_name, email, ip))
+        .partition(|ban| ban.shadow);
+
+    if !bans.is_empty() {
+        // the commenter is banned until the last ban ends, or for good if one never does
+        let until = bans
+            .iter()
+            .map(|ban| ban.expires_at)
+            .max_by_key(|expires_at| expires_at.unwrap_or(u128::MAX));
+
+        return Err(graphql_error(Error::Banned(until.flatten())));
+    }
+
+    Ok(!shadowbans.is_empty())
+}
+
+async fn set_comment_status(
+    ctx: &Context<'_>,
+    hash: &str,
+    status: CommentStatus,
+) -> Result<Option<GraphQLComment>> {
+    let store = ctx.data::<Arc<dyn CommentStore>>()?;
+
+    if store
+        .find_comment(hash)
+        .await
+        .map_err(store_error)?
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    let action = match status {
+        CommentStatus::Approved => Some(AuditAction::ApproveComment),
+        CommentStatus::Rejected => Some(AuditAction::RejectComment),
+        CommentStatus::Spam => Some(AuditAction::MarkSpam),
+        CommentStatus::Pending => None,
+    };
+
+    let updated = store.set_comment_status(hash, status).await;
+    let comment = match action {
+        Some(action) => audit(ctx, action, hash, None, updated).await?,
+        None => updated.map_err(store_error)?,
+    };
+
+    if let Some(comment) = &comment {
+        let spam_filters = ctx.data::<SpamFilterChain>()?;
+
+        match status {
+            CommentStatus::Approved => spam_filters.learn(comment, false).await,
+            CommentStatus::Spam => spam_filters.learn(comment, true).await,
+            CommentStatus::Pending | CommentStatus::Rejected => {}
+        }
+    }
+
+    Ok(comment.map(|comment| comment.into()))
+}
+
+#[Subscription]
+impl Subscription {
+    /// Comments becoming visible, either posted without need for approval or approved later.
+    /// Comments posted under a shadowban never show up.
+    async fn comment_added(
+        &self,
+        ctx: &Context<'_>,
+        thread_hash: String,
+    ) -> Result<impl Stream<Item = GraphQLComment>> {
+        comment_events(ctx, thread_hash, |event| match event {
+            CommentEvent::Added(comment) if is_public(&comment) => Some(comment),
+            CommentEvent::StatusChanged { comment, previous }
+                if is_public(&comment) && previous != CommentStatus::Approved =>
+            {
+                Some(comment)
+            }
+            _ => None,
+        })
+    }
+
+    /// Edits of visible comments.
+    async fn comment_edited(
+        &self,
+        ctx: &Context<'_>,
+        thread_hash: String,
+    ) -> Result<impl Stream<Item = GraphQLComment>> {
+        comment_events(ctx, thread_hash, |event| match event {
+            CommentEvent::Edited(comment) if is_public(&comment) => Some(comment),
+            _ => None,
+        })
+    }
+
+    /// Visible comments deleted or hidden by a moderator.
+    async fn comment_deleted(
+        &self,
+        ctx: &Context<'_>,
+        thread_hash: String,
+    ) -> Result<impl Stream<Item = GraphQLComment>> {
+        comment_events(ctx, thread_hash, |event| match event {
+            CommentEvent::Deleted(comment) if is_public(&comment) => Some(comment),
+            CommentEvent::StatusChanged { comment, previous }
+                if previous == CommentStatus::Approved
+                    && comment.status != CommentStatus::Approved
+                    && !comment.shadowed =>
+            {
+                Some(comment)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Whether everybody sees `comment`, not only moderators and its author.
+fn is_public(comment: &Comment) -> bool {
+    comment.status == CommentStatus::Approved && !comment.shadowed
+}
+
+/// Streams the comments of the thread with `thread_hash` picked from the store events by `select`.
+fn comment_events(
+    ctx: &Context<'_>,
+    thread_hash: String,
+    select: fn(CommentEvent) -> Option<Comment>,
+) -> Result<impl Stream<Item = GraphQLComment>> {
+    let events = ctx.data::<Arc<dyn CommentStore>>()?.subscribe();
+
+    Ok(events.filter_map(move |event| {
+        let comment = select(event)
+            .filter(|comment| comment.thread_hash == thread_hash)
+            .map(GraphQLComment::from);
+
+        future::ready(comment)
+    }))
+}
+
+/// Computes the positions `start..end` of the page selected by the connection arguments out of
+/// `total` items.
+fn page_bounds(
+    after: Option<usize>,
+    before: Option<usize>,
+    first: Option<usize>,
+    last: Option<usize>,
+    total: usize,
+) -> (usize, usize) {
+    let mut start = after.map(|after| after + 1).unwrap_or(0).min(total);
+    let mut end = before.unwrap_or(total).clamp(start, total);
+
+    if let Some(first) = first {
+        end = (start + first).min(end);
+    }
+
+    if let Some(last) = last {
+        start = end.saturating_sub(last).max(start);
+    }
+
+    (start, end)
+}
+
+/// Builds the connection of the `comments` at positions `start..end` out of `total`.
+fn connection<T, N>(
+    comments: Vec<T>,
+    start: usize,
+    end: usize,
+    total: usize,
+) -> Connection<usize, N, CommentConnectionFields>
+where
+    N: From<T> + OutputType,
+{
+    let mut connection = Connection::with_additional_fields(
+        start > 0,
+        end < total,
+        CommentConnectionFields { total_count: total },
+    );
+    connection.append(
+        comments
+            .into_iter()
+            .enumerate()
+            .map(|(index, comment)| Edge::new(start + index, comment.into())),
+    );
+
+    connection
+}
+
+/// Converts an error into a GraphQL error carrying its code and, for validation errors, the
+/// failed validations per field in the `fields` extension, for rate limited requests, the
+/// seconds to wait in the `retryAfter` extension or, for expiring bans, their end in the
+/// `bannedUntil` extension.
+fn graphql_error(error: Error) -> async_graphql::Error {
+    async_graphql::Error::new(error.to_string()).extend_with(|_, extensions| {
+        extensions.set("code", error.code());
+
+        if let Error::ValidationError { validation_errors } = &error {
+            let fields: IndexMap<Name, Value> = validation_errors
+                .field_errors()
+                .into_iter()
+                .map(|(field, errors)| {
+                    let codes: Vec<Value> = errors
+                        .iter()
+                        .map(|error| Value::from(error.code.to_string()))
+                        .collect();
+
+                    (Name::new(field), Value::List(codes))
+                })
+                .collect();
+
+            extensions.set("fields", Value::Object(fields));
+        }
+        if let Error::RateLimited(retry_after) = &error {
+            extensions.set("retryAfter", retry_after.as_secs_f64().ceil() as u64);
+        }
+        if let Error::Banned(Some(until)) = &error {
+            extensions.set("bannedUntil", until.to_string());
+        }
+    })
+}
+
+/// Records `action` of the signed in user on `target` in the audit log once `taken`, the
+/// result of the store call taking it, is in. Failed calls are recorded as
+/// [`AuditAction::Failed`] attempts, so the log never shows an action that didn't happen.
+///
+/// The stores don't share transactions, so an action taken but failing to be recorded fails the
+/// request all the same, letting the user know it went untraced.
+async fn audit<T>(
+    ctx: &Context<'_>,
+    action: AuditAction,
+    target: &str,
+    reason: Option<String>,
+    taken: Result<T, StoreError>,
+) -> Result<T> {
+    let actor = &current_user(ctx)?.name;
+    let store = ctx.data::<Arc<dyn AuditStore>>()?;
+
+    match taken {
+        Ok(value) => {
+            let entry = AuditEntry {
+                reason,
+                ..AuditEntry::new(action, actor, target, now())
+            };
+
+            store.append_audit_entry(entry).await.map_err(store_error)?;
+
+            Ok(value)
+        }
+        Err(error) => {
+            let error = Error::from(error);
+            let failure = format!("{} failed with {}", action, error.code());
+            let entry = AuditEntry {
+                reason: Some(match reason {
+                    Some(reason) => format!("{}: {}", failure, reason),
+                    None => failure,
+                }),
+                ..AuditEntry::new(AuditAction::Failed, actor, target, now())
+            };
+
+            // the error of the action is the one to report, even if recording it failed too
+            let _ = store.append_audit_entry(entry).await;
+
+            Err(graphql_error(error))
+        }
+    }
+}
+
+/// Fails unless the signed in user may grant and revoke `role` in `scope`.
+async fn require_rank_over(ctx: &Context<'_>, role: Role, scope: &Scope) -> Result<()> {
+    match role_in(ctx, scope).await? {
+        Some(Role::Owner) => Ok(()),
+        Some(held) if held > role => Ok(()),
+        _ => Err(graphql_error(Error::PermissionError)),
+    }
+}
+
+fn current_user<'a>(ctx: &Context<'a>) -> Result<&'a User> {
+    ctx.data_opt::<User>()
+        .ok_or_else(|| graphql_error(Error::AuthenticationError))
+}
+
+/// Counts the request against the limit of `operation` for the signed in user and the client
+/// address.
+fn rate_limit(ctx: &Context<'_>, operation: Operation) -> Result<()> {
+    let clients = Client::of(ctx.data_opt::<User>(), ctx.data_opt::<RequestInfo>());
+
+    ctx.data::<Arc<RateLimiter>>()?
+        .check(operation, &clients)
+        .map_err(|error| {
+            if let Error::RateLimited(retry_after) = &error {
+                ctx.append_http_header(
+                    "Retry-After",
+                    (retry_after.as_secs_f64().ceil() as u64).to_string(),
+                );
+            }
+
+            graphql_error(error)
+        })
+}
+
+fn signup_provider<'a>(ctx: &Context<'a>, name: &str) -> Result<&'a Arc<dyn SignupProvider>> {
+    ctx.data::<Vec<Arc<dyn SignupProvider>>>()?
+        .iter()
+        .find(|provider| provider.name() == name)
+        .ok_or_else(|| graphql_error(Error::SignupError))
+}
+
+fn store_error(error: StoreError) -> async_graphql::Error {
+    graphql_error(error.into())
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before unix epoch")
+        .as_millis()
+}
+
+#[derive(Clone)]
+pub struct GraphQLFrontend {
+    pub user_store: Arc<dyn UserStore>,
+    pub thread_store: Arc<dyn ThreadStore>,
+    pub comment_store: Arc<dyn CommentStore>,
+    pub ban_store: Arc<dyn BanStore>,
+    pub report_store: Arc<dyn ReportStore>,
+    pub role_store: Arc<dyn RoleStore>,
+    pub audit_store: Arc<dyn AuditStore>,
+    pub signup_providers: Vec<Arc<dyn SignupProvider>>,
+    pub sessions: Arc<Sessions>,
+    pub edit_window: Option<Duration>,
+    /// Site wide roles of users by lowercase email, on top of the roles granted in the store
+    pub roles: HashMap<String, Role>,
+    /// Whether comments wait for approval in threads that don't decide themselves
+    pub approval_required: bool,
+    /// Number of distinct reporters which sends a comment back to moderation
+    pub report_threshold: Option<usize>,
+    /// Run on new comments in the order they were added
+    pub spam_filters: Vec<Arc<dyn SpamFilter>>,
+    /// Number of reverse proxies in front of the server whose forwarding headers are trusted
+    pub trusted_proxies: usize,
+    pub rate_limits: HashMap<Operation, RateLimit>,
+    /// Leading zero bits of the proof of work guests have to do, guests can't comment if unset
+    pub guest_difficulty: Option<u32>,
+}
+
+impl GraphQLFrontend {
+    /// Serves all data from a single store implementing every store trait.
+    pub fn new<S>(store: Arc<S>) -> Self
+    where
+        S: UserStore
+            + ThreadStore
+            + CommentStore
+            + BanStore
+            + ReportStore
+            + RoleStore
+            + AuditStore
+            + 'static,
+    {
+        Self {
+            user_store: store.clone(),
+            thread_store: store.clone(),
+            comment_store: store.clone(),
+            ban_store: store.clone(),
+            report_store: store.clone(),
+            role_store: store.clone(),
+            audit_store: store,
+            signup_providers: vec![],
+            sessions: Arc::new(Sessions::random()),
+            edit_window: None,
+            roles: HashMap::new(),
+            approval_required: false,
+            report_threshold: None,
+            spam_filters: vec![],
+            trusted_proxies: 0,
+            rate_limits: HashMap::new(),
+            guest_difficulty: None,
+        }
+    }
+
+    /// Signs session tokens with `secret`, so sessions survive restarts of the server.
+    pub fn with_session_secret(mut self, secret: &[u8]) -> Self {
+        self.sessions = Arc::new(Sessions::new(secret));
+        self
+    }
+
+    pub fn with_signup_provider(mut self, provider: Arc<dyn SignupProvider>) -> Self {
+        self.signup_providers.push(provider);
+        self
+    }
+
+    /// Only lets users edit their comments for `window` after posting them.
+    pub fn with_edit_window(mut self, window: Duration) -> Self {
+        self.edit_window = Some(window);
+        self
+    }
+
+    /// Gives the user signed up with `email` `role` site wide, which can't be revoked through the
+    /// API.
+    ///
+    /// Users are picked by the email their signup provider verified rather than by name, as
+    /// anyone can sign up under a name nobody took yet.
+    pub fn with_role(mut self, email: &str, role: Role) -> Self {
+        self.roles.insert(email.to_lowercase(), role);
+        self
+    }
+
+    /// Holds new comments for approval unless their thread decides otherwise.
+    pub fn with_approval_required(mut self) -> Self {
+        self.approval_required = true;
+        self
+    }
+
+    /// Sends approved comments back to moderation once `reporters` distinct users reported them.
+    pub fn with_report_threshold(mut self, reporters: usize) -> Self {
+        self.report_threshold = Some(reporters);
+        self
+    }
+
+    /// Runs new comments through `filter` after the filters added so far.
+    pub fn with_spam_filter(mut self, filter: Arc<dyn SpamFilter>) -> Self {
+        self.spam_filters.push(filter);
+        self
+    }
+
+    /// Takes client addresses from the `Forwarded` or `X-Forwarded-For` header behind `hops`
+    /// reverse proxies, each appending the address it received the request from. Addresses
+    /// further left are sent by the client and ignored.
+    pub fn with_trusted_proxies(mut self, hops: usize) -> Self {
+        self.trusted_proxies = hops;
+        self
+    }
+
+    /// Limits how often each user and each client address may perform `operation`.
+    pub fn with_rate_limit(mut self, operation: Operation, limit: RateLimit) -> Self {
+        self.rate_limits.insert(operation, limit);
+        self
+    }
+
+    /// Lets guests comment after solving a proof of work challenge of `difficulty` leading zero
+    /// bits, which takes about `2^difficulty` hashes.
+    pub fn with_guest_comments(mut self, difficulty: u32) -> Self {
+        self.guest_difficulty = Some(difficulty);
+        self
+    }
+
+    /// Resolves a session token into the user it was issued for, if that user still exists.
+    pub async fn authenticate(&self, token: &str) -> Option<User> {
+        let name = self.sessions.verify(token).ok()?;
+
+        self.user_store.find_user(&name).await.ok().flatten()
+    }
+
+    pub fn schema(&self) -> CommentsSchema {
+        let mut builder = Schema::build(Query, Mutation, Subscription)
+            .data(self.user_store.clone())
+            .data(self.thread_store.clone())
+            .data(self.comment_store.clone())
+            .data(self.ban_store.clone())
+            .data(self.report_store.clone())
+            .data(self.role_store.clone())
+            .data(self.audit_store.clone())
+            .data(self.signup_providers.clone())
+            .data(self.sessions.clone())
+            .data(EditWindow(self.edit_window))
+            .data(ConfiguredRoles(self.roles.clone()))
+            .data(ApprovalPolicy(self.approval_required))
+            .data(ReportThreshold(self.report_threshold))
+            .data(SpamFilterChain::new(self.spam_filters.clone()))
+            .data(Arc::new(RateLimiter::new(self.rate_limits.clone())))
+            .extension(QueryRateLimit);
+
+        if let Some(difficulty) = self.guest_difficulty {
+            builder = builder.data(Arc::new(Challenges::new(difficulty)));
+        }
+
+        builder.finish()
+    }
+
+    /// Serves queries and mutations via POST and subscriptions via graphql-ws on `/`.
+    ///
+    /// Requests carrying a valid session token have the signed in [`User`] in their context, every
+    /// request has its [`RequestInfo`].
+    pub fn app(&self) -> tide::Server<()> {
+        let schema = self.schema();
+        let frontend = Arc::new(self.clone());
+        let mut app = tide::new();
+
+        app.at("/")
+            .get(GraphQLSubscription::new(schema.clone()).build())
+            .post(move |request: tide::Request<()>| {
+                let schema = schema.clone();
+                let frontend = frontend.clone();
+
+                async move {
+                    let cookie = request.header("Cookie").map(|value| value.as_str());
+                    let token = session_token(
+                        request.header("Authorization").map(|value| value.as_str()),
+                        cookie,
+                    );
+                    let binding = signin_binding(cookie);
+                    let request_info = request_info(&request, frontend.trusted_proxies);
+                    let mut graphql_request = async_graphql_tide::receive_request(request)
+                        .await?
+                        .data(request_info);
+
+                    if let Some(binding) = binding {
+                        graphql_request = graphql_request.data(binding);
+                    }
+                    if let Some(user) = match token {
+                        Some(token) => frontend.authenticate(&token).await,
+                        None => None,
+                    } {
+                        graphql_request = graphql_request.data(user);
+                    }
+
+                    async_graphql_tide::respond(schema.execute(graphql_request).await)
+                }
+            });
+
+        app
+    }
+}
+
+/// Takes the client address from the `Forwarded` or `X-Forwarded-For` header if there are
+/// `trusted_proxies`, from the connection otherwise.
+fn request_info(request: &tide::Request<()>, trusted_proxies: usize) -> RequestInfo {
+    let header = |name: &str| request.header(name).map(|value| value.as_str().to_string());
+    let values = |name: &str| {
+        request
+            .header(name)
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+    let peer_ip = request.peer_addr().and_then(parse_ip);
+    let ip = if trusted_proxies > 0 {
+        forwarded_ip(
+            &values("Forwarded"),
+            &values("X-Forwarded-For"),
+            trusted_proxies,
+        )
+        .or(peer_ip)
+    } else {
+        peer_ip
+    };
+
+    RequestInfo {
+        ip,
+        user_agent: header("User-Agent"),
+        referrer: header("Referer"),
+    }
+}
+
+/// The address the outermost of `hops` proxies received the request from, that is the `hops`th
+/// entry from the right of the `Forwarded` header, or of `X-Forwarded-For` without it.
+///
+/// Requests that passed fewer proxies resolve to the leftmost entry.
+fn forwarded_ip(forwarded: &[&str], x_forwarded_for: &[&str], hops: usize) -> Option<IpAddr> {
+    let entries: Vec<Option<IpAddr>> = if forwarded.is_empty() {
+        x_forwarded_for
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(parse_ip)
+            .collect()
+    } else {
+        forwarded
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|element| {
+                element
+                    .split(';')
+                    .find_map(|pair| {
+                        let (key, value) = pair.split_once('=')?;
+
+                        key.trim().eq_ignore_ascii_case("for").then_some(value)
+                    })
+                    .and_then(parse_ip)
+            })
+            .collect()
+    };
+
+    entries
+        .get(entries.len().saturating_sub(hops))
+        .copied()
+        .flatten()
+}
+
+/// Parses an address with or without port, as found in `Forwarded` headers or peer addresses.
+fn parse_ip(address: &str) -> Option<IpAddr> {
+    let address = address.trim().trim_matches('"');
+
+    address
+        .parse::<SocketAddr>()
+        .map(|address| address.ip())
+        .or_else(|_| address.trim_matches(|c| c == '[' || c == ']').parse())
+        .ok()
+}
+
+impl From<User> for GraphQLUser {
+    fn from(u: User) -> Self {
+        GraphQLUser {
+            name: u.name,
+            email: u.email,
+        }
+    }
+}
+
+impl From<Thread> for GraphQLThread {
+    fn from(t: Thread) -> Self {
+        Self {
+            hash: t.hash,
+            name: t.name,
+            require_approval: t.require_approval,
+            locked: t.locked,
+        }
+    }
+}
+
+impl From<Comment> for GraphQLComment {
+    fn from(c: Comment) -> Self {
+        let deleted = c.is_deleted();
+        let hide = |value: String| {
+            if deleted {
+                TOMBSTONE.to_string()
+            } else {
+                value
+            }
+        };
+
+        Self {
+            thread_hash: c.thread_hash,
+            hash: c.hash,
+            parent_hash: c.parent_hash,
+            email: hide(c.email),
+            date: c.date.to_string(),
+            content: hide(c.content),
+            edited_at: c.edited_at.map(|edited_at| edited_at.to_string()),
+            deleted,
+            status: c.status.into(),
+            deletion: c.deletion,
+            shadowed: c.shadowed,
+        }
+    }
+}
+
+impl From<(Comment, usize)> for GraphQLReportedComment {
+    fn from((comment, report_count): (Comment, usize)) -> Self {
+        Self {
+            comment: comment.into(),
+            report_count,
+        }
+    }
+}
+
+impl From<Report> for GraphQLReport {
+    fn from(r: Report) -> Self {
+        Self {
+            reporter: r.reporter,
+            reason: r.reason,
+            date: r.date.to_string(),
+        }
+    }
+}
+
+impl From<AuditEntry> for GraphQLAuditEntry {
+    fn from(e: AuditEntry) -> Self {
+        Self {
+            action: e.action.into(),
+            actor: e.actor,
+            target: e.target,
+            reason: e.reason,
+            date: e.date.to_string(),
+        }
+    }
+}
+
+impl From<RoleGrant> for GraphQLRoleGrant {
+    fn from(g: RoleGrant) -> Self {
+        let thread_hash = match g.scope {
+            Scope::Site => None,
+            Scope::Thread(thread_hash) => Some(thread_hash),
+        };
+
+        Self {
+            user_name: g.user_name,
+            role: g.role.into(),
+            thread_hash,
+        }
+    }
+}
+
+impl From<Ban> for GraphQLBan {
+    fn from(b: Ban) -> Self {
+        let kind = match b.target {
+            BanTarget::User(_) => GraphQLBanKind::User,
+            BanTarget::EmailDomain(_) => GraphQLBanKind::EmailDomain,
+            BanTarget::IpRange(_) => GraphQLBanKind::IpRange,
+        };
+
+        Self {
+            id: b.id,
+            kind,
+            value: b.target.value(),
+            shadow: b.shadow,
+            reason: b.reason,
+            banned_by: b.banned_by,
+            date: b.date.to_string(),
+            expires_at: b.expires_at.map(|expires_at| expires_at.to_string()),
+        }
+    }
+}
+
+impl From<Deletion> for GraphQLDeletion {
+    fn from(d: Deletion) -> Self {
+        Self {
+            deleted_by: d.deleted_by,
+            reason: d.reason,
+            date: d.date.to_string(),
+        }
+    }
+}
+
+impl From<Revision> for GraphQLRevision {
+    fn from(r: Revision) -> Self {
+        Self {
+            date: r.date.to_string(),
+            content: r.content,
+        }
+    }
+}
+
+impl Frontend for GraphQLFrontend {
+    fn run(&self) -> Pin<Box<dyn std::future::Future<Output = Result<(), Error>>>> {
+        let listen_addr = env::var("LISTEN_ADDR").unwrap_or_else(|_| "localhost:8000".to_owned());
+
+        println!("Hostet at: http://{}", listen_addr);
+
+        let app = self.app();
+
+        Box::pin(app.listen(listen_addr).map_err(|_| Error::NewtorkError))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        forwarded_ip, parse_ip,
+        rate_limit::{Operation, RateLimit},
+        session::{Sessions, SigninBinding},
+        GraphQLFrontend,
+    };
+    use async_graphql::Request;
+    use comments_rs_core_backend::{
+        data::{
+            AuditAction, AuditEntry, Ban, Comment, CommentStatus, RequestInfo, Role, Thread, User,
+        },
+        error::{Error, StoreError},
+        pow,
+        spam::{Blocklist, DuplicateContent, LinkLimit, NaiveBayes, Verdict},
+        traits::{
+            AuditStore, Authorization, BanStore, CommentStore, RoleStore, SignupProvider,
+            SignupResult, SpamFilter, SpamResult, StoreResult, ThreadStore, UserStore,
+        },
+    };
+    use comments_rs_memdb_backend::MemDB;
+    use futures::{SinkExt, StreamExt};
+    use graphql_client::GraphQLQuery;
+    use serde_json::{json, Value};
+    use tide::listener::Listener;
+    use tokio::{select, time::sleep};
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    /// Confirms every token as the email of the user to sign in.
+    struct TestSignupProvider {
+        user_store: Arc<MemDB>,
+    }
+
+    impl SignupProvider for TestSignupProvider {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn signup(&self, email: &str) -> SignupResult {
+            let user = User {
+                email: email.to_string(),
+                name: String::new(),
+            };
+
+            Box::pin(async move { Ok(user) })
+        }
+
+        fn confirm(&self, token: &str, user_name: &str) -> SignupResult {
+            let user_store = self.user_store.clone();
+            let user = User::try_new(token, user_name);
+
+            Box::pin(async move { Ok(user_store.save_user(user?).await?) })
+        }
+
+        fn authorize(&self, redirect_uri: &str) -> Result<Authorization, Error> {
+            Ok(Authorization {
+                url: format!("https://login.test/?redirect_uri={}", redirect_uri),
+                binding: "binding".to_string(),
+            })
+        }
+
+        /// Only confirms sign ins started with `authorize`.
+        fn confirm_bound(&self, token: &str, binding: &str, user_name: &str) -> SignupResult {
+            if binding != "binding" {
+                return Box::pin(async { Err(Error::SignupError) });
+            }
+
+            self.confirm(token, user_name)
+        }
+    }
+
+    fn signed_in(query: impl Into<String>) -> Request {
+        Request::new(query).data(User::new("test@mail.com", "test"))
+    }
+
+    #[derive(GraphQLQuery)]
+    #[graphql(
+        schema_path = "schema.graphql",
+        query_path = "test/users-query.graphql",
+        response_derives = "Debug"
+    )]
+    pub struct Query;
+
+    #[tokio::test]
+    async fn test_users() {
+        let memdb = Arc::new(MemDB::default());
+        memdb
+            .save_user(User::new("test@mail.com", "test"))
+            .await
+            .unwrap();
+        memdb
+            .save_user(User::new("test2@mail.com", "test2"))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_role("admin@mail.com", Role::Admin)
+            .schema();
+        let query = || {
+            let body = serde_json::to_value(Query::build_query(query::Variables {})).unwrap();
+            serde_json::from_value::<Request>(body).unwrap()
+        };
+
+        let response = schema.execute(query()).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-03");
+
+        let response = schema
+            .execute(query().data(User::new("test@mail.com", "test")))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        // configured roles go with the email, not with whoever signed up under the name
+        let response = schema
+            .execute(query().data(User::new("impostor@mail.com", "admin")))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema
+            .execute(query().data(User::new("Admin@Mail.com", "other")))
+            .await;
+
+        assert!(response.errors.is_empty());
+
+        let response = schema
+            .execute(query().data(User::new("admin@mail.com", "admin")))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["users"],
+            json!([
+                { "email": "test@mail.com", "name": "test" },
+                { "email": "test2@mail.com", "name": "test2" },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_comment_email() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        memdb
+            .save_user(User::new("test@mail.com", "test"))
+            .await
+            .unwrap();
+        memdb
+            .save_comment(Comment::new(&thread.hash, "test@mail.com", 17, "content"))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_role("moderator@mail.com", Role::Moderator)
+            .schema();
+        let comments = format!(
+            r#"{{ thread(hash: "{}") {{ comments {{ edges {{ node {{ email author }} }} }} }} }}"#,
+            thread.hash
+        );
+        let node = |request: Request| {
+            let schema = schema.clone();
+            async move {
+                schema.execute(request).await.data.into_json().unwrap()["thread"]["comments"]
+                    ["edges"][0]["node"]
+                    .clone()
+            }
+        };
+
+        assert_eq!(
+            node(Request::new(comments.clone())).await,
+            json!({ "email": null, "author": "test" })
+        );
+        assert_eq!(
+            node(Request::new(comments.clone()).data(User::new("other@mail.com", "other"))).await,
+            json!({ "email": null, "author": "test" })
+        );
+        assert_eq!(
+            node(signed_in(comments.clone())).await,
+            json!({ "email": "test@mail.com", "author": "test" })
+        );
+        assert_eq!(
+            node(Request::new(comments).data(User::new("moderator@mail.com", "moderator"))).await,
+            json!({ "email": "test@mail.com", "author": "test" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_and_delete_comment() {
+        let schema = GraphQLFrontend::new(Arc::new(MemDB::default()))
+            .with_role("admin@mail.com", Role::Admin)
+            .schema();
+        let admin = |query: String| Request::new(query).data(User::new("admin@mail.com", "admin"));
+        let create_thread = r#"mutation { createThread(name: "thread") { hash name } }"#;
+
+        let response = schema.execute(create_thread).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-03");
+
+        let response = schema.execute(signed_in(create_thread)).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema.execute(admin(create_thread.to_string())).await;
+        let json = response.data.into_json().unwrap();
+        let thread_hash = json["createThread"]["hash"].as_str().unwrap().to_string();
+
+        assert_eq!(&json["createThread"]["name"], "thread");
+
+        let response = schema
+            .execute(signed_in(format!(
+                r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ hash threadHash email content }} }}"#,
+                thread_hash
+            )))
+            .await;
+        let json = response.data.into_json().unwrap();
+        let comment_hash = json["postComment"]["hash"].as_str().unwrap().to_string();
+
+        assert_eq!(&json["postComment"]["threadHash"], thread_hash.as_str());
+        assert_eq!(&json["postComment"]["email"], "test@mail.com");
+        assert_eq!(&json["postComment"]["content"], "content");
+
+        let delete_comment = format!(
+            r#"mutation {{ deleteComment(hash: "{}") {{ hash }} }}"#,
+            comment_hash
+        );
+        let json = schema
+            .execute(signed_in(delete_comment.as_str()))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(&json["deleteComment"]["hash"], comment_hash.as_str());
+
+        let json = schema
+            .execute(signed_in(delete_comment.as_str()))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(json["deleteComment"], Value::Null);
+
+        let json = schema
+            .execute(admin(format!(
+                r#"mutation {{ deleteThread(hash: "{}") {{ name }} }}"#,
+                thread_hash
+            )))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(&json["deleteThread"]["name"], "thread");
+    }
+
+    #[tokio::test]
+    async fn test_post_comment_validation_error() {
+        let schema = GraphQLFrontend::new(Arc::new(MemDB::default())).schema();
+
+        let response = schema
+            .execute(signed_in(
+                r#"mutation { postComment(threadHash: "hash", content: "") { hash } }"#,
+            ))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-00-01");
+        assert_eq!(error["extensions"]["fields"]["content"], json!(["regex"]));
+        assert_eq!(
+            error["extensions"]["fields"]["thread_hash"],
+            json!(["regex"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_comment_thread_not_exists() {
+        let schema = GraphQLFrontend::new(Arc::new(MemDB::default())).schema();
+        let thread = Thread::new("thread");
+
+        let response = schema
+            .execute(signed_in(format!(
+                r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ hash }} }}"#,
+                thread.hash
+            )))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-01-02");
+    }
+
+    #[tokio::test]
+    async fn test_post_replies() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(&thread.hash, "test@mail.com", 17, "comment"))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb).schema();
+        let post_reply = |parent_hash: &str, content: &str| {
+            signed_in(format!(
+                r#"mutation {{ postComment(threadHash: "{}", parentHash: "{}", content: "{}") {{ hash parentHash }} }}"#,
+                thread.hash, parent_hash, content
+            ))
+        };
+
+        let response = schema.execute(post_reply(&comment.hash, "reply")).await;
+        let reply = response.data.into_json().unwrap()["postComment"].clone();
+
+        assert_eq!(reply["parentHash"], json!(comment.hash));
+
+        schema
+            .execute(post_reply(reply["hash"].as_str().unwrap(), "nested"))
+            .await;
+        let response = schema
+            .execute(format!(
+                r#"{{ thread(hash: "{}") {{ comments {{ edges {{ node {{ content parentHash replies {{ content replies {{ content }} }} }} }} }} }} }}"#,
+                thread.hash
+            ))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["thread"]["comments"]["edges"][0]["node"],
+            json!({
+                "content": "comment",
+                "parentHash": null,
+                "replies": [{ "content": "reply", "replies": [{ "content": "nested" }] }]
+            })
+        );
+
+        let response = schema
+            .execute(post_reply(&Thread::new("missing").hash, "reply"))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-01-04");
+    }
+
+    #[tokio::test]
+    async fn test_edit_comment() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(
+                &thread.hash,
+                "test@mail.com",
+                crate::now(),
+                "content",
+            ))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_edit_window(Duration::from_secs(60))
+            .schema();
+        let edit = format!(
+            r#"mutation {{ editComment(hash: "{}", content: "edited") {{ hash content editedAt revisions {{ content date }} }} }}"#,
+            comment.hash
+        );
+
+        let response = schema.execute(signed_in(edit.clone())).await;
+        let edited = response.data.into_json().unwrap()["editComment"].clone();
+
+        assert_eq!(edited["hash"], json!(comment.hash));
+        assert_eq!(edited["content"], "edited");
+        assert!(edited["editedAt"].is_string());
+        assert_eq!(
+            edited["revisions"],
+            json!([{ "content": "content", "date": comment.date.to_string() }])
+        );
+
+        let response = schema
+            .execute(Request::new(edit.clone()).data(User::new("other@mail.com", "other")))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema.execute(edit).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-03");
+
+        let response = schema
+            .execute(signed_in(
+                r#"mutation { editComment(hash: "unknown", content: "edited") { hash } }"#,
+            ))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "editComment": null })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edit_comment_window_closed() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(&thread.hash, "test@mail.com", 17, "content"))
+            .await
+            .unwrap();
+        let edit = format!(
+            r#"mutation {{ editComment(hash: "{}", content: "edited") {{ content }} }}"#,
+            comment.hash
+        );
+
+        let response = GraphQLFrontend::new(memdb.clone())
+            .with_edit_window(Duration::from_secs(60))
+            .schema()
+            .execute(signed_in(edit.clone()))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-04-01");
+
+        // without a window comments stay editable
+        let response = GraphQLFrontend::new(memdb)
+            .schema()
+            .execute(signed_in(edit))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "editComment": { "content": "edited" } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_restore_comment() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(&thread.hash, "test@mail.com", 17, "content"))
+            .await
+            .unwrap();
+        let reply = memdb
+            .save_comment(Comment::new_reply(
+                &thread.hash,
+                &comment.hash,
+                "other@mail.com",
+                18,
+                "reply",
+            ))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb.clone())
+            .with_role("admin@mail.com", Role::Admin)
+            .schema();
+        let admin = |query: String| Request::new(query).data(User::new("admin@mail.com", "admin"));
+        let delete = |hash: &str| {
+            format!(
+                r#"mutation {{ deleteComment(hash: "{}", reason: "oops") {{ content email deleted }} }}"#,
+                hash
+            )
+        };
+        let restore = format!(
+            r#"mutation {{ restoreComment(hash: "{}") {{ content deleted }} }}"#,
+            comment.hash
+        );
+        let comments = format!(
+            r#"{{ thread(hash: "{}") {{ comments {{ edges {{ node {{ content deleted deletion {{ deletedBy reason }} }} }} }} }} }}"#,
+            thread.hash
+        );
+
+        let response = schema
+            .execute(Request::new(delete(&comment.hash)).data(User::new("other@mail.com", "other")))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema.execute(signed_in(delete(&comment.hash))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "deleteComment": { "content": "[deleted]", "email": null, "deleted": true } })
+        );
+
+        let response = schema.execute(signed_in(comments.clone())).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["thread"]["comments"]["edges"],
+            json!([
+                { "node": { "content": "[deleted]", "deleted": true, "deletion": null } },
+                { "node": { "content": "reply", "deleted": false, "deletion": null } }
+            ])
+        );
+
+        let response = schema.execute(admin(comments)).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["thread"]["comments"]["edges"][0]["node"]
+                ["deletion"],
+            json!({ "deletedBy": "test", "reason": "oops" })
+        );
+
+        let response = schema.execute(signed_in(restore.clone())).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema.execute(admin(restore)).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "restoreComment": { "content": "content", "deleted": false } })
+        );
+
+        // replies without replies of their own are deleted outright
+        schema.execute(admin(delete(&reply.hash))).await;
+
+        assert_eq!(memdb.find_comment(&reply.hash).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_moderation_queue() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb.clone())
+            .with_role("admin@mail.com", Role::Admin)
+            .with_approval_required()
+            .schema();
+        let admin = |query: String| Request::new(query).data(User::new("admin@mail.com", "admin"));
+        let post = |content: &str| {
+            format!(
+                r#"mutation {{ postComment(threadHash: "{}", content: "{}") {{ hash status }} }}"#,
+                thread.hash, content
+            )
+        };
+        let comments = format!(
+            r#"{{ thread(hash: "{}") {{ comments {{ totalCount edges {{ node {{ content }} }} }} }} }}"#,
+            thread.hash
+        );
+        let queue = |status: &str| {
+            format!(
+                r#"{{ moderationQueue(status: {}) {{ totalCount edges {{ node {{ content status }} }} }} }}"#,
+                status
+            )
+        };
+
+        let response = schema.execute(signed_in(post("first"))).await;
+        let json = response.data.into_json().unwrap();
+        let first = json["postComment"]["hash"].as_str().unwrap().to_string();
+
+        assert_eq!(&json["postComment"]["status"], "PENDING");
+
+        let response = schema.execute(signed_in(post("second"))).await;
+        let second = response.data.into_json().unwrap()["postComment"]["hash"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let response = schema.execute(signed_in(comments.clone())).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["thread"]["comments"]["totalCount"],
+            0
+        );
+
+        let response = schema.execute(signed_in(queue("PENDING"))).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema.execute(admin(queue("PENDING"))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["moderationQueue"],
+            json!({ "totalCount": 2, "edges": [
+                { "node": { "content": "first", "status": "PENDING" } },
+                { "node": { "content": "second", "status": "PENDING" } }
+            ] })
+        );
+
+        let response = schema
+            .execute(admin(format!(
+                r#"mutation {{ approveComment(hash: "{}") {{ status }} }}"#,
+                first
+            )))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "approveComment": { "status": "APPROVED" } })
+        );
+
+        schema
+            .execute(admin(format!(
+                r#"mutation {{ rejectComment(hash: "{}", spam: true) {{ status }} }}"#,
+                second
+            )))
+            .await;
+
+        let response = schema.execute(signed_in(comments.clone())).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["thread"]["comments"],
+            json!({ "totalCount": 1, "edges": [{ "node": { "content": "first" } }] })
+        );
+
+        let response = schema.execute(admin(queue("SPAM"))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["moderationQueue"]["edges"],
+            json!([{ "node": { "content": "second", "status": "SPAM" } }])
+        );
+
+        // admins are trusted, and threads may opt out of the global policy
+        let response = schema.execute(admin(post("admin"))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["postComment"]["status"],
+            "APPROVED"
+        );
+
+        let response = schema
+            .execute(admin(format!(
+                r#"mutation {{ updateThread(hash: "{}", requireApproval: false) {{ requireApproval }} }}"#,
+                thread.hash
+            )))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "updateThread": { "requireApproval": false } })
+        );
+
+        let response = schema.execute(signed_in(post("third"))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["postComment"]["status"],
+            "APPROVED"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_moderation_subscriptions() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb.clone()).schema();
+
+        let mut added = schema.execute_stream(format!(
+            r#"subscription {{ commentAdded(threadHash: "{}") {{ hash status }} }}"#,
+            thread.hash
+        ));
+        let mut deleted = schema.execute_stream(format!(
+            r#"subscription {{ commentDeleted(threadHash: "{}") {{ hash status }} }}"#,
+            thread.hash
+        ));
+
+        select! {
+            _ = added.next() => panic!("Subscription yielded before a comment was added"),
+            _ = deleted.next() => panic!("Subscription yielded before a comment was deleted"),
+            _ = sleep(Duration::from_millis(10)) => {}
+        }
+
+        let comment = memdb
+            .save_comment(
+                Comment::new(&thread.hash, "test@mail.com", 1, "content")
+                    .with_status(CommentStatus::Pending),
+            )
+            .await
+            .unwrap();
+        memdb
+            .set_comment_status(&comment.hash, CommentStatus::Approved)
+            .await
+            .unwrap();
+        memdb
+            .set_comment_status(&comment.hash, CommentStatus::Rejected)
+            .await
+            .unwrap();
+
+        // the pending comment only shows up once approved
+        assert_eq!(
+            added.next().await.unwrap().data.into_json().unwrap(),
+            json!({ "commentAdded": { "hash": comment.hash, "status": "APPROVED" } })
+        );
+        assert_eq!(
+            deleted.next().await.unwrap().data.into_json().unwrap(),
+            json!({ "commentDeleted": { "hash": comment.hash, "status": "REJECTED" } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_skip_hidden_comments() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb.clone()).schema();
+
+        let mut edited = schema.execute_stream(format!(
+            r#"subscription {{ commentEdited(threadHash: "{}") {{ hash content }} }}"#,
+            thread.hash
+        ));
+        let mut deleted = schema.execute_stream(format!(
+            r#"subscription {{ commentDeleted(threadHash: "{}") {{ hash }} }}"#,
+            thread.hash
+        ));
+
+        select! {
+            _ = edited.next() => panic!("Subscription yielded before a comment was edited"),
+            _ = deleted.next() => panic!("Subscription yielded before a comment was deleted"),
+            _ = sleep(Duration::from_millis(10)) => {}
+        }
+
+        let pending = memdb
+            .save_comment(
+                Comment::new(&thread.hash, "test@mail.com", 1, "pending")
+                    .with_status(CommentStatus::Pending),
+            )
+            .await
+            .unwrap();
+        let shadowed = memdb
+            .save_comment(Comment {
+                shadowed: true,
+                ..Comment::new(&thread.hash, "test@mail.com", 2, "shadowed")
+            })
+            .await
+            .unwrap();
+        let public = memdb
+            .save_comment(Comment::new(&thread.hash, "test@mail.com", 3, "public"))
+            .await
+            .unwrap();
+
+        for comment in [&pending, &shadowed] {
+            memdb
+                .edit_comment(comment.try_edit("edited secret", 4).unwrap())
+                .await
+                .unwrap();
+        }
+        memdb
+            .set_comment_status(&pending.hash, CommentStatus::Rejected)
+            .await
+            .unwrap();
+        memdb
+            .set_comment_status(&shadowed.hash, CommentStatus::Spam)
+            .await
+            .unwrap();
+        memdb.delete_comment(&pending.hash).await.unwrap();
+        memdb.delete_comment(&shadowed.hash).await.unwrap();
+        memdb
+            .edit_comment(public.try_edit("edited", 5).unwrap())
+            .await
+            .unwrap();
+        memdb.delete_comment(&public.hash).await.unwrap();
+
+        // only the public comment comes through, the others would leak their content
+        assert_eq!(
+            edited.next().await.unwrap().data.into_json().unwrap(),
+            json!({ "commentEdited": { "hash": public.hash, "content": "edited" } })
+        );
+        assert_eq!(
+            deleted.next().await.unwrap().data.into_json().unwrap(),
+            json!({ "commentDeleted": { "hash": public.hash } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_content_counts_saved_comments() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_spam_filter(Arc::new(DuplicateContent::new(10)))
+            .schema();
+        let post = |parent_hash: &str| {
+            format!(
+                r#"mutation {{ postComment(threadHash: "{}", parentHash: {}, content: "A long enough comment") {{ status }} }}"#,
+                thread.hash, parent_hash
+            )
+        };
+
+        // the reply to a missing comment isn't saved, so it can be posted again
+        let response = schema.execute(signed_in(post(r#""missing""#))).await;
+
+        assert_eq!(response.errors.len(), 1);
+
+        let response = schema.execute(signed_in(post("null"))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "postComment": { "status": "APPROVED" } })
+        );
+
+        let response = schema.execute(signed_in(post("null"))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "postComment": { "status": "SPAM" } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_comment_spam_filters() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let classifier = Arc::new(NaiveBayes::default());
+        let schema = GraphQLFrontend::new(memdb.clone())
+            .with_role("admin@mail.com", Role::Admin)
+            .with_spam_filter(Arc::new(LinkLimit::new(0)))
+            .with_spam_filter(Arc::new(
+                Blocklist::new(&["casino"], &[] as &[&str]).unwrap(),
+            ))
+            .with_spam_filter(classifier.clone())
+            .schema();
+        let admin = |query: String| Request::new(query).data(User::new("admin@mail.com", "admin"));
+        let post = |content: &str| {
+            format!(
+                r#"mutation {{ postComment(threadHash: "{}", content: "{}") {{ hash status }} }}"#,
+                thread.hash, content
+            )
+        };
+        let status = |response: async_graphql::Response| {
+            response.data.into_json().unwrap()["postComment"]["status"].clone()
+        };
+
+        assert_eq!(
+            status(schema.execute(signed_in(post("hello"))).await),
+            "APPROVED"
+        );
+        assert_eq!(
+            status(schema.execute(signed_in(post("see www.example.com"))).await),
+            "PENDING"
+        );
+        assert_eq!(
+            status(schema.execute(signed_in(post("best casino"))).await),
+            "SPAM"
+        );
+        assert_eq!(
+            status(schema.execute(admin(post("admin casino"))).await),
+            "APPROVED"
+        );
+
+        // moderation decisions train the classifier
+        let response = schema.execute(signed_in(post("cheap pills"))).await;
+        let spam = response.data.into_json().unwrap()["postComment"]["hash"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let response = schema.execute(signed_in(post("nice article"))).await;
+        let ham = response.data.into_json().unwrap()["postComment"]["hash"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        assert_eq!(classifier.spam_probability("cheap pills"), None);
+
+        schema
+            .execute(admin(format!(
+                r#"mutation {{ rejectComment(hash: "{}", spam: true) {{ status }} }}"#,
+                spam
+            )))
+            .await;
+        schema
+            .execute(admin(format!(
+                r#"mutation {{ approveComment(hash: "{}") {{ status }} }}"#,
+                ham
+            )))
+            .await;
+
+        assert!(classifier.spam_probability("cheap pills").unwrap() > 0.5);
+        assert!(classifier.spam_probability("nice article").unwrap() < 0.5);
+    }
+
+    /// Records the request of every comment checked.
+    #[derive(Default)]
+    struct RecordingFilter {
+        requests: std::sync::Mutex<Vec<RequestInfo>>,
+    }
+
+    impl SpamFilter for RecordingFilter {
+        fn check(&self, _comment: &Comment, request: &RequestInfo) -> SpamResult<Verdict> {
+            self.requests.lock().unwrap().push(request.clone());
+
+            Box::pin(async { Ok(Verdict::Approve) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_info_over_http() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let filter = Arc::new(RecordingFilter::default());
+        let frontend = GraphQLFrontend::new(memdb.clone())
+            .with_session_secret(b"secret")
+            .with_spam_filter(filter.clone());
+        let token = Sessions::new(b"secret").issue(
+            &memdb
+                .save_user(User::new("test@mail.com", "test"))
+                .await
+                .unwrap(),
+        );
+        let body = json!({
+            "query": format!(r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ hash }} }}"#, thread.hash)
+        });
+
+        for frontend in [frontend.clone(), frontend.with_trusted_proxies(1)] {
+            let mut listener = frontend.app().bind("127.0.0.1:0").await.unwrap();
+            let url = listener.info()[0].connection().to_string();
+            tokio::spawn(async move { listener.accept().await });
+
+            reqwest::Client::new()
+                .post(&url)
+                .json(&body)
+                .bearer_auth(&token)
+                .header("User-Agent", "test-agent")
+                .header("Referer", "https://example.com/post")
+                // the client forged the first entry, the proxy appended the second
+                .header("X-Forwarded-For", "198.51.100.1, 203.0.113.7")
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let requests = filter.requests.lock().unwrap();
+
+        assert_eq!(
+            requests[0],
+            RequestInfo {
+                ip: Some("127.0.0.1".parse().unwrap()),
+                user_agent: Some("test-agent".to_string()),
+                referrer: Some("https://example.com/post".to_string()),
+            }
+        );
+        assert_eq!(requests[1].ip, Some("203.0.113.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_ip() {
+        assert_eq!(
+            parse_ip("127.0.0.1:8000"),
+            Some("127.0.0.1".parse().unwrap())
+        );
+        assert_eq!(parse_ip("\"[::1]:8000\""), Some("::1".parse().unwrap()));
+        assert_eq!(parse_ip("[::1]"), Some("::1".parse().unwrap()));
+        assert_eq!(parse_ip("unknown"), None);
+    }
+
+    #[test]
+    fn test_forwarded_ip() {
+        let ip = |ip: &str| Some(ip.parse().unwrap());
+        let x_forwarded_for = ["198.51.100.1, 203.0.113.7", "192.0.2.1"];
+
+        assert_eq!(forwarded_ip(&[], &x_forwarded_for, 1), ip("192.0.2.1"));
+        assert_eq!(forwarded_ip(&[], &x_forwarded_for, 2), ip("203.0.113.7"));
+        assert_eq!(forwarded_ip(&[], &x_forwarded_for, 5), ip("198.51.100.1"));
+        assert_eq!(forwarded_ip(&[], &[], 1), None);
+
+        // Forwarded takes precedence
+        let forwarded = [r#"for=198.51.100.1;proto=https, For="[2001:db8::1]:4711""#];
+
+        assert_eq!(
+            forwarded_ip(&forwarded, &x_forwarded_for, 1),
+            ip("2001:db8::1")
+        );
+        assert_eq!(forwarded_ip(&["for=unknown"], &[], 1), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_rate_limit(Operation::Post, RateLimit::new(1, Duration::from_secs(60)))
+            .with_rate_limit(Operation::Query, RateLimit::new(2, Duration::from_secs(60)))
+            .with_rate_limit(
+                Operation::Report,
+                RateLimit::new(1, Duration::from_secs(60)),
+            )
+            .schema();
+        let post = format!(
+            r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ hash }} }}"#,
+            thread.hash
+        );
+        let report = format!(
+            r#"mutation {{ reportComment(hash: "{}", reason: "spam") {{ hash }} }}"#,
+            thread.hash
+        );
+
+        assert!(schema.execute(signed_in(report.clone())).await.is_ok());
+
+        let response = schema.execute(signed_in(report)).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-05-01");
+
+        assert!(schema.execute(signed_in(post.clone())).await.is_ok());
+
+        let response = schema.execute(signed_in(post)).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-05-01");
+        assert_eq!(&error["extensions"]["retryAfter"], 60);
+        assert_eq!(
+            response.http_headers.get("Retry-After").unwrap(),
+            &"60".to_string()
+        );
+
+        // mutations don't count as queries, other users have their own limits
+        assert!(schema.execute(signed_in("{ me { name } }")).await.is_ok());
+        assert!(schema.execute(signed_in("{ me { name } }")).await.is_ok());
+
+        let response = schema.execute(signed_in("{ me { name } }")).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-05-01");
+        assert!(schema
+            .execute(Request::new("{ me { name } }").data(User::new("other@mail.com", "other")))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_guest_comments() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        memdb
+            .save_user(User::new("test@mail.com", "test"))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb).with_guest_comments(8).schema();
+        let post = |email: &str, proof: Option<(&str, u64)>| {
+            let proof = proof
+                .map(|(challenge, nonce)| {
+                    format!(
+                        r#", proof: {{ challenge: "{}", nonce: "{}" }}"#,
+                        challenge, nonce
+                    )
+                })
+                .unwrap_or_default();
+
+            format!(
+                r#"mutation {{ postComment(threadHash: "{}", content: "content", guestEmail: "{}"{}) {{ email author }} }}"#,
+                thread.hash, email, proof
+            )
+        };
+        let error_code = |response: async_graphql::Response| {
+            serde_json::to_value(&response.errors[0]).unwrap()["extensions"]["code"].clone()
+        };
+
+        let response = schema
+            .execute("{ challenge { challenge difficulty } }")
+            .await;
+        let json = response.data.into_json().unwrap();
+        let challenge = json["challenge"]["challenge"].as_str().unwrap().to_string();
+        let nonce = pow::solve(&challenge, 8);
+        let wrong = (0..)
+            .find(|nonce| !pow::verify(&challenge, 8, *nonce))
+            .unwrap();
+
+        assert_eq!(json["challenge"]["difficulty"], 8);
+        assert_eq!(
+            error_code(schema.execute(post("guest@mail.com", None)).await),
+            "E-03-05"
+        );
+        assert_eq!(
+            error_code(
+                schema
+                    .execute(post("guest@mail.com", Some((&challenge, wrong))))
+                    .await
+            ),
+            "E-03-05"
+        );
+        assert_eq!(
+            error_code(
+                schema
+                    .execute(post("test@mail.com", Some((&challenge, nonce))))
+                    .await
+            ),
+            "E-03-04"
+        );
+        // posts failing for other reasons don't use up the challenge
+        assert_eq!(
+            error_code(
+                schema
+                    .execute(
+                        post("guest@mail.com", Some((&challenge, nonce)))
+                            .replace(&thread.hash, &"f".repeat(64))
+                    )
+                    .await
+            ),
+            "E-01-02"
+        );
+
+        let response = schema
+            .execute(post("guest@mail.com", Some((&challenge, nonce))))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "postComment": { "email": null, "author": null } })
+        );
+        // every challenge is good for one comment
+        assert_eq!(
+            error_code(
+                schema
+                    .execute(post("guest@mail.com", Some((&challenge, nonce))))
+                    .await
+            ),
+            "E-03-05"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bans() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_role("admin@mail.com", Role::Admin)
+            .schema();
+        let admin = |query: String| Request::new(query).data(User::new("admin@mail.com", "admin"));
+        let troll = |query: String| {
+            Request::new(query)
+                .data(User::new("troll@mail.com", "troll"))
+                .data(RequestInfo {
+                    ip: Some("192.0.2.17".parse().unwrap()),
+                    ..RequestInfo::default()
+                })
+        };
+        let ban = |kind: &str, value: &str, shadow: bool| {
+            format!(
+                r#"mutation {{ ban(kind: {}, value: "{}", shadow: {}, duration: 60) {{ id expiresAt }} }}"#,
+                kind, value, shadow
+            )
+        };
+        let post = format!(
+            r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ hash }} }}"#,
+            thread.hash
+        );
+        let comments = format!(
+            r#"{{ thread(hash: "{}") {{ comments {{ totalCount edges {{ node {{ email shadowed }} }} }} }} }}"#,
+            thread.hash
+        );
+
+        let response = schema.execute(signed_in(ban("USER", "troll", true))).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema.execute(admin(ban("USER", "troll", true))).await;
+        let shadowban = response.data.into_json().unwrap()["ban"]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        assert!(schema.execute(troll(post.clone())).await.is_ok());
+        assert!(schema.execute(signed_in(post.clone())).await.is_ok());
+
+        let json = schema
+            .execute(signed_in(comments.clone()))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(
+            json["thread"]["comments"],
+            json!({ "totalCount": 1, "edges": [{ "node": { "email": "test@mail.com", "shadowed": null } }] })
+        );
+
+        let json = schema
+            .execute(troll(comments.clone()))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(json["thread"]["comments"]["totalCount"], 2);
+
+        let json = schema
+            .execute(admin(comments.clone()))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(json["thread"]["comments"]["totalCount"], 1);
+        assert_eq!(
+            json["thread"]["comments"]["edges"][0]["node"]["shadowed"],
+            false
+        );
+
+        // a ban of the address keeps the user from posting at all
+        let response = schema
+            .execute(admin(ban("IP_RANGE", "192.0.2.0/24", false)))
+            .await;
+        let expires_at = response.data.into_json().unwrap()["ban"]["expiresAt"].clone();
+        let response = schema.execute(troll(post.clone())).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-06");
+        assert_eq!(error["extensions"]["bannedUntil"], expires_at);
+        assert!(schema.execute(signed_in(post.clone())).await.is_ok());
+
+        let response = schema
+            .execute(admin(ban("IP_RANGE", "example.com", false)))
+            .await;
+
+        assert_eq!(response.errors.len(), 1);
+
+        let response = schema
+            .execute(admin("{ bans { kind value shadow bannedBy } }".to_string()))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["bans"],
+            json!([
+                { "kind": "USER", "value": "troll", "shadow": true, "bannedBy": "admin" },
+                { "kind": "IP_RANGE", "value": "192.0.2.0/24", "shadow": false, "bannedBy": "admin" },
+            ])
+        );
+
+        let response = schema
+            .execute(admin(format!(
+                r#"mutation {{ unban(id: "{}") {{ kind }} }}"#,
+                shadowban
+            )))
+            .await;
+
+        assert_eq!(response.data.into_json().unwrap()["unban"]["kind"], "USER");
+    }
+
+    #[tokio::test]
+    async fn test_report_comment() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(&thread.hash, "author@mail.com", 17, "content"))
+            .await
+            .unwrap();
+        let other = memdb
+            .save_comment(Comment::new(&thread.hash, "author@mail.com", 18, "other"))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_role("admin@mail.com", Role::Admin)
+            .with_report_threshold(2)
+            .schema();
+        let admin = |query: &str| {
+            Request::new(query.to_string()).data(User::new("admin@mail.com", "admin"))
+        };
+        let report = |hash: &str, reason: &str| {
+            format!(
+                r#"mutation {{ reportComment(hash: "{}", reason: "{}") {{ status }} }}"#,
+                hash, reason
+            )
+        };
+        let reported = "{ reportedComments { totalCount edges { node { comment { content } \
+                        reportCount reports { reporter reason } } } } }";
+
+        let response = schema
+            .execute(Request::new(report(&comment.hash, "spam")))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-03");
+
+        let response = schema.execute(signed_in(report(&comment.hash, ""))).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-00-01");
+
+        // reporting twice still counts as one reporter
+        let response = schema
+            .execute(signed_in(report(&comment.hash, "spam")))
+            .await;
+        let response_2 = schema
+            .execute(signed_in(report(&comment.hash, "rude")))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["reportComment"]["status"],
+            "APPROVED"
+        );
+        assert_eq!(
+            response_2.data.into_json().unwrap()["reportComment"]["status"],
+            "APPROVED"
+        );
+        assert!(schema
+            .execute(signed_in(report(&other.hash, "spam")))
+            .await
+            .is_ok());
+
+        let response = schema.execute(admin(&report(&comment.hash, "spam"))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["reportComment"]["status"],
+            "PENDING"
+        );
+
+        let response = schema.execute(admin(reported)).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["reportedComments"],
+            json!({
+                "totalCount": 2,
+                "edges": [
+                    { "node": {
+                        "comment": { "content": "content" },
+                        "reportCount": 2,
+                        "reports": [
+                            { "reporter": "test", "reason": "rude" },
+                            { "reporter": "admin", "reason": "spam" },
+                        ],
+                    } },
+                    { "node": {
+                        "comment": { "content": "other" },
+                        "reportCount": 1,
+                        "reports": [{ "reporter": "test", "reason": "spam" }],
+                    } },
+                ],
+            })
+        );
+
+        let response = schema.execute(signed_in(reported)).await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+
+        let response = schema
+            .execute(signed_in(report(&thread.hash, "spam")))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["reportComment"],
+            json!(null)
+        );
+
+        // comments awaiting moderation can't be reported
+        let response = schema
+            .execute(signed_in(report(&comment.hash, "spam")))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["reportComment"],
+            json!(null)
+        );
+
+        // approving again dismisses the reports so far
+        let approve = format!(
+            r#"mutation {{ approveComment(hash: "{}") {{ status }} }}"#,
+            comment.hash
+        );
+        assert!(schema.execute(admin(&approve)).await.is_ok());
+
+        let response = schema
+            .execute(signed_in(report(&comment.hash, "spam")))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["reportComment"]["status"],
+            "APPROVED"
+        );
+
+        let response = schema.execute(admin(reported)).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["reportedComments"]["edges"][0]["node"]
+                ["reportCount"],
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_roles() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let other = memdb.save_thread(Thread::new("other")).await.unwrap();
+        let mut pending = vec![];
+        for thread in [&thread, &other] {
+            let comment = Comment::new(&thread.hash, "author@mail.com", 17, &thread.name)
+                .with_status(CommentStatus::Pending);
+            pending.push(memdb.save_comment(comment).await.unwrap());
+        }
+        for (email, name) in [
+            ("owner@mail.com", "owner"),
+            ("admin@mail.com", "admin"),
+            ("moderator@mail.com", "moderator"),
+        ] {
+            memdb.save_user(User::new(email, name)).await.unwrap();
+        }
+        let schema = GraphQLFrontend::new(memdb.clone())
+            .with_role("owner@mail.com", Role::Owner)
+            .schema();
+        let as_user = |name: &str, query: String| {
+            Request::new(query).data(User::new(&format!("{}@mail.com", name), name))
+        };
+        let code = |response: async_graphql::Response| {
+            serde_json::to_value(&response.errors[0]).unwrap()["extensions"]["code"].clone()
+        };
+        let grant = |user_name: &str, role: &str, thread_hash: Option<&str>| {
+            format!(
+                r#"mutation {{ grantRole(userName: "{}", role: {}, threadHash: {}) {{ userName role threadHash }} }}"#,
+                user_name,
+                role,
+                thread_hash.map_or("null".to_string(), |hash| format!(r#""{}""#, hash))
+            )
+        };
+        let approve = |hash: &str| {
+            format!(
+                r#"mutation {{ approveComment(hash: "{}") {{ status }} }}"#,
+                hash
+            )
+        };
+
+        let response = schema.execute(grant("admin", "ADMIN", None)).await;
+
+        assert_eq!(code(response), "E-03-03");
+
+        let response = schema
+            .execute(signed_in(grant("admin", "ADMIN", None)))
+            .await;
+
+        assert_eq!(code(response), "E-03-04");
+
+        let response = schema
+            .execute(as_user("owner", grant("admin", "ADMIN", None)))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["grantRole"],
+            json!({ "userName": "admin", "role": "ADMIN", "threadHash": null })
+        );
+
+        let response = schema
+            .execute(as_user(
+                "admin",
+                grant("moderator", "MODERATOR", Some(&thread.hash)),
+            ))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["grantRole"],
+            json!({ "userName": "moderator", "role": "MODERATOR", "threadHash": thread.hash })
+        );
+
+        // admins only grant roles below their own and can't demote each other
+        let response = schema
+            .execute(as_user(
+                "admin",
+                grant("moderator", "ADMIN", Some(&thread.hash)),
+            ))
+            .await;
+
+        assert_eq!(code(response), "E-03-04");
+
+        let response = schema
+            .execute(as_user("admin", grant("admin", "COMMENTER", None)))
+            .await;
+
+        assert_eq!(code(response), "E-03-04");
+
+        let response = schema
+            .execute(as_user("admin", grant("unknown", "MODERATOR", None)))
+            .await;
+
+        assert_eq!(response.data.into_json().unwrap()["grantRole"], json!(null));
+
+        // the moderator of a thread moderates that thread only
+        let response = schema
+            .execute(as_user("moderator", approve(&pending[0].hash)))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["approveComment"]["status"],
+            "APPROVED"
+        );
+
+        let response = schema
+            .execute(as_user("moderator", approve(&pending[1].hash)))
+            .await;
+
+        assert_eq!(code(response), "E-03-04");
+
+        let response = schema
+            .execute(as_user(
+                "moderator",
+                r#"mutation { ban(kind: USER, value: "troll") { id } }"#.to_string(),
+            ))
+            .await;
+
+        assert_eq!(code(response), "E-03-04");
+
+        let response = schema
+            .execute(as_user(
+                "admin",
+                "{ roles { userName role threadHash } }".to_string(),
+            ))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["roles"],
+            json!([
+                { "userName": "admin", "role": "ADMIN", "threadHash": null },
+                { "userName": "moderator", "role": "MODERATOR", "threadHash": thread.hash },
+            ])
+        );
+
+        let revoke = format!(
+            r#"mutation {{ revokeRole(userName: "moderator", threadHash: "{}") {{ role }} }}"#,
+            thread.hash
+        );
+        let response = schema.execute(as_user("admin", revoke.clone())).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["revokeRole"],
+            json!({ "role": "MODERATOR" })
+        );
+        assert_eq!(memdb.find_roles("moderator").await, Ok(vec![]));
+
+        let response = schema.execute(as_user("admin", revoke)).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["revokeRole"],
+            json!(null)
+        );
+
+        let response = schema
+            .execute(as_user("moderator", approve(&pending[0].hash)))
+            .await;
+
+        assert_eq!(code(response), "E-03-04");
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(&thread.hash, "author@mail.com", 17, "content"))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_role("admin@mail.com", Role::Admin)
+            .schema();
+        let admin = |query: String| Request::new(query).data(User::new("admin@mail.com", "admin"));
+
+        for mutation in [
+            format!(
+                r#"mutation {{ rejectComment(hash: "{}", spam: true) {{ hash }} }}"#,
+                comment.hash
+            ),
+            format!(
+                r#"mutation {{ deleteComment(hash: "{}", reason: "off topic") {{ hash }} }}"#,
+                comment.hash
+            ),
+            r#"mutation { ban(kind: USER, value: "troll", reason: "trolling") { id } }"#
+                .to_string(),
+            format!(
+                r#"mutation {{ lockThread(hash: "{}", locked: true, reason: "flame war") {{ hash }} }}"#,
+                thread.hash
+            ),
+            format!(
+                r#"mutation {{ updateThread(hash: "{}", requireApproval: true) {{ hash }} }}"#,
+                thread.hash
+            ),
+            // acting on nothing isn't recorded
+            format!(
+                r#"mutation {{ restoreComment(hash: "{}") {{ hash }} }}"#,
+                thread.hash
+            ),
+        ] {
+            let response = schema.execute(admin(mutation)).await;
+
+            assert!(response.is_ok(), "{:?}", response.errors);
+        }
+
+        let response = schema
+            .execute(admin(
+                "{ auditLog(first: 3) { totalCount pageInfo { hasNextPage } edges { node { \
+                 action actor target reason } } } }"
+                    .to_string(),
+            ))
+            .await;
+        let json = response.data.into_json().unwrap();
+
+        assert_eq!(json["auditLog"]["totalCount"], 5);
+        assert_eq!(json["auditLog"]["pageInfo"]["hasNextPage"], true);
+        assert_eq!(
+            json["auditLog"]["edges"][0]["node"],
+            json!({
+                "action": "UPDATE_THREAD",
+                "actor": "admin",
+                "target": thread.hash,
+                "reason": "require approval: true",
+            })
+        );
+        assert_eq!(
+            json["auditLog"]["edges"][1]["node"]["action"],
+            "LOCK_THREAD"
+        );
+        assert_eq!(json["auditLog"]["edges"][1]["node"]["reason"], "flame war");
+        assert_eq!(json["auditLog"]["edges"][2]["node"]["action"], "BAN");
+        assert_eq!(json["auditLog"]["edges"][2]["node"]["reason"], "trolling");
+
+        let response = schema
+            .execute(admin(format!(
+                r#"{{ auditLog(target: "{}") {{ edges {{ node {{ action reason }} }} }} }}"#,
+                comment.hash
+            )))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["auditLog"]["edges"],
+            json!([
+                { "node": { "action": "DELETE_COMMENT", "reason": "off topic" } },
+                { "node": { "action": "MARK_SPAM", "reason": null } },
+            ])
+        );
+
+        let response = schema
+            .execute(signed_in("{ auditLog { totalCount } }"))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-04");
+    }
+
+    /// Fails to record anything.
+    struct FailingAuditStore;
+
+    impl AuditStore for FailingAuditStore {
+        fn append_audit_entry(&self, _entry: AuditEntry) -> StoreResult<AuditEntry> {
+            Box::pin(async { Err(StoreError::DatabaseError("unavailable".to_string())) })
+        }
+
+        fn count_audit_entries(&self, _target: Option<&str>) -> StoreResult<usize> {
+            Box::pin(async { Ok(0) })
+        }
+
+        fn find_audit_entries(
+            &self,
+            _target: Option<&str>,
+            _offset: usize,
+            _limit: usize,
+        ) -> StoreResult<Vec<AuditEntry>> {
+            Box::pin(async { Ok(vec![]) })
+        }
+    }
+
+    /// Fails to save bans.
+    struct FailingBanStore;
+
+    impl BanStore for FailingBanStore {
+        fn save_ban(&self, _ban: Ban) -> StoreResult<Ban> {
+            Box::pin(async { Err(StoreError::DatabaseError("unavailable".to_string())) })
+        }
+
+        fn delete_ban(&self, _id: &str) -> StoreResult<Option<Ban>> {
+            Box::pin(async { Ok(None) })
+        }
+
+        fn find_all_bans(&self) -> StoreResult<Vec<Ban>> {
+            Box::pin(async { Ok(vec![]) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unrecorded_actions_fail() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(&thread.hash, "author@mail.com", 17, "content"))
+            .await
+            .unwrap();
+        let schema = GraphQLFrontend {
+            audit_store: Arc::new(FailingAuditStore),
+            ..GraphQLFrontend::new(memdb.clone()).with_role("admin@mail.com", Role::Admin)
+        }
+        .schema();
+        let admin = |query: String| Request::new(query).data(User::new("admin@mail.com", "admin"));
+
+        for mutation in [
+            format!(
+                r#"mutation {{ deleteComment(hash: "{}") {{ hash }} }}"#,
+                comment.hash
+            ),
+            format!(
+                r#"mutation {{ lockThread(hash: "{}", locked: true) {{ hash }} }}"#,
+                thread.hash
+            ),
+            r#"mutation { ban(kind: USER, value: "troll") { id } }"#.to_string(),
+        ] {
+            let response = schema.execute(admin(mutation)).await;
+            let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+            assert_eq!(&error["extensions"]["code"], "E-01-03");
+        }
+
+        // the actions are taken before they are recorded
+        assert_ne!(memdb.find_comment(&comment.hash).await, Ok(Some(comment)));
+        assert!(
+            memdb
+                .find_thread_by_hash(&thread.hash)
+                .await
+                .unwrap()
+                .unwrap()
+                .locked
+        );
+        assert_eq!(memdb.find_all_bans().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_actions_are_recorded() {
+        let memdb = Arc::new(MemDB::default());
+        let schema = GraphQLFrontend {
+            ban_store: Arc::new(FailingBanStore),
+            ..GraphQLFrontend::new(memdb.clone()).with_role("admin@mail.com", Role::Admin)
+        }
+        .schema();
+        let admin = |query: &str| {
+            Request::new(query.to_string()).data(User::new("admin@mail.com", "admin"))
+        };
+
+        let response = schema
+            .execute(admin(
+                r#"mutation { ban(kind: USER, value: "troll", reason: "spam") { id } }"#,
+            ))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-01-03");
+
+        let entries = memdb.find_audit_entries(None, 0, 10).await.unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, AuditAction::Failed);
+        assert_eq!(entries[0].actor, "admin");
+        assert_eq!(
+            entries[0].reason.as_deref(),
+            Some("ban failed with E-01-03: spam")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lock_thread() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb)
+            .with_role("moderator@mail.com", Role::Moderator)
+            .schema();
+        let moderator =
+            |query: String| Request::new(query).data(User::new("moderator@mail.com", "moderator"));
+        let lock = |locked: bool| {
+            format!(
+                r#"mutation {{ lockThread(hash: "{}", locked: {}) {{ locked }} }}"#,
+                thread.hash, locked
+            )
+        };
+        let post = format!(
+            r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ hash }} }}"#,
+            thread.hash
+        );
+        let code = |response: async_graphql::Response| {
+            serde_json::to_value(&response.errors[0]).unwrap()["extensions"]["code"].clone()
+        };
+
+        assert_eq!(code(schema.execute(signed_in(lock(true))).await), "E-03-04");
+
+        let response = schema.execute(moderator(lock(true))).await;
+
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({ "lockThread": { "locked": true } })
+        );
+        assert_eq!(
+            code(schema.execute(signed_in(post.clone())).await),
+            "E-04-02"
+        );
+        assert!(schema.execute(moderator(post.clone())).await.is_ok());
+
+        assert!(schema.execute(moderator(lock(false))).await.is_ok());
+        assert!(schema.execute(signed_in(post)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_comment_unauthenticated() {
+        let memdb = MemDB::default();
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let schema = GraphQLFrontend::new(Arc::new(memdb)).schema();
+
+        let response = schema
+            .execute(format!(
+                r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ hash }} }}"#,
+                thread.hash
+            ))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-03");
+    }
+
+    #[tokio::test]
+    async fn test_session_over_http() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let frontend = GraphQLFrontend::new(memdb.clone())
+            .with_session_secret(b"secret")
+            .with_signup_provider(Arc::new(TestSignupProvider {
+                user_store: memdb.clone(),
+            }));
+        let mut listener = frontend.app().bind("127.0.0.1:0").await.unwrap();
+        let url = listener.info()[0].connection().to_string();
+        tokio::spawn(async move { listener.accept().await });
+
+        let client = reqwest::Client::new();
+        let post = |body: Value| client.post(&url).json(&body);
+
+        let response = post(json!({
+            "query": r#"mutation { confirm(provider: "test", token: "test@mail.com", userName: "test") { token user { name } } }"#
+        }))
+        .send()
+        .await
+        .unwrap();
+        let cookie = response.headers()["set-cookie"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let json: Value = response.json().await.unwrap();
+        let token = json["data"]["confirm"]["token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        assert_eq!(json["data"]["confirm"]["user"]["name"], "test");
+        assert!(cookie.starts_with(&format!("session={};", token)));
+        assert!(cookie.contains("HttpOnly"));
+
+        let me = json!({ "query": "{ me { name email } }" });
+        let json: Value = post(me.clone())
+            .header("Cookie", format!("session={}", token))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            json["data"]["me"],
+            json!({ "name": "test", "email": "test@mail.com" })
+        );
+
+        let json: Value = post(json!({
+            "query": format!(r#"mutation {{ postComment(threadHash: "{}", content: "content") {{ email }} }}"#, thread.hash)
+        }))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+        assert_eq!(json["data"]["postComment"]["email"], "test@mail.com");
+
+        let json: Value = post(me.clone())
+            .bearer_auth("forged")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(json["data"]["me"], Value::Null);
+
+        // sessions of deleted users end
+        memdb.delete_user("test").await.unwrap();
+        let json: Value = post(me)
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(json["data"]["me"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let memdb = Arc::new(MemDB::default());
+        let schema = GraphQLFrontend::new(memdb.clone())
+            .with_signup_provider(Arc::new(TestSignupProvider { user_store: memdb }))
+            .schema();
+
+        let response = schema
+            .execute(r#"mutation { authorize(provider: "test", redirectUri: "https://example.com/callback") }"#)
+            .await;
+
+        assert!(response
+            .http_headers
+            .get("Set-Cookie")
+            .unwrap()
+            .starts_with("signin=binding;"));
+        assert_eq!(
+            response.data.into_json().unwrap()["authorize"],
+            "https://login.test/?redirect_uri=https://example.com/callback"
+        );
+
+        // confirming needs the binding from the cookie
+        let confirm = r#"mutation { confirm(provider: "test", token: "test@mail.com", userName: "test") { user { name } } }"#;
+        let response = schema
+            .execute(Request::new(confirm).data(SigninBinding("forged".to_string())))
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-01");
+
+        let response = schema
+            .execute(Request::new(confirm).data(SigninBinding("binding".to_string())))
+            .await;
+
+        assert_eq!(
+            response.data.into_json().unwrap()["confirm"]["user"]["name"],
+            "test"
+        );
+
+        let response = schema
+            .execute(r#"mutation { authorize(provider: "unknown", redirectUri: "https://example.com/callback") }"#)
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-01");
+    }
+
+    #[tokio::test]
+    async fn test_signup_unknown_provider() {
+        let schema = GraphQLFrontend::new(Arc::new(MemDB::default())).schema();
+
+        let response = schema
+            .execute(
+                r#"mutation { signup(provider: "unknown", email: "test@mail.com") { email } }"#,
+            )
+            .await;
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+
+        assert_eq!(&error["extensions"]["code"], "E-03-01");
+    }
+
+    #[tokio::test]
+    async fn test_thread_comments_pagination() {
+        let memdb = MemDB::default();
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+
+        for date in 0..5 {
+            memdb
+                .save_comment(Comment::new(
+                    &thread.hash,
+                    "test@mail.com",
+                    date,
+                    &format!("comment {}", date),
+                ))
+                .await
+                .unwrap();
+        }
+
+        let schema = GraphQLFrontend::new(Arc::new(memdb)).schema();
+        let comments = |arguments: &str| {
+            format!(
+                r#"{{ thread(hash: "{}") {{ comments({}) {{ edges {{ cursor node {{ content }} }} pageInfo {{ hasPreviousPage hasNextPage }} totalCount }} }} }}"#,
+                thread.hash, arguments
+            )
+        };
+
+        let json = schema
+            .execute(comments("first: 2"))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+        let connection = &json["thread"]["comments"];
+
+        assert_eq!(
+            connection["edges"],
+            json!([
+                { "cursor": "0", "node": { "content": "comment 0" } },
+                { "cursor": "1", "node": { "content": "comment 1" } },
+            ])
+        );
+        assert_eq!(
+            connection["pageInfo"],
+            json!({ "hasPreviousPage": false, "hasNextPage": true })
+        );
+        assert_eq!(connection["totalCount"], 5);
+
+        let json = schema
+            .execute(comments(r#"first: 2, after: "3""#))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+        let connection = &json["thread"]["comments"];
+
+        assert_eq!(
+            connection["edges"],
+            json!([{ "cursor": "4", "node": { "content": "comment 4" } }])
+        );
+        assert_eq!(
+            connection["pageInfo"],
+            json!({ "hasPreviousPage": true, "hasNextPage": false })
+        );
+
+        let json = schema
+            .execute(comments(r#"last: 2, before: "3""#))
+            .await
+            .data
+            .into_json()
+            .unwrap();
+
+        assert_eq!(
+            json["thread"]["comments"]["edges"],
+            json!([
+                { "cursor": "1", "node": { "content": "comment 1" } },
+                { "cursor": "2", "node": { "content": "comment 2" } },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_comment_subscriptions() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let thread_2 = memdb.save_thread(Thread::new("thread_2")).await.unwrap();
+        let schema = GraphQLFrontend::new(memdb.clone()).schema();
+
+        let mut added = schema.execute_stream(format!(
+            r#"subscription {{ commentAdded(threadHash: "{}") {{ hash content }} }}"#,
+            thread.hash
+        ));
+        let mut deleted = schema.execute_stream(format!(
+            r#"subscription {{ commentDeleted(threadHash: "{}") {{ hash }} }}"#,
+            thread.hash
+        ));
+
+        // subscriptions only start listening once they are polled
+        select! {
+            _ = added.next() => panic!("Subscription yielded before a comment was added"),
+            _ = deleted.next() => panic!("Subscription yielded before a comment was deleted"),
+            _ = sleep(Duration::from_millis(10)) => {}
+        }
+
+        memdb
+            .save_comment(Comment::new(&thread_2.hash, "test@mail.com", 0, "other"))
+            .await
+            .unwrap();
+        let comment = memdb
+            .save_comment(Comment::new(&thread.hash, "test@mail.com", 1, "content"))
+            .await
+            .unwrap();
+        memdb.delete_comment(&comment.hash).await.unwrap();
+
+        assert_eq!(
+            added.next().await.unwrap().data.into_json().unwrap(),
+            json!({ "commentAdded": { "hash": comment.hash, "content": "content" } })
+        );
+        assert_eq!(
+            deleted.next().await.unwrap().data.into_json().unwrap(),
+            json!({ "commentDeleted": { "hash": comment.hash } })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_comment_subscription_over_websocket() {
+        let memdb = Arc::new(MemDB::default());
+        let thread = memdb.save_thread(Thread::new("thread")).await.unwrap();
+        let app = GraphQLFrontend::new(memdb.clone()).app();
+
+        let client = async {
+            // give the server time to bind
+            sleep(Duration::from_millis(100)).await;
+
+            let mut request = "ws://localhost:8001/".into_client_request().unwrap();
+            request
+                .headers_mut()
+                .insert("Sec-WebSocket-Protocol", "graphql-ws".parse().unwrap());
+            let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+            socket
+                .send(Message::Text(
+                    json!({ "type": "connection_init" }).to_string(),
+                ))
+                .await
+                .unwrap();
+            let ack: Value =
+                serde_json::from_str(&socket.next().await.unwrap().unwrap().into_text().unwrap())
+                    .unwrap();
+            assert_eq!(ack["type"], "connection_ack");
+
+            socket
+                .send(Message::Text(
+                    json!({
+                        "type": "start",
+                        "id": "1",
+                        "payload": {
+                            "query": format!(
+                                r#"subscription {{ commentAdded(threadHash: "{}") {{ content }} }}"#,
+                                thread.hash
+                            )
+                        }
+                    })
+                    .to_string(),
+                ))
+                .await
+                .unwrap();
+            // the protocol does not acknowledge started operations
+            sleep(Duration::from_millis(100)).await;
+
+            memdb
+                .save_comment(Comment::new(&thread.hash, "test@mail.com", 0, "content"))
+                .await
+                .unwrap();
+
+            serde_json::from_str::<Value>(
+                &socket.next().await.unwrap().unwrap().into_text().unwrap(),
+            )
+            .unwrap()
+        };
 
         select! {
-            res = response => {
-                let resp: Response = res.unwrap();
-                let json: Value = serde_json::from_str(resp.text().await.unwrap().as_str()).unwrap();
-
-                assert_eq!(&json["data"]["users"][0]["name"], "test");
-                assert_eq!(&json["data"]["users"][0]["email"], "test@mail.com");
-                assert_eq!(&json["data"]["users"][1]["name"], "test2");
-                assert_eq!(&json["data"]["users"][1]["email"], "test2@mail.com");
+            message = client => {
+                assert_eq!(
+                    message,
+                    json!({
+                        "type": "data",
+                        "id": "1",
+                        "payload": { "data": { "commentAdded": { "content": "content" } } }
+                    })
+                );
             },
-            _server = frontend.run() => panic!("Server stopped before request returned")
+            _server = app.listen("localhost:8001") => panic!("Server stopped before subscription returned")
         };
     }
 }
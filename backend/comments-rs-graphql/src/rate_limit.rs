@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery},
+    parser::types::{ExecutableDocument, OperationType},
+    Pos, ServerResult, Variables,
+};
+use comments_rs_core_backend::{
+    data::{RequestInfo, User},
+    error::Error,
+};
+
+use crate::graphql_error;
+
+/// Number of buckets kept before full ones are dropped, they are recreated full on demand.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// The operations limited separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Posting comments
+    Post,
+    /// Starting and confirming signups
+    Signup,
+    /// Reporting comments
+    Report,
+    /// Query requests of any kind
+    Query,
+}
+
+/// Allows bursts of `burst` requests, refilled evenly over `period`. A `burst` of zero blocks
+/// the operation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, period: Duration) -> Self {
+        Self { burst, period }
+    }
+
+    fn refill_rate(&self) -> f64 {
+        self.burst as f64 / self.period.as_secs_f64()
+    }
+}
+
+/// Parses `<burst>/<seconds>`, e.g. `5/60` for five requests a minute.
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid rate limit {}, expected <burst>/<seconds>", s);
+        let (burst, seconds) = s.split_once('/').ok_or_else(invalid)?;
+        let burst = burst.trim().parse().map_err(|_| invalid())?;
+        let seconds: u64 = seconds.trim().parse().map_err(|_| invalid())?;
+
+        if seconds == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(burst, Duration::from_secs(seconds)))
+    }
+}
+
+impl Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.burst, self.period.as_secs())
+    }
+}
+
+/// Who a request is counted against.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Client {
+    User(String),
+    Ip(IpAddr),
+}
+
+impl Client {
+    /// The signed in user and the address a request came from, as far as known.
+    pub fn of(user: Option<&User>, request: Option<&RequestInfo>) -> Vec<Client> {
+        user.map(|user| Client::User(user.name.clone()))
+            .into_iter()
+            .chain(request.and_then(|request| request.ip).map(Client::Ip))
+            .collect()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets per operation and client.
+///
+/// A request takes a token from the bucket of every client it is counted against, so signed in
+/// users can't escape the limit of their address by switching accounts and vice versa.
+/// Operations without a limit are never limited.
+pub struct RateLimiter {
+    limits: HashMap<Operation, RateLimit>,
+    buckets: Mutex<HashMap<(Operation, Client), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: HashMap<Operation, RateLimit>) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token for `operation` from the buckets of all `clients`, or fails with how long
+    /// to wait until every bucket has one again.
+    pub fn check(&self, operation: Operation, clients: &[Client]) -> Result<(), Error> {
+        let limit = match self.limits.get(&operation) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let rate = limit.refill_rate();
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|(operation, _), bucket| {
+                let rate = self.limits[operation].refill_rate();
+
+                tokens(bucket, rate, now) < self.limits[operation].burst as f64
+            });
+        }
+
+        let mut wait: f64 = 0.0;
+
+        for client in clients {
+            let bucket = buckets
+                .entry((operation, client.clone()))
+                .or_insert(Bucket {
+                    tokens: limit.burst as f64,
+                    updated: now,
+                });
+
+            bucket.tokens = tokens(bucket, rate, now).min(limit.burst as f64);
+            bucket.updated = now;
+            wait = wait.max((1.0 - bucket.tokens) / rate);
+        }
+
+        if wait > 0.0 {
+            let wait = wait.min(limit.period.as_secs_f64());
+
+            return Err(Error::RateLimited(Duration::from_secs_f64(wait)));
+        }
+
+        for client in clients {
+            if let Some(bucket) = buckets.get_mut(&(operation, client.clone())) {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn tokens(bucket: &Bucket, rate: f64, now: Instant) -> f64 {
+    bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate
+}
+
+/// Limits query operations with the [`RateLimiter`] of the schema.
+pub struct QueryRateLimit;
+
+impl ExtensionFactory for QueryRateLimit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryRateLimitExtension)
+    }
+}
+
+struct QueryRateLimitExtension;
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for QueryRateLimitExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let is_query = document
+            .operations
+            .iter()
+            .any(|(_, operation)| operation.node.ty == OperationType::Query);
+
+        if let (true, Some(limiter)) = (is_query, ctx.data_opt::<Arc<RateLimiter>>()) {
+            let clients = Client::of(ctx.data_opt::<User>(), ctx.data_opt::<RequestInfo>());
+
+            limiter
+                .check(Operation::Query, &clients)
+                .map_err(|error| graphql_error(error).into_server_error(Pos::default()))?;
+        }
+
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use comments_rs_core_backend::error::Error;
+
+    use crate::rate_limit::{Client, Operation, RateLimit, RateLimiter};
+
+    fn limiter(burst: u32, period: Duration) -> RateLimiter {
+        RateLimiter::new(HashMap::from([(
+            Operation::Post,
+            RateLimit::new(burst, period),
+        )]))
+    }
+
+    #[test]
+    fn test_burst_and_refill() {
+        let limiter = limiter(2, Duration::from_millis(200));
+        let user = [Client::User("test".to_string())];
+
+        assert_eq!(limiter.check(Operation::Post, &user), Ok(()));
+        assert_eq!(limiter.check(Operation::Post, &user), Ok(()));
+        assert!(matches!(
+            limiter.check(Operation::Post, &user),
+            Err(Error::RateLimited(wait)) if wait <= Duration::from_millis(100)
+        ));
+
+        std::thread::sleep(Duration::from_millis(110));
+
+        assert_eq!(limiter.check(Operation::Post, &user), Ok(()));
+        // other operations and clients have their own buckets
+        assert_eq!(limiter.check(Operation::Query, &user), Ok(()));
+        assert_eq!(
+            limiter.check(Operation::Post, &[Client::User("other".to_string())]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_all_clients_counted() {
+        let limiter = limiter(1, Duration::from_secs(60));
+        let ip = Client::Ip("127.0.0.1".parse().unwrap());
+
+        assert_eq!(
+            limiter.check(
+                Operation::Post,
+                &[Client::User("test".to_string()), ip.clone()]
+            ),
+            Ok(())
+        );
+        // a different account from the same address is still limited
+        assert!(limiter
+            .check(Operation::Post, &[Client::User("other".to_string()), ip])
+            .is_err());
+        // and the failed request took no token from the other account
+        assert_eq!(
+            limiter.check(Operation::Post, &[Client::User("other".to_string())]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_parse_rate_limit() {
+        assert_eq!(
+            "5/60".parse(),
+            Ok(RateLimit::new(5, Duration::from_secs(60)))
+        );
+        assert!("5".parse::<RateLimit>().is_err());
+        assert!("5/0".parse::<RateLimit>().is_err());
+        assert_eq!(
+            RateLimit::new(5, Duration::from_secs(60)).to_string(),
+            "5/60"
+        );
+    }
+}
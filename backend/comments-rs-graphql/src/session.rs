@@ -0,0 +1,207 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use comments_rs_core_backend::{data::User, error::Error};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Name of the cookie carrying the session token.
+pub const SESSION_COOKIE: &str = "session";
+
+/// Name of the cookie carrying the binding of a sign in started with `authorize`.
+pub const SIGNIN_COOKIE: &str = "signin";
+
+/// How long a sign in started with `authorize` may take.
+const SIGNIN_LIFETIME: Duration = Duration::from_secs(600);
+
+/// How long a session lasts by default.
+const LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Issues and verifies the signed tokens identifying the user of a request.
+pub struct Sessions {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey<'static>,
+    lifetime: Duration,
+}
+
+/// The binding of the sign in the browser started, from its signin cookie.
+pub struct SigninBinding(pub String);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// Name of the signed in user
+    sub: String,
+    exp: u64,
+}
+
+impl Sessions {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret).into_static(),
+            lifetime: LIFETIME,
+        }
+    }
+
+    /// Signs with a secret generated on startup, so sessions end with the process.
+    pub fn random() -> Self {
+        let mut secret = [0u8; 32];
+        thread_rng().fill_bytes(&mut secret);
+
+        Self::new(&secret)
+    }
+
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn issue(&self, user: &User) -> String {
+        let claims = Claims {
+            sub: user.name.clone(),
+            exp: now() + self.lifetime.as_secs(),
+        };
+
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)
+            .expect("Session claims can always be encoded")
+    }
+
+    /// Returns the name of the user the token was issued for.
+    pub fn verify(&self, token: &str) -> Result<String, Error> {
+        decode::<Claims>(
+            token,
+            &self.decoding_key,
+            &Validation::new(Algorithm::HS256),
+        )
+        .map(|token| token.claims.sub)
+        .map_err(|error| Error::SignatureError(vec![format!("{:?}", error.kind())]))
+    }
+
+    /// A `Set-Cookie` value storing `token` in the browser.
+    pub fn cookie(&self, token: &str) -> String {
+        format!(
+            "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax",
+            SESSION_COOKIE,
+            token,
+            self.lifetime.as_secs()
+        )
+    }
+
+    /// A `Set-Cookie` value storing the `binding` of a sign in in the browser.
+    pub fn signin_cookie(&self, binding: &str) -> String {
+        format!(
+            "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax",
+            SIGNIN_COOKIE,
+            binding,
+            SIGNIN_LIFETIME.as_secs()
+        )
+    }
+
+    /// A `Set-Cookie` value removing the session cookie.
+    pub fn removal_cookie(&self) -> String {
+        format!(
+            "{}=; Max-Age=0; Path=/; HttpOnly; SameSite=Lax",
+            SESSION_COOKIE
+        )
+    }
+}
+
+/// Takes the session token from a bearer `Authorization` header or, failing that, the session
+/// cookie.
+pub fn session_token(authorization: Option<&str>, cookie: Option<&str>) -> Option<String> {
+    let bearer = authorization.and_then(|authorization| authorization.strip_prefix("Bearer "));
+    let cookie = cookie.and_then(|cookie| cookie_value(cookie, SESSION_COOKIE));
+
+    bearer
+        .or(cookie)
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Takes the binding of a started sign in from the signin cookie.
+pub fn signin_binding(cookie: Option<&str>) -> Option<SigninBinding> {
+    cookie
+        .and_then(|cookie| cookie_value(cookie, SIGNIN_COOKIE))
+        .map(|binding| binding.trim())
+        .filter(|binding| !binding.is_empty())
+        .map(|binding| SigninBinding(binding.to_string()))
+}
+
+fn cookie_value<'a>(cookie: &'a str, name: &str) -> Option<&'a str> {
+    cookie
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(cookie_name, _)| *cookie_name == name)
+        .map(|(_, value)| value)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before unix epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use comments_rs_core_backend::{data::User, error::Error};
+
+    use crate::session::{session_token, signin_binding, Sessions, SigninBinding};
+
+    #[test]
+    fn test_issue_and_verify() {
+        let sessions = Sessions::new(b"secret");
+        let token = sessions.issue(&User::new("test@mail.com", "test"));
+
+        assert_eq!(sessions.verify(&token), Ok("test".to_string()));
+        assert_eq!(
+            Sessions::new(b"other secret").verify(&token),
+            Err(Error::SignatureError(vec!["InvalidSignature".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_verify_expired() {
+        let sessions = Sessions::new(b"secret").with_lifetime(Duration::from_secs(0));
+        let token = sessions.issue(&User::new("test@mail.com", "test"));
+        // jsonwebtoken allows no leeway by default, but checks `exp` in whole seconds
+        std::thread::sleep(Duration::from_millis(1100));
+
+        assert_eq!(
+            sessions.verify(&token),
+            Err(Error::SignatureError(vec!["ExpiredSignature".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_session_token() {
+        assert_eq!(
+            session_token(Some("Bearer token"), None),
+            Some("token".to_string())
+        );
+        assert_eq!(
+            session_token(None, Some("theme=dark; session=token")),
+            Some("token".to_string())
+        );
+        assert_eq!(
+            session_token(Some("Bearer bearer"), Some("session=cookie")),
+            Some("bearer".to_string())
+        );
+        assert_eq!(session_token(Some("Basic abc"), Some("session=")), None);
+        assert_eq!(session_token(None, None), None);
+    }
+
+    #[test]
+    fn test_signin_binding() {
+        let binding = |cookie| signin_binding(cookie).map(|SigninBinding(binding)| binding);
+
+        assert_eq!(
+            binding(Some("session=token; signin=binding")),
+            Some("binding".to_string())
+        );
+        assert_eq!(binding(Some("session=token; signin=")), None);
+        assert_eq!(binding(None), None);
+    }
+}
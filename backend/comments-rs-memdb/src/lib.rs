@@ -6,9 +6,16 @@ use std::{
 };
 
 use comments_rs_core_backend::{
-    data::{Comment, Thread, User},
+    data::{
+        AuditEntry, Ban, Comment, CommentStatus, CommentTree, Deletion, Report, Revision,
+        RoleGrant, Scope, Thread, User,
+    },
     error::StoreError,
-    traits::{CommentStore, StoreResult, ThreadStore, UserStore},
+    events::{CommentEvent, EventBus, EventStream},
+    traits::{
+        AuditStore, BanStore, CommentStore, ReportStore, RoleStore, StoreResult, ThreadStore,
+        UserStore,
+    },
 };
 
 #[derive(Default)]
@@ -16,11 +23,17 @@ struct CommentDb {
     users: Vec<User>,
     threads: Vec<Thread>,
     comments: Vec<Comment>,
+    revisions: Vec<Revision>,
+    bans: Vec<Ban>,
+    reports: Vec<Report>,
+    roles: Vec<RoleGrant>,
+    audit_log: Vec<AuditEntry>,
 }
 
 #[derive(Default)]
 pub struct MemDB {
     data: Box<Mutex<CommentDb>>,
+    events: EventBus,
 }
 
 struct ImmediateFuture<T> {
@@ -39,8 +52,20 @@ where
 }
 
 impl UserStore for MemDB {
-    fn save_user(&mut self, user: User) -> StoreResult<User> {
-        let data = self.data.get_mut().unwrap();
+    fn save_user(&self, user: User) -> StoreResult<User> {
+        let mut data = self.data.lock().unwrap();
+
+        if data.users.iter().any(|saved| saved.name == user.name) {
+            return Box::pin(ImmediateFuture {
+                result: Err(StoreError::NameNotUnique),
+            });
+        }
+        if data.users.iter().any(|saved| saved.email == user.email) {
+            return Box::pin(ImmediateFuture {
+                result: Err(StoreError::EmailNotUnique),
+            });
+        }
+
         data.users.push(user);
 
         Box::pin(ImmediateFuture {
@@ -54,18 +79,28 @@ impl UserStore for MemDB {
             .users
             .iter()
             .find(|user| user.name.as_str() == name)
-            .map(|user| user.clone());
+            .cloned();
 
         Box::pin(ImmediateFuture {
             result: Ok(option_user),
         })
     }
 
-    fn delete_user(
-        &mut self,
-        name: &str,
-    ) -> StoreResult<Option<User>> {
-        let data = self.data.get_mut().unwrap();
+    fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>> {
+        let data = self.data.lock().unwrap();
+        let option_user = data
+            .users
+            .iter()
+            .find(|user| user.email.as_str() == email)
+            .cloned();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(option_user),
+        })
+    }
+
+    fn delete_user(&self, name: &str) -> StoreResult<Option<User>> {
+        let mut data = self.data.lock().unwrap();
 
         let index = data
             .users
@@ -73,16 +108,18 @@ impl UserStore for MemDB {
             .position(|user| user.name.as_str() == name);
 
         match index {
-            Some(index) => Box::pin(ImmediateFuture {
-                result: Ok(Some(data.users.remove(index))),
-            }),
+            Some(index) => {
+                data.roles.retain(|grant| grant.user_name != name);
+
+                Box::pin(ImmediateFuture {
+                    result: Ok(Some(data.users.remove(index))),
+                })
+            }
             None => Box::pin(ImmediateFuture { result: Ok(None) }),
         }
     }
 
-    fn find_all_users(
-        &self,
-    ) -> StoreResult<Vec<User>> {
+    fn find_all_users(&self) -> StoreResult<Vec<User>> {
         let data = self.data.lock().unwrap();
 
         Box::pin(ImmediateFuture {
@@ -92,38 +129,73 @@ impl UserStore for MemDB {
 }
 
 impl ThreadStore for MemDB {
-    fn save_thread(
-        &mut self,
-        thread: Thread,
-    ) -> StoreResult<Thread> {
-        let data = self.data.get_mut().unwrap();
-        data.threads.push(thread);
+    fn save_thread(&self, thread: Thread) -> StoreResult<Thread> {
+        let mut data = self.data.lock().unwrap();
+
+        let saved = match data.threads.iter().find(|saved| saved.hash == thread.hash) {
+            Some(saved) => saved.clone(),
+            None => {
+                data.threads.push(thread.clone());
+                thread
+            }
+        };
+
+        Box::pin(ImmediateFuture { result: Ok(saved) })
+    }
+
+    fn update_thread(&self, thread: Thread) -> StoreResult<Option<Thread>> {
+        let mut data = self.data.lock().unwrap();
+
+        let updated = data
+            .threads
+            .iter_mut()
+            .find(|saved| saved.hash == thread.hash)
+            .map(|saved| {
+                saved.require_approval = thread.require_approval;
+                saved.locked = thread.locked;
+                saved.clone()
+            });
 
         Box::pin(ImmediateFuture {
-            result: Ok(data.threads.last().unwrap().clone()),
+            result: Ok(updated),
         })
     }
 
-    fn delete_thread(
-        &mut self,
-        hash: &str,
-    ) -> StoreResult<Option<Thread>> {
-        let data = self.data.get_mut().unwrap();
+    fn delete_thread(&self, hash: &str) -> StoreResult<Option<Thread>> {
+        let mut data = self.data.lock().unwrap();
 
         let index = data.threads.iter().position(|thread| thread.hash == hash);
 
         match index {
-            Some(index) => Box::pin(ImmediateFuture {
-                result: Ok(Some(data.threads.remove(index))),
-            }),
+            Some(index) => {
+                let CommentDb {
+                    comments,
+                    revisions,
+                    reports,
+                    ..
+                } = &mut *data;
+
+                revisions.retain(|revision| {
+                    comments.iter().any(|comment| {
+                        comment.hash == revision.comment_hash && comment.thread_hash != hash
+                    })
+                });
+                reports.retain(|report| {
+                    comments.iter().any(|comment| {
+                        comment.hash == report.comment_hash && comment.thread_hash != hash
+                    })
+                });
+                comments.retain(|comment| comment.thread_hash != hash);
+
+                Box::pin(ImmediateFuture {
+                    result: Ok(Some(data.threads.remove(index))),
+                })
+            }
             None => Box::pin(ImmediateFuture { result: Ok(None) }),
         }
     }
 
-    fn find_thread_by_hash(
-        &self,
-        hash: &str,
-    ) -> StoreResult<Option<Thread>> {
+    fn find_thread_by_hash(&self, hash: &str) -> StoreResult<Option<Thread>> {
         let data = self.data.lock().unwrap();
 
         Box::pin(ImmediateFuture {
@@ -131,13 +203,11 @@ impl ThreadStore for MemDB {
                 .threads
                 .iter()
                 .find(|thread| thread.hash == hash)
-                .map(|thread| thread.clone())),
+                .cloned()),
         })
     }
 
-    fn find_all_threads(
-        &self,
-    ) -> StoreResult<Vec<Thread>> {
+    fn find_all_threads(&self) -> StoreResult<Vec<Thread>> {
         let data = self.data.lock().unwrap();
 
         Box::pin(ImmediateFuture {
@@ -147,36 +217,68 @@ impl ThreadStore for MemDB {
 }
 
 impl CommentStore for MemDB {
-    fn save_comment(
-        &mut self,
-        comment: Comment,
-    ) -> StoreResult<Comment> {
-        let data = self.data.get_mut().unwrap();
+    fn save_comment(&self, comment: Comment) -> StoreResult<Comment> {
+        let mut data = self.data.lock().unwrap();
 
         let thread = data
             .threads
             .iter()
             .find(|thread| thread.hash == comment.thread_hash);
 
-        match thread {
-            Some(_) => {
-                data.comments.push(comment);
+        let parent_exists = comment.parent_hash.as_ref().map(|parent_hash| {
+            data.comments
+                .iter()
+                .any(|saved| &saved.hash == parent_hash && saved.thread_hash == comment.thread_hash)
+        });
+
+        match (thread, parent_exists) {
+            (Some(_), Some(false)) => Box::pin(ImmediateFuture {
+                result: Err(StoreError::ParentNotExists(comment.parent_hash.unwrap())),
+            }),
+            (Some(_), _) => {
+                data.comments.push(comment.clone());
+                self.events.publish(CommentEvent::Added(comment));
 
                 Box::pin(ImmediateFuture {
                     result: Ok(data.comments.last().unwrap().clone()),
                 })
             }
-            None => Box::pin(ImmediateFuture {
-                result: Err(StoreError::ThreadNotExists(comment.thread_hash.into())),
+            (None, _) => Box::pin(ImmediateFuture {
+                result: Err(StoreError::ThreadNotExists(comment.thread_hash)),
             }),
         }
     }
 
-    fn delete_comment(
-        &mut self,
-        hash: &str,
-    ) -> StoreResult<Option<Comment>> {
-        let data = self.data.get_mut().unwrap();
+    fn edit_comment(&self, comment: Comment) -> StoreResult<Option<Comment>> {
+        let mut data = self.data.lock().unwrap();
+
+        let index = data
+            .comments
+            .iter()
+            .position(|saved| saved.hash == comment.hash);
+
+        match index {
+            Some(index) => {
+                let revision = data.comments[index].revision();
+                let saved = &mut data.comments[index];
+
+                saved.content = comment.content;
+                saved.edited_at = comment.edited_at;
+
+                let saved = saved.clone();
+                data.revisions.push(revision);
+                self.events.publish(CommentEvent::Edited(saved.clone()));
+
+                Box::pin(ImmediateFuture {
+                    result: Ok(Some(saved)),
+                })
+            }
+            None => Box::pin(ImmediateFuture { result: Ok(None) }),
+        }
+    }
+
+    fn delete_comment(&self, hash: &str) -> StoreResult<Option<Comment>> {
+        let mut data = self.data.lock().unwrap();
 
         let index = data
             .comments
@@ -184,16 +286,186 @@ impl CommentStore for MemDB {
             .position(|comment| comment.hash == hash);
 
         match index {
-            Some(index) => Box::pin(ImmediateFuture {
-                result: Ok(Some(data.comments.remove(index))),
-            }),
+            Some(index) => {
+                let comment = data.comments.remove(index);
+                let mut deleted = vec![comment.hash.clone()];
+
+                // replies come after their parents, so one pass catches every descendant
+                data.comments.retain(|reply| match &reply.parent_hash {
+                    Some(parent_hash) if deleted.contains(parent_hash) => {
+                        deleted.push(reply.hash.clone());
+                        false
+                    }
+                    _ => true,
+                });
+                data.revisions
+                    .retain(|revision| !deleted.contains(&revision.comment_hash));
+                data.reports
+                    .retain(|report| !deleted.contains(&report.comment_hash));
+                self.events.publish(CommentEvent::Deleted(comment.clone()));
+
+                Box::pin(ImmediateFuture {
+                    result: Ok(Some(comment)),
+                })
+            }
             None => Box::pin(ImmediateFuture { result: Ok(None) }),
         }
     }
 
-    fn find_thread_comments(
+    fn soft_delete_comment(&self, hash: &str, deletion: Deletion) -> StoreResult<Option<Comment>> {
+        let mut data = self.data.lock().unwrap();
+
+        let index = data
+            .comments
+            .iter()
+            .position(|comment| comment.hash == hash);
+        let has_replies = data
+            .comments
+            .iter()
+            .any(|comment| comment.parent_hash.as_deref() == Some(hash));
+
+        let comment = index.map(|index| {
+            if has_replies {
+                data.comments[index].deletion = Some(deletion);
+                data.comments[index].clone()
+            } else {
+                let comment = data.comments.remove(index);
+                data.revisions
+                    .retain(|revision| revision.comment_hash != hash);
+                data.reports.retain(|report| report.comment_hash != hash);
+
+                Comment {
+                    deletion: Some(deletion),
+                    ..comment
+                }
+            }
+        });
+
+        if let Some(comment) = &comment {
+            self.events.publish(CommentEvent::Deleted(comment.clone()));
+        }
+
+        Box::pin(ImmediateFuture {
+            result: Ok(comment),
+        })
+    }
+
+    fn restore_comment(&self, hash: &str) -> StoreResult<Option<Comment>> {
+        let mut data = self.data.lock().unwrap();
+
+        let comment = data
+            .comments
+            .iter_mut()
+            .find(|comment| comment.hash == hash && comment.is_deleted())
+            .map(|comment| {
+                comment.deletion = None;
+                comment.clone()
+            });
+
+        if let Some(comment) = &comment {
+            self.events.publish(CommentEvent::Edited(comment.clone()));
+        }
+
+        Box::pin(ImmediateFuture {
+            result: Ok(comment),
+        })
+    }
+
+    fn set_comment_status(
+        &self,
+        hash: &str,
+        status: CommentStatus,
+    ) -> StoreResult<Option<Comment>> {
+        let mut data = self.data.lock().unwrap();
+
+        let changed = data
+            .comments
+            .iter_mut()
+            .find(|comment| comment.hash == hash)
+            .map(|comment| {
+                let previous = comment.status;
+                comment.status = status;
+                (comment.clone(), previous)
+            });
+
+        if let Some((comment, previous)) = &changed {
+            if status == CommentStatus::Approved {
+                data.reports
+                    .retain(|report| report.comment_hash != comment.hash);
+            }
+            self.events.publish(CommentEvent::StatusChanged {
+                comment: comment.clone(),
+                previous: *previous,
+            });
+        }
+        let comment = changed.map(|(comment, _)| comment);
+
+        Box::pin(ImmediateFuture {
+            result: Ok(comment),
+        })
+    }
+
+    fn find_comment(&self, hash: &str) -> StoreResult<Option<Comment>> {
+        let data = self.data.lock().unwrap();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(data
+                .comments
+                .iter()
+                .find(|comment| comment.hash == hash)
+                .cloned()),
+        })
+    }
+
+    fn find_revisions(&self, hash: &str) -> StoreResult<Vec<Revision>> {
+        let data = self.data.lock().unwrap();
+
+        let revisions: Vec<Revision> = data
+            .revisions
+            .iter()
+            .filter(|revision| revision.comment_hash == hash)
+            .cloned()
+            .collect();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(revisions),
+        })
+    }
+
+    fn find_thread_comments(&self, thread_hash: &str) -> StoreResult<Vec<Comment>> {
+        let data = self.data.lock().unwrap();
+
+        let comments: Vec<Comment> = data
+            .comments
+            .iter()
+            .filter(|comment| comment.thread_hash == thread_hash)
+            .cloned()
+            .collect();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(comments),
+        })
+    }
+
+    fn count_thread_comments(&self, thread_hash: &str, viewer: Option<&str>) -> StoreResult<usize> {
+        let data = self.data.lock().unwrap();
+
+        let count = data
+            .comments
+            .iter()
+            .filter(|comment| comment.thread_hash == thread_hash)
+            .filter(|comment| is_visible(comment, viewer))
+            .count();
+
+        Box::pin(ImmediateFuture { result: Ok(count) })
+    }
+
+    fn find_thread_comments_page(
         &self,
         thread_hash: &str,
+        viewer: Option<&str>,
+        offset: usize,
+        limit: usize,
     ) -> StoreResult<Vec<Comment>> {
         let data = self.data.lock().unwrap();
 
@@ -201,13 +473,323 @@ impl CommentStore for MemDB {
             .comments
             .iter()
             .filter(|comment| comment.thread_hash == thread_hash)
-            .map(|comment| comment.clone())
+            .filter(|comment| is_visible(comment, viewer))
+            .skip(offset)
+            .take(limit)
+            .cloned()
             .collect();
 
         Box::pin(ImmediateFuture {
             result: Ok(comments),
         })
     }
+
+    fn count_comments_by_status(&self, status: CommentStatus) -> StoreResult<usize> {
+        let data = self.data.lock().unwrap();
+
+        let count = data
+            .comments
+            .iter()
+            .filter(|comment| comment.status == status)
+            .count();
+
+        Box::pin(ImmediateFuture { result: Ok(count) })
+    }
+
+    fn find_comments_by_status(
+        &self,
+        status: CommentStatus,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Comment>> {
+        let data = self.data.lock().unwrap();
+
+        let comments: Vec<Comment> = data
+            .comments
+            .iter()
+            .filter(|comment| comment.status == status)
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(comments),
+        })
+    }
+
+    fn find_replies(&self, parent_hash: &str) -> StoreResult<Vec<Comment>> {
+        let data = self.data.lock().unwrap();
+
+        let comments: Vec<Comment> = data
+            .comments
+            .iter()
+            .filter(|comment| comment.parent_hash.as_deref() == Some(parent_hash))
+            .cloned()
+            .collect();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(comments),
+        })
+    }
+
+    fn find_reply_tree(&self, hash: &str) -> StoreResult<Option<CommentTree>> {
+        let data = self.data.lock().unwrap();
+
+        let tree = data
+            .comments
+            .iter()
+            .find(|comment| comment.hash == hash)
+            .and_then(|comment| {
+                let thread_comments = data
+                    .comments
+                    .iter()
+                    .filter(|reply| reply.thread_hash == comment.thread_hash)
+                    .cloned()
+                    .collect();
+
+                CommentTree::build(hash, thread_comments)
+            });
+
+        Box::pin(ImmediateFuture { result: Ok(tree) })
+    }
+
+    fn subscribe(&self) -> EventStream {
+        self.events.subscribe()
+    }
+}
+
+impl RoleStore for MemDB {
+    fn save_role(&self, grant: RoleGrant) -> StoreResult<RoleGrant> {
+        let mut data = self.data.lock().unwrap();
+
+        match data
+            .roles
+            .iter_mut()
+            .find(|saved| saved.user_name == grant.user_name && saved.scope == grant.scope)
+        {
+            Some(saved) => saved.role = grant.role,
+            None => data.roles.push(grant.clone()),
+        }
+
+        Box::pin(ImmediateFuture { result: Ok(grant) })
+    }
+
+    fn delete_role(&self, user_name: &str, scope: &Scope) -> StoreResult<Option<RoleGrant>> {
+        let mut data = self.data.lock().unwrap();
+
+        let index = data
+            .roles
+            .iter()
+            .position(|grant| grant.user_name == user_name && &grant.scope == scope);
+
+        Box::pin(ImmediateFuture {
+            result: Ok(index.map(|index| data.roles.remove(index))),
+        })
+    }
+
+    fn find_roles(&self, user_name: &str) -> StoreResult<Vec<RoleGrant>> {
+        let data = self.data.lock().unwrap();
+
+        let roles: Vec<RoleGrant> = data
+            .roles
+            .iter()
+            .filter(|grant| grant.user_name == user_name)
+            .cloned()
+            .collect();
+
+        Box::pin(ImmediateFuture { result: Ok(roles) })
+    }
+
+    fn find_all_roles(&self) -> StoreResult<Vec<RoleGrant>> {
+        let data = self.data.lock().unwrap();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(data.roles.clone()),
+        })
+    }
+}
+
+impl BanStore for MemDB {
+    fn save_ban(&self, ban: Ban) -> StoreResult<Ban> {
+        let mut data = self.data.lock().unwrap();
+
+        if data.bans.iter().any(|saved| saved.id == ban.id) {
+            return Box::pin(ImmediateFuture {
+                result: Err(StoreError::BanNotUnique(ban.id)),
+            });
+        }
+        data.bans.push(ban);
+
+        Box::pin(ImmediateFuture {
+            result: Ok(data.bans.last().unwrap().clone()),
+        })
+    }
+
+    fn delete_ban(&self, id: &str) -> StoreResult<Option<Ban>> {
+        let mut data = self.data.lock().unwrap();
+
+        let index = data.bans.iter().position(|ban| ban.id == id);
+
+        Box::pin(ImmediateFuture {
+            result: Ok(index.map(|index| data.bans.remove(index))),
+        })
+    }
+
+    fn find_all_bans(&self) -> StoreResult<Vec<Ban>> {
+        let data = self.data.lock().unwrap();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(data.bans.clone()),
+        })
+    }
+}
+
+impl AuditStore for MemDB {
+    fn append_audit_entry(&self, entry: AuditEntry) -> StoreResult<AuditEntry> {
+        let mut data = self.data.lock().unwrap();
+        data.audit_log.push(entry.clone());
+
+        Box::pin(ImmediateFuture { result: Ok(entry) })
+    }
+
+    fn count_audit_entries(&self, target: Option<&str>) -> StoreResult<usize> {
+        let data = self.data.lock().unwrap();
+
+        let count = data
+            .audit_log
+            .iter()
+            .filter(|entry| target.is_none_or(|target| entry.target == target))
+            .count();
+
+        Box::pin(ImmediateFuture { result: Ok(count) })
+    }
+
+    fn find_audit_entries(
+        &self,
+        target: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<AuditEntry>> {
+        let data = self.data.lock().unwrap();
+
+        let entries: Vec<AuditEntry> = data
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|entry| target.is_none_or(|target| entry.target == target))
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(entries),
+        })
+    }
+}
+
+impl ReportStore for MemDB {
+    fn save_report(&self, report: Report) -> StoreResult<usize> {
+        let mut data = self.data.lock().unwrap();
+
+        match data.reports.iter_mut().find(|saved| {
+            saved.comment_hash == report.comment_hash && saved.reporter == report.reporter
+        }) {
+            Some(saved) => *saved = report.clone(),
+            None => data.reports.push(report.clone()),
+        }
+
+        let count = data
+            .reports
+            .iter()
+            .filter(|saved| saved.comment_hash == report.comment_hash)
+            .count();
+
+        Box::pin(ImmediateFuture { result: Ok(count) })
+    }
+
+    fn find_reports(&self, comment_hash: &str) -> StoreResult<Vec<Report>> {
+        let data = self.data.lock().unwrap();
+
+        let reports: Vec<Report> = data
+            .reports
+            .iter()
+            .filter(|report| report.comment_hash == comment_hash)
+            .cloned()
+            .collect();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(reports),
+        })
+    }
+
+    fn count_reported_comments(&self) -> StoreResult<usize> {
+        let data = self.data.lock().unwrap();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(reported_comments(&data).len()),
+        })
+    }
+
+    fn find_reported_comments(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<(Comment, usize)>> {
+        let data = self.data.lock().unwrap();
+
+        let comments: Vec<(Comment, usize)> = reported_comments(&data)
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect();
+
+        Box::pin(ImmediateFuture {
+            result: Ok(comments),
+        })
+    }
+}
+
+/// Reported comments with their number of reports, most reported first.
+fn reported_comments(data: &CommentDb) -> Vec<(Comment, usize)> {
+    // reports are kept in the order they were made, so comments are first met at their first
+    // report and the stable sort keeps that order among equally reported ones
+    let mut counts: Vec<(&str, usize)> = vec![];
+
+    for report in &data.reports {
+        match counts
+            .iter_mut()
+            .find(|(hash, _)| *hash == report.comment_hash)
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((&report.comment_hash, 1)),
+        }
+    }
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    counts
+        .into_iter()
+        .filter_map(|(hash, count)| {
+            data.comments
+                .iter()
+                .find(|comment| comment.hash == hash)
+                .map(|comment| (comment.clone(), count))
+        })
+        .collect()
+}
+
+/// Whether `comment` shows up in its thread for the user with email `viewer`.
+fn is_visible(comment: &Comment, viewer: Option<&str>) -> bool {
+    comment.status == CommentStatus::Approved
+        && (!comment.shadowed || viewer == Some(comment.email.as_str()))
+}
+
+#[cfg(test)]
+mod conformance {
+    use crate::MemDB;
+
+    comments_rs_testsuite_backend::store_tests!(async { MemDB::default() });
 }
 
 #[cfg(test)]
@@ -221,7 +803,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_save_user() {
-        let mut user_db = MemDB::default();
+        let user_db = MemDB::default();
         let user = User::new("test@mail.com", "name");
 
         let saved_user = user_db.save_user(user).await.unwrap();
@@ -232,9 +814,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_find_user() {
-        let mut user_db = MemDB::default();
+        let user_db = MemDB::default();
         let user = User::new("test@mail.com", "name");
-        let user1 = User::new("test@mail.com", "name1");
+        let user1 = User::new("test1@mail.com", "name1");
 
         user_db.save_user(user.clone()).await.unwrap();
 
@@ -247,13 +829,13 @@ mod tests {
 
     #[tokio::test]
     async fn test_find_all_users() {
-        let mut user_db = MemDB::default();
+        let user_db = MemDB::default();
         let user = user_db
             .save_user(User::new("test@mail.com", "name"))
             .await
             .unwrap();
         let user1 = user_db
-            .save_user(User::new("test@mail.com", "name1"))
+            .save_user(User::new("test1@mail.com", "name1"))
             .await
             .unwrap();
 
@@ -265,9 +847,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_delete_user() {
-        let mut user_db = MemDB::default();
+        let user_db = MemDB::default();
         let user = User::new("test@mail.com", "name");
-        let user1 = User::new("test@mail.com", "name1");
+        let user1 = User::new("test1@mail.com", "name1");
 
         user_db.save_user(user.clone()).await.unwrap();
         user_db.save_user(user1.clone()).await.unwrap();
@@ -283,7 +865,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_save_thread() {
-        let mut comment_db = MemDB::default();
+        let comment_db = MemDB::default();
         let thread = Thread::new("thread");
 
         let save_result = comment_db.save_thread(thread.clone()).await.unwrap();
@@ -293,7 +875,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_delete_thread() {
-        let mut comment_db = MemDB::default();
+        let comment_db = MemDB::default();
         let thread = Thread::new("thread");
 
         let save_result = comment_db.save_thread(thread.clone()).await.unwrap();
@@ -317,7 +899,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_find_thread_by_hash() {
-        let mut comment_db = MemDB::default();
+        let comment_db = MemDB::default();
         let thread = Thread::new("thread");
 
         let _saved_thread = comment_db.save_thread(thread.clone()).await.unwrap();
@@ -333,7 +915,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_find_all_threads() {
-        let mut comment_db = MemDB::default();
+        let comment_db = MemDB::default();
         let thread = Thread::new("thread");
 
         let saved_thread = comment_db.save_thread(thread.clone()).await.unwrap();
@@ -343,7 +925,10 @@ mod tests {
             vec![saved_thread.clone()]
         );
 
-        let saved_thread_2 = comment_db.save_thread(thread.clone()).await.unwrap();
+        let saved_thread_2 = comment_db
+            .save_thread(Thread::new("thread_2"))
+            .await
+            .unwrap();
 
         assert_eq!(
             comment_db.find_all_threads().await.unwrap(),
@@ -353,7 +938,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_save_comment() {
-        let mut comment_db = MemDB::default();
+        let comment_db = MemDB::default();
         let thread = comment_db.save_thread(Thread::new("thread")).await.unwrap();
         let comment = Comment::new(thread.hash.as_str(), "user@mail.com", 17, "content");
         let saved_comment = comment_db.save_comment(comment.clone()).await.unwrap();
@@ -363,7 +948,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_delete_comment() {
-        let mut comment_db = MemDB::default();
+        let comment_db = MemDB::default();
         let thread = comment_db.save_thread(Thread::new("thread")).await.unwrap();
         let comment = Comment::new(thread.hash.as_str(), "user@mail.com", 17, "content");
         let saved_comment = comment_db.save_comment(comment.clone()).await.unwrap();
@@ -387,7 +972,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_find_all_comments() {
-        let mut comment_db = MemDB::default();
+        let comment_db = MemDB::default();
         let thread = comment_db.save_thread(Thread::new("thread")).await.unwrap();
         let thread_2 = comment_db
             .save_thread(Thread::new("thread_2"))
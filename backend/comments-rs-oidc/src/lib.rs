@@ -0,0 +1,726 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use comments_rs_core_backend::{
+    data::User,
+    error::Error,
+    traits::{Authorization, SignupProvider, SignupResult, UserStore},
+};
+use comments_rs_google_backend::jwks::JwksCache;
+use jsonwebtoken::{decode, decode_header, Algorithm, Validation};
+use rand::{thread_rng, RngCore};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// How long a started sign in may take until the callback arrives.
+const PENDING_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Signs users in at any OpenID Connect identity provider using the authorization-code flow with
+/// PKCE.
+///
+/// A sign in starts with [`SignupProvider::authorize`], which returns the url to send the user to
+/// along with a binding the caller has to keep. The identity provider redirects back with `code`
+/// and `state` in the query string, which both steps of [`SignupProvider`] take as their token
+/// together with the binding: `signup_bound` looks up the user owning the verified email and
+/// `confirm_bound` additionally creates the user under `user_name` if there is none yet. Without
+/// the binding, which never leaves the caller, a leaked callback is of no use. A callback can be
+/// used for exactly one sign in.
+#[derive(Clone)]
+pub struct OidcSignupProvider {
+    user_store: Arc<dyn UserStore>,
+    client_id: String,
+    client_secret: Option<String>,
+    metadata: ProviderMetadata,
+    jwks: Arc<JwksCache>,
+    client: reqwest::Client,
+    pending: Arc<Mutex<HashMap<String, Pending>>>,
+}
+
+/// The parts of the discovery document this provider relies on.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+enum Pending {
+    Authorizing {
+        binding: String,
+        redirect_uri: String,
+        code_verifier: String,
+        nonce: String,
+        started: Instant,
+    },
+    /// The code was redeemed by `signup_bound` for a user who doesn't exist yet, kept for the
+    /// following `confirm_bound`
+    Verified {
+        binding: String,
+        email: String,
+        started: Instant,
+    },
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdToken {
+    email: String,
+    #[serde(default)]
+    email_verified: bool,
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+impl SignupProvider for OidcSignupProvider {
+    fn name(&self) -> &'static str {
+        "oidc"
+    }
+
+    /// Sign ins have to be started with `authorize`, see `signup_bound`.
+    fn signup(&self, _callback: &str) -> SignupResult {
+        Box::pin(async { Err(Error::SignupError) })
+    }
+
+    /// Sign ins have to be started with `authorize`, see `confirm_bound`.
+    fn confirm(&self, _callback: &str, _user_name: &str) -> SignupResult {
+        Box::pin(async { Err(Error::SignupError) })
+    }
+
+    fn authorize(&self, redirect_uri: &str) -> Result<Authorization, Error> {
+        self.start(redirect_uri)
+            .map(|(authorization, _)| authorization)
+    }
+
+    fn signup_bound(&self, callback: &str, binding: &str) -> SignupResult {
+        let provider = self.clone();
+        let callback = callback.to_string();
+        let binding = binding.to_string();
+
+        Box::pin(async move {
+            let (state, email, started) = provider.authenticate(&callback, &binding).await?;
+
+            match provider.user_store.find_user_by_email(&email).await? {
+                Some(user) => Ok(user),
+                // the user has to pick a name next
+                None => {
+                    provider.keep_verified(state, binding, email, started);
+
+                    Err(Error::SignupError)
+                }
+            }
+        })
+    }
+
+    fn confirm_bound(&self, callback: &str, binding: &str, user_name: &str) -> SignupResult {
+        let provider = self.clone();
+        let callback = callback.to_string();
+        let binding = binding.to_string();
+        let user_name = user_name.to_string();
+
+        Box::pin(async move {
+            let (state, email, started) = provider.authenticate(&callback, &binding).await?;
+            let user = match provider.user_store.find_user_by_email(&email).await? {
+                Some(user) => Ok(user),
+                None => match User::try_new(&email, &user_name) {
+                    Ok(user) => provider
+                        .user_store
+                        .save_user(user)
+                        .await
+                        .map_err(Error::from),
+                    Err(error) => Err(error),
+                },
+            };
+
+            // the user may try another name
+            if user.is_err() {
+                provider.keep_verified(state, binding, email, started);
+            }
+
+            user
+        })
+    }
+}
+
+impl OidcSignupProvider {
+    /// Reads the endpoints of the identity provider from its `discovery_url`, usually
+    /// `<issuer>/.well-known/openid-configuration`.
+    pub async fn discover(
+        user_store: Arc<dyn UserStore>,
+        discovery_url: &str,
+        client_id: &str,
+    ) -> Result<Self, Error> {
+        let client = reqwest::Client::new();
+        let metadata: ProviderMetadata = client
+            .get(discovery_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(map_err)?
+            .json()
+            .await
+            .map_err(map_err)?;
+
+        Ok(Self {
+            user_store,
+            client_id: client_id.to_string(),
+            client_secret: None,
+            jwks: Arc::new(JwksCache::new(&metadata.jwks_uri)),
+            metadata,
+            client,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Authenticates at the token endpoint with `client_secret`, which confidential clients
+    /// need in addition to PKCE.
+    pub fn with_client_secret(mut self, client_secret: &str) -> Self {
+        self.client_secret = Some(client_secret.to_string());
+        self
+    }
+
+    pub fn metadata(&self) -> &ProviderMetadata {
+        &self.metadata
+    }
+
+    /// Starts a sign in, the identity provider redirects the user to `redirect_uri` afterwards.
+    /// Returns the state as well.
+    fn start(&self, redirect_uri: &str) -> Result<(Authorization, String), Error> {
+        let state = random_string();
+        let binding = random_string();
+        let nonce = random_string();
+        let code_verifier = random_string();
+        let code_challenge = base64::encode_config(
+            Sha256::digest(code_verifier.as_bytes()),
+            base64::URL_SAFE_NO_PAD,
+        );
+
+        let url = Url::parse_with_params(
+            &self.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", redirect_uri),
+                ("scope", "openid email"),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|_| Error::SignupError)?;
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, pending| pending.started().elapsed() < PENDING_TIMEOUT);
+        pending.insert(
+            state.clone(),
+            Pending::Authorizing {
+                binding: binding.clone(),
+                redirect_uri: redirect_uri.to_string(),
+                code_verifier,
+                nonce,
+                started: Instant::now(),
+            },
+        );
+
+        Ok((
+            Authorization {
+                url: url.into(),
+                binding,
+            },
+            state,
+        ))
+    }
+
+    /// Resolves the callback query into its state, the verified email of the user and when the
+    /// sign in started, as long as it was started by the holder of `binding`. The state is used
+    /// up, unless `binding` doesn't match.
+    async fn authenticate(
+        &self,
+        callback: &str,
+        binding: &str,
+    ) -> Result<(String, String, Instant), Error> {
+        let params: HashMap<String, String> =
+            url::form_urlencoded::parse(callback.trim_start_matches('?').as_bytes())
+                .into_owned()
+                .collect();
+        let state = params.get("state").ok_or(Error::SignupError)?;
+
+        let pending = {
+            let mut pending = self.pending.lock().unwrap();
+
+            match pending.get(state) {
+                Some(entry) if entry.binding() == binding => pending.remove(state),
+                _ => None,
+            }
+        };
+        let (redirect_uri, code_verifier, nonce, started) = match pending {
+            Some(pending) if pending.started().elapsed() >= PENDING_TIMEOUT => {
+                return Err(Error::SignupError)
+            }
+            Some(Pending::Verified { email, started, .. }) => {
+                return Ok((state.clone(), email, started))
+            }
+            Some(Pending::Authorizing {
+                redirect_uri,
+                code_verifier,
+                nonce,
+                started,
+                ..
+            }) => (redirect_uri, code_verifier, nonce, started),
+            None => return Err(Error::SignupError),
+        };
+
+        let code = params.get("code").ok_or(Error::SignupError)?;
+        let id_token = self.exchange(code, &redirect_uri, &code_verifier).await?;
+        let email = self.verify(&id_token, &nonce).await?;
+
+        Ok((state.clone(), email, started))
+    }
+
+    /// Keeps the verified `email` for a `confirm_bound` with the same callback and binding, until
+    /// the sign in `started` times out.
+    fn keep_verified(&self, state: String, binding: String, email: String, started: Instant) {
+        self.pending.lock().unwrap().insert(
+            state,
+            Pending::Verified {
+                binding,
+                email,
+                started,
+            },
+        );
+    }
+
+    /// Redeems `code` at the token endpoint and returns the ID token.
+    async fn exchange(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+    ) -> Result<String, Error> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", &self.client_id),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+
+        let response = self
+            .client
+            .post(&self.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(map_err)?;
+
+        if !response.status().is_success() {
+            return Err(Error::SignupError);
+        }
+
+        let token: TokenResponse = response.json().await.map_err(map_err)?;
+
+        Ok(token.id_token)
+    }
+
+    /// Checks signature, issuer, audience, expiry and nonce of `id_token` and returns its email.
+    async fn verify(&self, id_token: &str, nonce: &str) -> Result<String, Error> {
+        let header = decode_header(id_token).map_err(signature_error)?;
+        let kid = header
+            .kid
+            .ok_or_else(|| Error::SignatureError(vec!["MissingKeyId".to_string()]))?;
+        let key = self.jwks.key(&kid).await?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&self.client_id]);
+        validation.iss = Some(self.metadata.issuer.clone());
+
+        let token = decode::<IdToken>(id_token, &key, &validation)
+            .map_err(signature_error)?
+            .claims;
+
+        if token.nonce.as_deref() != Some(nonce) {
+            return Err(Error::SignatureError(vec!["InvalidNonce".to_string()]));
+        }
+        if !token.email_verified {
+            return Err(Error::SignupError);
+        }
+
+        Ok(token.email)
+    }
+}
+
+impl Pending {
+    fn started(&self) -> Instant {
+        match self {
+            Pending::Authorizing { started, .. } | Pending::Verified { started, .. } => *started,
+        }
+    }
+
+    fn binding(&self) -> &str {
+        match self {
+            Pending::Authorizing { binding, .. } | Pending::Verified { binding, .. } => binding,
+        }
+    }
+}
+
+fn random_string() -> String {
+    let mut bytes = [0u8; 32];
+    thread_rng().fill_bytes(&mut bytes);
+
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn signature_error(error: jsonwebtoken::errors::Error) -> Error {
+    Error::SignatureError(vec![format!("{:?}", error.kind())])
+}
+
+fn map_err(_error: reqwest::Error) -> Error {
+    Error::NewtorkError
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use comments_rs_core_backend::{
+        data::User,
+        error::Error,
+        traits::{SignupProvider, UserStore},
+    };
+    use comments_rs_memdb_backend::MemDB;
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use serde::Deserialize;
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+    use tide::{listener::Listener, Request, Response};
+    use url::Url;
+
+    use crate::OidcSignupProvider;
+
+    const CLIENT_ID: &str = "comments";
+    const REDIRECT_URI: &str = "http://localhost:8000/callback";
+
+    /// Codes handed out by the mock, with the PKCE challenge, nonce and email they were issued
+    /// for.
+    type Codes = Arc<Mutex<HashMap<String, (String, String, String)>>>;
+
+    #[derive(Deserialize)]
+    struct TokenRequest {
+        grant_type: String,
+        code: String,
+        client_id: String,
+        code_verifier: String,
+    }
+
+    /// A minimal identity provider serving discovery, keys and the token endpoint.
+    struct MockIdentityProvider {
+        url: String,
+        codes: Codes,
+    }
+
+    impl MockIdentityProvider {
+        async fn start() -> Self {
+            let codes = Codes::default();
+            let mut app = tide::with_state(codes.clone());
+
+            app.at("/.well-known/openid-configuration")
+                .get(|request: Request<Codes>| async move {
+                    let issuer = issuer(&request);
+
+                    Ok(json!({
+                        "issuer": issuer,
+                        "authorization_endpoint": format!("{}/authorize", issuer),
+                        "token_endpoint": format!("{}/token", issuer),
+                        "jwks_uri": format!("{}/certs", issuer),
+                    }))
+                });
+            app.at("/certs").get(|_| async {
+                Ok(Response::builder(200)
+                    .body(include_str!("../test/keys/jwks.json"))
+                    .content_type(tide::http::mime::JSON)
+                    .build())
+            });
+            app.at("/token").post(|mut request: Request<Codes>| async move {
+                let token_request: TokenRequest = request.body_form().await?;
+                let issued = request.state().lock().unwrap().remove(&token_request.code);
+                let challenge = base64::encode_config(
+                    Sha256::digest(token_request.code_verifier.as_bytes()),
+                    base64::URL_SAFE_NO_PAD,
+                );
+
+                match issued {
+                    Some((code_challenge, nonce, email))
+                        if code_challenge == challenge
+                            && token_request.grant_type == "authorization_code"
+                            && token_request.client_id == CLIENT_ID =>
+                    {
+                        let id_token = sign(json!({
+                            "iss": issuer(&request),
+                            "aud": CLIENT_ID,
+                            "exp": now() + 3600,
+                            "email": email,
+                            "email_verified": true,
+                            "nonce": nonce,
+                        }));
+
+                        Ok(Response::builder(200)
+                            .body(json!({ "access_token": "access", "token_type": "Bearer", "id_token": id_token }))
+                            .build())
+                    }
+                    _ => Ok(Response::builder(400)
+                        .body(json!({ "error": "invalid_grant" }))
+                        .build()),
+                }
+            });
+
+            let mut listener = app.bind("127.0.0.1:0").await.unwrap();
+            let url = listener.info()[0].connection().to_string();
+            tokio::spawn(async move { listener.accept().await });
+
+            Self { url, codes }
+        }
+
+        fn discovery_url(&self) -> String {
+            format!("{}/.well-known/openid-configuration", self.url)
+        }
+
+        /// Plays the user signing in as `email` at `authorization_url` and returns the query of
+        /// the redirect back.
+        fn login(&self, authorization_url: &str, email: &str) -> String {
+            let url = Url::parse(authorization_url).unwrap();
+            let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+            assert_eq!(params["response_type"], "code");
+            assert_eq!(params["client_id"], CLIENT_ID);
+            assert_eq!(params["redirect_uri"], REDIRECT_URI);
+            assert_eq!(params["code_challenge_method"], "S256");
+
+            let code = format!("code-{}", params["state"]);
+            self.codes.lock().unwrap().insert(
+                code.clone(),
+                (
+                    params["code_challenge"].clone(),
+                    params["nonce"].clone(),
+                    email.to_string(),
+                ),
+            );
+
+            format!("code={}&state={}", code, params["state"])
+        }
+    }
+
+    fn issuer(request: &Request<Codes>) -> String {
+        request.url().origin().ascii_serialization()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn sign(claims: serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key".to_string());
+        let key = EncodingKey::from_rsa_pem(include_bytes!("../test/keys/private.pem")).unwrap();
+
+        encode(&header, &claims, &key).unwrap()
+    }
+
+    async fn provider(idp: &MockIdentityProvider, user_store: Arc<MemDB>) -> OidcSignupProvider {
+        OidcSignupProvider::discover(user_store, &idp.discovery_url(), CLIENT_ID)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_discover() {
+        let idp = MockIdentityProvider::start().await;
+        let provider = provider(&idp, Arc::new(MemDB::default())).await;
+
+        assert_eq!(provider.metadata().issuer, idp.url);
+        assert_eq!(
+            provider.metadata().token_endpoint,
+            format!("{}/token", idp.url)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discover_network_error() {
+        let result = OidcSignupProvider::discover(
+            Arc::new(MemDB::default()),
+            "http://127.0.0.1:1/.well-known/openid-configuration",
+            CLIENT_ID,
+        )
+        .await;
+
+        assert_eq!(result.err(), Some(Error::NewtorkError));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_creates_user() {
+        let idp = MockIdentityProvider::start().await;
+        let memdb = Arc::new(MemDB::default());
+        let provider = provider(&idp, memdb.clone()).await;
+
+        let authorization = provider.authorize(REDIRECT_URI).unwrap();
+        let callback = idp.login(&authorization.url, "test@mail.com");
+        let user = provider
+            .confirm_bound(&callback, &authorization.binding, "test")
+            .await
+            .unwrap();
+
+        assert_eq!(user, User::new("test@mail.com", "test"));
+        assert_eq!(memdb.find_user("test").await.unwrap(), Some(user));
+    }
+
+    #[tokio::test]
+    async fn test_signup_existing_user() {
+        let idp = MockIdentityProvider::start().await;
+        let memdb = Arc::new(MemDB::default());
+        let user = memdb
+            .save_user(User::new("test@mail.com", "test"))
+            .await
+            .unwrap();
+        let provider = provider(&idp, memdb).await;
+
+        let authorization = provider.authorize(REDIRECT_URI).unwrap();
+        let callback = idp.login(&authorization.url, "test@mail.com");
+
+        assert_eq!(
+            provider
+                .signup_bound(&callback, &authorization.binding)
+                .await,
+            Ok(user)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signup_unknown_user_then_confirm() {
+        let idp = MockIdentityProvider::start().await;
+        let memdb = Arc::new(MemDB::default());
+        memdb
+            .save_user(User::new("taken@mail.com", "taken"))
+            .await
+            .unwrap();
+        let provider = provider(&idp, memdb).await;
+
+        let authorization = provider.authorize(REDIRECT_URI).unwrap();
+        let binding = authorization.binding;
+        let callback = idp.login(&authorization.url, "test@mail.com");
+
+        assert_eq!(
+            provider.signup_bound(&callback, &binding).await,
+            Err(Error::SignupError)
+        );
+        // the code was redeemed already, the verified email is kept for the confirmation, but
+        // only for the holder of the binding
+        assert_eq!(
+            provider.confirm_bound(&callback, "forged", "test").await,
+            Err(Error::SignupError)
+        );
+        assert!(provider
+            .confirm_bound(&callback, &binding, "taken")
+            .await
+            .is_err());
+        assert_eq!(
+            provider.confirm_bound(&callback, &binding, "test").await,
+            Ok(User::new("test@mail.com", "test"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_callback_used_once() {
+        let idp = MockIdentityProvider::start().await;
+        let provider = provider(&idp, Arc::new(MemDB::default())).await;
+
+        let authorization = provider.authorize(REDIRECT_URI).unwrap();
+        let callback = idp.login(&authorization.url, "test@mail.com");
+
+        provider
+            .confirm_bound(&callback, &authorization.binding, "test")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            provider
+                .signup_bound(&callback, &authorization.binding)
+                .await,
+            Err(Error::SignupError)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_callback_needs_binding() {
+        let idp = MockIdentityProvider::start().await;
+        let provider = provider(&idp, Arc::new(MemDB::default())).await;
+
+        let authorization = provider.authorize(REDIRECT_URI).unwrap();
+        let callback = idp.login(&authorization.url, "test@mail.com");
+
+        assert_eq!(provider.signup(&callback).await, Err(Error::SignupError));
+        assert_eq!(
+            provider.confirm(&callback, "test").await,
+            Err(Error::SignupError)
+        );
+        assert_eq!(
+            provider.confirm_bound(&callback, "forged", "test").await,
+            Err(Error::SignupError)
+        );
+        // a wrong binding doesn't use up the callback
+        assert_eq!(
+            provider
+                .confirm_bound(&callback, &authorization.binding, "test")
+                .await,
+            Ok(User::new("test@mail.com", "test"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_state() {
+        let idp = MockIdentityProvider::start().await;
+        let provider = provider(&idp, Arc::new(MemDB::default())).await;
+
+        assert_eq!(
+            provider
+                .confirm_bound("code=code&state=state", "binding", "test")
+                .await,
+            Err(Error::SignupError)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_code() {
+        let idp = MockIdentityProvider::start().await;
+        let provider = provider(&idp, Arc::new(MemDB::default())).await;
+
+        let authorization = provider.authorize(REDIRECT_URI).unwrap();
+        let url = Url::parse(&authorization.url).unwrap();
+        let (_, state) = url.query_pairs().find(|(name, _)| name == "state").unwrap();
+        let callback = format!("code=forged&state={}", state);
+
+        assert_eq!(
+            provider
+                .confirm_bound(&callback, &authorization.binding, "test")
+                .await,
+            Err(Error::SignupError)
+        );
+    }
+}
@@ -0,0 +1,22 @@
+CREATE TABLE users (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL
+);
+
+CREATE TABLE threads (
+    id BIGSERIAL PRIMARY KEY,
+    hash TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE comments (
+    id BIGSERIAL PRIMARY KEY,
+    hash TEXT NOT NULL UNIQUE,
+    thread_hash TEXT NOT NULL REFERENCES threads (hash) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    date NUMERIC(39, 0) NOT NULL,
+    content TEXT NOT NULL
+);
+
+CREATE INDEX comments_thread_hash_idx ON comments (thread_hash, id);
@@ -0,0 +1,4 @@
+-- replies reference their parent, deleting a comment deletes the replies below it
+ALTER TABLE comments ADD COLUMN parent_hash TEXT REFERENCES comments (hash) ON DELETE CASCADE;
+
+CREATE INDEX comments_parent_hash_idx ON comments (parent_hash, id);
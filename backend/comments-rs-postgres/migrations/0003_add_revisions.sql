@@ -0,0 +1,11 @@
+ALTER TABLE comments ADD COLUMN edited_at NUMERIC(39, 0);
+
+-- former contents of edited comments
+CREATE TABLE revisions (
+    id BIGSERIAL PRIMARY KEY,
+    comment_hash TEXT NOT NULL REFERENCES comments (hash) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    date NUMERIC(39, 0) NOT NULL
+);
+
+CREATE INDEX revisions_comment_hash_idx ON revisions (comment_hash, id);
@@ -0,0 +1,4 @@
+-- comments deleted while having replies stay as tombstone, marked by who deleted them, when and why
+ALTER TABLE comments ADD COLUMN deleted_by TEXT;
+ALTER TABLE comments ADD COLUMN deleted_reason TEXT;
+ALTER TABLE comments ADD COLUMN deleted_at NUMERIC(39, 0);
@@ -0,0 +1,14 @@
+ALTER TABLE comments ADD COLUMN shadowed BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- bans target a user name, an email domain or an ip range
+CREATE TABLE bans (
+    id BIGSERIAL PRIMARY KEY,
+    ban_id TEXT NOT NULL UNIQUE,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    shadow BOOLEAN NOT NULL,
+    reason TEXT,
+    banned_by TEXT NOT NULL,
+    date NUMERIC(39, 0) NOT NULL,
+    expires_at NUMERIC(39, 0)
+);
@@ -0,0 +1,9 @@
+-- every reader can report a comment once, reporting it again replaces the former report
+CREATE TABLE reports (
+    id BIGSERIAL PRIMARY KEY,
+    comment_hash TEXT NOT NULL REFERENCES comments (hash) ON DELETE CASCADE,
+    reporter TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    date NUMERIC(39, 0) NOT NULL,
+    UNIQUE (comment_hash, reporter)
+);
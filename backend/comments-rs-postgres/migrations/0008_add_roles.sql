@@ -0,0 +1,8 @@
+-- roles apply site wide or in a single thread, scopes are kept as `site` or `thread:<hash>`
+CREATE TABLE roles (
+    id BIGSERIAL PRIMARY KEY,
+    user_name TEXT NOT NULL REFERENCES users (name) ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    UNIQUE (user_name, scope)
+);
@@ -1,8 +1,1159 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
+use std::str::FromStr;
+
+use comments_rs_core_backend::{
+    data::{
+        AuditEntry, Ban, BanTarget, Comment, CommentStatus, CommentTree, Deletion, Report,
+        Revision, RoleGrant, Scope, Thread, User,
+    },
+    error::StoreError,
+    events::{CommentEvent, EventBus, EventStream},
+    traits::{
+        AuditStore, BanStore, CommentStore, ReportStore, RoleStore, StoreResult, ThreadStore,
+        UserStore,
+    },
+};
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    postgres::{PgConnectOptions, PgPoolOptions},
+    FromRow, PgPool,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Columns read into a [`CommentRow`], dates are cast to text since they exceed `i64`.
+macro_rules! comment_columns {
+    () => {
+        "thread_hash, email, hash, parent_hash, CAST(date AS TEXT) AS date, content, \
+         CAST(edited_at AS TEXT) AS edited_at, deleted_by, deleted_reason, \
+         CAST(deleted_at AS TEXT) AS deleted_at, status, shadowed"
+    };
+}
+
+/// Columns read into a [`BanRow`].
+macro_rules! ban_columns {
+    () => {
+        "ban_id, kind, value, shadow, reason, banned_by, CAST(date AS TEXT) AS date, \
+         CAST(expires_at AS TEXT) AS expires_at"
+    };
+}
+
+type ThreadRow = (String, String, Option<bool>, bool);
+
+#[derive(FromRow)]
+struct CommentRow {
+    thread_hash: String,
+    email: String,
+    hash: String,
+    parent_hash: Option<String>,
+    date: String,
+    content: String,
+    edited_at: Option<String>,
+    deleted_by: Option<String>,
+    deleted_reason: Option<String>,
+    deleted_at: Option<String>,
+    status: String,
+    shadowed: bool,
+}
+
+#[derive(FromRow)]
+struct ReportedCommentRow {
+    #[sqlx(flatten)]
+    comment: CommentRow,
+    report_count: i64,
+}
+
+#[derive(FromRow)]
+struct BanRow {
+    ban_id: String,
+    kind: String,
+    value: String,
+    shadow: bool,
+    reason: Option<String>,
+    banned_by: String,
+    date: String,
+    expires_at: Option<String>,
+}
+
+pub struct PostgresStore {
+    pool: PgPool,
+    events: EventBus,
+}
+
+impl PostgresStore {
+    pub async fn connect(url: &str) -> Result<Self, StoreError> {
+        Self::connect_with(PgConnectOptions::from_str(url).map_err(map_err)?).await
+    }
+
+    pub async fn connect_with(options: PgConnectOptions) -> Result<Self, StoreError> {
+        let pool = PgPoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(map_err)?;
+
+        Self::new(pool).await
+    }
+
+    /// Wraps an existing pool, applying all pending migrations first.
+    pub async fn new(pool: PgPool) -> Result<Self, StoreError> {
+        MIGRATOR.run(&pool).await.map_err(map_migrate_err)?;
+
+        Ok(Self {
+            pool,
+            events: EventBus::default(),
+        })
+    }
+}
+
+impl UserStore for PostgresStore {
+    fn save_user(&self, user: User) -> StoreResult<User> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            sqlx::query("INSERT INTO users (name, email) VALUES ($1, $2)")
+                .bind(&user.name)
+                .bind(&user.email)
+                .execute(&pool)
+                .await
+                .map_err(map_user_err)?;
+
+            Ok(user)
+        })
+    }
+
+    fn delete_user(&self, name: &str) -> StoreResult<Option<User>> {
+        let pool = self.pool.clone();
+        let name = name.to_string();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, (String, String)>(
+                "DELETE FROM users WHERE name = $1 RETURNING email, name",
+            )
+            .bind(name)
+            .fetch_optional(&pool)
+            .await
+            .map(|row| row.map(to_user))
+            .map_err(map_err)
+        })
+    }
+
+    fn find_user(&self, name: &str) -> StoreResult<Option<User>> {
+        let pool = self.pool.clone();
+        let name = name.to_string();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, (String, String)>("SELECT email, name FROM users WHERE name = $1")
+                .bind(name)
+                .fetch_optional(&pool)
+                .await
+                .map(|row| row.map(to_user))
+                .map_err(map_err)
+        })
+    }
+
+    fn find_user_by_email(&self, email: &str) -> StoreResult<Option<User>> {
+        let pool = self.pool.clone();
+        let email = email.to_string();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, (String, String)>("SELECT email, name FROM users WHERE email = $1")
+                .bind(email)
+                .fetch_optional(&pool)
+                .await
+                .map(|row| row.map(to_user))
+                .map_err(map_err)
+        })
+    }
+
+    fn find_all_users(&self) -> StoreResult<Vec<User>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, (String, String)>("SELECT email, name FROM users ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .map(|rows| rows.into_iter().map(to_user).collect())
+                .map_err(map_err)
+        })
+    }
+}
+
+impl ThreadStore for PostgresStore {
+    fn save_thread(&self, thread: Thread) -> StoreResult<Thread> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let mut transaction = pool.begin().await.map_err(map_err)?;
+
+            sqlx::query(
+                "INSERT INTO threads (hash, name, require_approval, locked) \
+                 VALUES ($1, $2, $3, $4) \
+                 ON CONFLICT (hash) DO NOTHING",
+            )
+            .bind(&thread.hash)
+            .bind(&thread.name)
+            .bind(thread.require_approval)
+            .bind(thread.locked)
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+
+            let row = sqlx::query_as::<_, ThreadRow>(
+                "SELECT hash, name, require_approval, locked FROM threads WHERE hash = $1",
+            )
+            .bind(&thread.hash)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+
+            transaction.commit().await.map_err(map_err)?;
+
+            Ok(to_thread(row))
+        })
+    }
+
+    fn update_thread(&self, thread: Thread) -> StoreResult<Option<Thread>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, ThreadRow>(
+                "UPDATE threads SET require_approval = $1, locked = $2 WHERE hash = $3 \
+                 RETURNING hash, name, require_approval, locked",
+            )
+            .bind(thread.require_approval)
+            .bind(thread.locked)
+            .bind(&thread.hash)
+            .fetch_optional(&pool)
+            .await
+            .map(|row| row.map(to_thread))
+            .map_err(map_err)
+        })
+    }
+
+    fn delete_thread(&self, hash: &str) -> StoreResult<Option<Thread>> {
+        let pool = self.pool.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, ThreadRow>(
+                "DELETE FROM threads WHERE hash = $1 \
+                 RETURNING hash, name, require_approval, locked",
+            )
+            .bind(hash)
+            .fetch_optional(&pool)
+            .await
+            .map(|row| row.map(to_thread))
+            .map_err(map_err)
+        })
+    }
+
+    fn find_thread_by_hash(&self, hash: &str) -> StoreResult<Option<Thread>> {
+        let pool = self.pool.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, ThreadRow>(
+                "SELECT hash, name, require_approval, locked FROM threads WHERE hash = $1",
+            )
+            .bind(hash)
+            .fetch_optional(&pool)
+            .await
+            .map(|row| row.map(to_thread))
+            .map_err(map_err)
+        })
+    }
+
+    fn find_all_threads(&self) -> StoreResult<Vec<Thread>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            sqlx::query_as::<_, ThreadRow>(
+                "SELECT hash, name, require_approval, locked FROM threads ORDER BY id",
+            )
+            .fetch_all(&pool)
+            .await
+            .map(|rows| rows.into_iter().map(to_thread).collect())
+            .map_err(map_err)
+        })
+    }
+}
+
+impl CommentStore for PostgresStore {
+    fn save_comment(&self, comment: Comment) -> StoreResult<Comment> {
+        let pool = self.pool.clone();
+        let events = self.events.clone();
+
+        Box::pin(async move {
+            let mut transaction = pool.begin().await.map_err(map_err)?;
+
+            if let Some(parent_hash) = &comment.parent_hash {
+                let (thread_exists, parent_exists) = sqlx::query_as::<_, (bool, bool)>(
+                    "SELECT EXISTS (SELECT 1 FROM threads WHERE hash = $1), \
+                     EXISTS (SELECT 1 FROM comments WHERE hash = $2 AND thread_hash = $1)",
+                )
+                .bind(&comment.thread_hash)
+                .bind(parent_hash)
+                .fetch_one(&mut *transaction)
+                .await
+                .map_err(map_err)?;
+
+                if !thread_exists {
+                    return Err(StoreError::ThreadNotExists(comment.thread_hash));
+                }
+                if !parent_exists {
+                    return Err(StoreError::ParentNotExists(parent_hash.clone()));
+                }
+            }
+
+            sqlx::query(
+                "INSERT INTO comments \
+                 (hash, thread_hash, email, parent_hash, date, content, status, shadowed) \
+                 VALUES ($1, $2, $3, $4, CAST($5 AS NUMERIC), $6, $7, $8)",
+            )
+            .bind(&comment.hash)
+            .bind(&comment.thread_hash)
+            .bind(&comment.email)
+            .bind(&comment.parent_hash)
+            .bind(comment.date.to_string())
+            .bind(&comment.content)
+            .bind(comment.status.as_str())
+            .bind(comment.shadowed)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => {
+                    StoreError::ThreadNotExists(comment.thread_hash.clone())
+                }
+                e => map_err(e),
+            })?;
+            transaction.commit().await.map_err(map_err)?;
+
+            events.publish(CommentEvent::Added(comment.clone()));
+
+            Ok(comment)
+        })
+    }
+
+    fn edit_comment(&self, comment: Comment) -> StoreResult<Option<Comment>> {
+        let pool = self.pool.clone();
+        let events = self.events.clone();
+
+        Box::pin(async move {
+            let mut transaction = pool.begin().await.map_err(map_err)?;
+
+            sqlx::query(
+                "INSERT INTO revisions (comment_hash, content, date) \
+                 SELECT hash, content, COALESCE(edited_at, date) FROM comments WHERE hash = $1",
+            )
+            .bind(&comment.hash)
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+
+            let row = sqlx::query_as::<_, CommentRow>(concat!(
+                "UPDATE comments SET content = $1, edited_at = CAST($2 AS NUMERIC) \
+                 WHERE hash = $3 RETURNING ",
+                comment_columns!()
+            ))
+            .bind(&comment.content)
+            .bind(comment.edited_at.map(|edited_at| edited_at.to_string()))
+            .bind(&comment.hash)
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+
+            transaction.commit().await.map_err(map_err)?;
+
+            let comment = row.map(to_comment).transpose()?;
+
+            if let Some(comment) = &comment {
+                events.publish(CommentEvent::Edited(comment.clone()));
+            }
+
+            Ok(comment)
+        })
+    }
+
+    fn delete_comment(&self, hash: &str) -> StoreResult<Option<Comment>> {
+        let pool = self.pool.clone();
+        let events = self.events.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            let row = sqlx::query_as::<_, CommentRow>(concat!(
+                "DELETE FROM comments WHERE hash = $1 RETURNING ",
+                comment_columns!()
+            ))
+            .bind(hash)
+            .fetch_optional(&pool)
+            .await
+            .map_err(map_err)?;
+
+            let comment = row.map(to_comment).transpose()?;
+
+            if let Some(comment) = &comment {
+                events.publish(CommentEvent::Deleted(comment.clone()));
+            }
+
+            Ok(comment)
+        })
+    }
+
+    fn soft_delete_comment(&self, hash: &str, deletion: Deletion) -> StoreResult<Option<Comment>> {
+        let pool = self.pool.clone();
+        let events = self.events.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            let mut transaction = pool.begin().await.map_err(map_err)?;
+
+            let (has_replies,) = sqlx::query_as::<_, (bool,)>(
+                "SELECT EXISTS (SELECT 1 FROM comments WHERE parent_hash = $1)",
+            )
+            .bind(&hash)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+
+            let row = if has_replies {
+                sqlx::query_as::<_, CommentRow>(concat!(
+                    "UPDATE comments \
+                     SET deleted_by = $1, deleted_reason = $2, deleted_at = CAST($3 AS NUMERIC) \
+                     WHERE hash = $4 RETURNING ",
+                    comment_columns!()
+                ))
+                .bind(&deletion.deleted_by)
+                .bind(&deletion.reason)
+                .bind(deletion.date.to_string())
+                .bind(&hash)
+                .fetch_optional(&mut *transaction)
+                .await
+            } else {
+                sqlx::query_as::<_, CommentRow>(concat!(
+                    "DELETE FROM comments WHERE hash = $1 RETURNING ",
+                    comment_columns!()
+                ))
+                .bind(&hash)
+                .fetch_optional(&mut *transaction)
+                .await
+            }
+            .map_err(map_err)?;
+
+            transaction.commit().await.map_err(map_err)?;
+
+            let comment = row.map(to_comment).transpose()?.map(|comment| Comment {
+                deletion: Some(deletion),
+                ..comment
+            });
+
+            if let Some(comment) = &comment {
+                events.publish(CommentEvent::Deleted(comment.clone()));
+            }
+
+            Ok(comment)
+        })
+    }
+
+    fn restore_comment(&self, hash: &str) -> StoreResult<Option<Comment>> {
+        let pool = self.pool.clone();
+        let events = self.events.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            let row = sqlx::query_as::<_, CommentRow>(concat!(
+                "UPDATE comments SET deleted_by = NULL, deleted_reason = NULL, deleted_at = NULL \
+                 WHERE hash = $1 AND deleted_at IS NOT NULL RETURNING ",
+                comment_columns!()
+            ))
+            .bind(hash)
+            .fetch_optional(&pool)
+            .await
+            .map_err(map_err)?;
+
+            let comment = row.map(to_comment).transpose()?;
+
+            if let Some(comment) = &comment {
+                events.publish(CommentEvent::Edited(comment.clone()));
+            }
+
+            Ok(comment)
+        })
+    }
+
+    fn set_comment_status(
+        &self,
+        hash: &str,
+        status: CommentStatus,
+    ) -> StoreResult<Option<Comment>> {
+        let pool = self.pool.clone();
+        let events = self.events.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            let mut transaction = pool.begin().await.map_err(map_err)?;
+
+            let previous = sqlx::query_as::<_, (String,)>(
+                "SELECT status FROM comments WHERE hash = $1 FOR UPDATE",
+            )
+            .bind(&hash)
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+            let previous = match previous {
+                Some((previous,)) => previous
+                    .parse::<CommentStatus>()
+                    .map_err(StoreError::DatabaseError)?,
+                None => return Ok(None),
+            };
+
+            let row = sqlx::query_as::<_, CommentRow>(concat!(
+                "UPDATE comments SET status = $1 WHERE hash = $2 RETURNING ",
+                comment_columns!()
+            ))
+            .bind(status.as_str())
+            .bind(hash)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+            let comment = to_comment(row)?;
+
+            // approving settles the reports so far, later ones count from zero
+            if status == CommentStatus::Approved {
+                sqlx::query("DELETE FROM reports WHERE comment_hash = $1")
+                    .bind(&comment.hash)
+                    .execute(&mut *transaction)
+                    .await
+                    .map_err(map_err)?;
+            }
+
+            transaction.commit().await.map_err(map_err)?;
+
+            events.publish(CommentEvent::StatusChanged {
+                comment: comment.clone(),
+                previous,
+            });
+
+            Ok(Some(comment))
+        })
+    }
+
+    fn find_comment(&self, hash: &str) -> StoreResult<Option<Comment>> {
+        let pool = self.pool.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            let row = sqlx::query_as::<_, CommentRow>(concat!(
+                "SELECT ",
+                comment_columns!(),
+                " FROM comments WHERE hash = $1"
+            ))
+            .bind(hash)
+            .fetch_optional(&pool)
+            .await
+            .map_err(map_err)?;
+
+            row.map(to_comment).transpose()
+        })
+    }
+
+    fn find_revisions(&self, hash: &str) -> StoreResult<Vec<Revision>> {
+        let pool = self.pool.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (String, String, String)>(
+                "SELECT comment_hash, content, CAST(date AS TEXT) FROM revisions \
+                 WHERE comment_hash = $1 ORDER BY id",
+            )
+            .bind(hash)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_revision).collect()
+        })
+    }
+
+    fn find_thread_comments(&self, thread_hash: &str) -> StoreResult<Vec<Comment>> {
+        let pool = self.pool.clone();
+        let thread_hash = thread_hash.to_string();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, CommentRow>(concat!(
+                "SELECT ",
+                comment_columns!(),
+                " FROM comments WHERE thread_hash = $1 ORDER BY id"
+            ))
+            .bind(thread_hash)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_comment).collect()
+        })
+    }
+
+    fn count_thread_comments(&self, thread_hash: &str, viewer: Option<&str>) -> StoreResult<usize> {
+        let pool = self.pool.clone();
+        let thread_hash = thread_hash.to_string();
+        let viewer = viewer.map(|viewer| viewer.to_string());
+
+        Box::pin(async move {
+            let (count,) = sqlx::query_as::<_, (i64,)>(
+                "SELECT COUNT(*) FROM comments WHERE thread_hash = $1 AND status = 'approved' \
+                 AND (NOT shadowed OR email = $2)",
+            )
+            .bind(thread_hash)
+            .bind(viewer)
+            .fetch_one(&pool)
+            .await
+            .map_err(map_err)?;
+
+            Ok(count as usize)
+        })
+    }
+
+    fn find_thread_comments_page(
+        &self,
+        thread_hash: &str,
+        viewer: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Comment>> {
+        let pool = self.pool.clone();
+        let thread_hash = thread_hash.to_string();
+        let viewer = viewer.map(|viewer| viewer.to_string());
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, CommentRow>(concat!(
+                "SELECT ",
+                comment_columns!(),
+                " FROM comments WHERE thread_hash = $1 AND status = 'approved' \
+                 AND (NOT shadowed OR email = $2) ORDER BY id LIMIT $3 OFFSET $4"
+            ))
+            .bind(thread_hash)
+            .bind(viewer)
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_comment).collect()
+        })
+    }
+
+    fn count_comments_by_status(&self, status: CommentStatus) -> StoreResult<usize> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let (count,) =
+                sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM comments WHERE status = $1")
+                    .bind(status.as_str())
+                    .fetch_one(&pool)
+                    .await
+                    .map_err(map_err)?;
+
+            Ok(count as usize)
+        })
+    }
+
+    fn find_comments_by_status(
+        &self,
+        status: CommentStatus,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<Comment>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, CommentRow>(concat!(
+                "SELECT ",
+                comment_columns!(),
+                " FROM comments WHERE status = $1 ORDER BY id LIMIT $2 OFFSET $3"
+            ))
+            .bind(status.as_str())
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_comment).collect()
+        })
+    }
+
+    fn find_replies(&self, parent_hash: &str) -> StoreResult<Vec<Comment>> {
+        let pool = self.pool.clone();
+        let parent_hash = parent_hash.to_string();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, CommentRow>(concat!(
+                "SELECT ",
+                comment_columns!(),
+                " FROM comments WHERE parent_hash = $1 ORDER BY id"
+            ))
+            .bind(parent_hash)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_comment).collect()
+        })
+    }
+
+    fn find_reply_tree(&self, hash: &str) -> StoreResult<Option<CommentTree>> {
+        let pool = self.pool.clone();
+        let hash = hash.to_string();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, CommentRow>(concat!(
+                "WITH RECURSIVE tree (tree_id, tree_hash) AS ( \
+                     SELECT id, hash FROM comments WHERE hash = $1 \
+                     UNION ALL \
+                     SELECT id, hash FROM comments JOIN tree ON parent_hash = tree_hash \
+                 ) \
+                 SELECT ",
+                comment_columns!(),
+                " FROM comments JOIN tree ON id = tree_id ORDER BY id"
+            ))
+            .bind(&hash)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+            let comments = rows
+                .into_iter()
+                .map(to_comment)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(CommentTree::build(&hash, comments))
+        })
     }
+
+    fn subscribe(&self) -> EventStream {
+        self.events.subscribe()
+    }
+}
+
+impl RoleStore for PostgresStore {
+    fn save_role(&self, grant: RoleGrant) -> StoreResult<RoleGrant> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            sqlx::query(
+                "INSERT INTO roles (user_name, role, scope) VALUES ($1, $2, $3) \
+                 ON CONFLICT (user_name, scope) DO UPDATE SET role = excluded.role",
+            )
+            .bind(&grant.user_name)
+            .bind(grant.role.as_str())
+            .bind(grant.scope.to_string())
+            .execute(&pool)
+            .await
+            .map_err(map_err)?;
+
+            Ok(grant)
+        })
+    }
+
+    fn delete_role(&self, user_name: &str, scope: &Scope) -> StoreResult<Option<RoleGrant>> {
+        let pool = self.pool.clone();
+        let user_name = user_name.to_string();
+        let scope = scope.to_string();
+
+        Box::pin(async move {
+            let row = sqlx::query_as::<_, (String, String, String)>(
+                "DELETE FROM roles WHERE user_name = $1 AND scope = $2 \
+                 RETURNING user_name, role, scope",
+            )
+            .bind(user_name)
+            .bind(scope)
+            .fetch_optional(&pool)
+            .await
+            .map_err(map_err)?;
+
+            row.map(to_role_grant).transpose()
+        })
+    }
+
+    fn find_roles(&self, user_name: &str) -> StoreResult<Vec<RoleGrant>> {
+        let pool = self.pool.clone();
+        let user_name = user_name.to_string();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (String, String, String)>(
+                "SELECT user_name, role, scope FROM roles WHERE user_name = $1 ORDER BY id",
+            )
+            .bind(user_name)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_role_grant).collect()
+        })
+    }
+
+    fn find_all_roles(&self) -> StoreResult<Vec<RoleGrant>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (String, String, String)>(
+                "SELECT user_name, role, scope FROM roles ORDER BY id",
+            )
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_role_grant).collect()
+        })
+    }
+}
+
+impl AuditStore for PostgresStore {
+    fn append_audit_entry(&self, entry: AuditEntry) -> StoreResult<AuditEntry> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            sqlx::query(
+                "INSERT INTO audit_log (action, actor, target, reason, date) \
+                 VALUES ($1, $2, $3, $4, CAST($5 AS NUMERIC))",
+            )
+            .bind(entry.action.as_str())
+            .bind(&entry.actor)
+            .bind(&entry.target)
+            .bind(&entry.reason)
+            .bind(entry.date.to_string())
+            .execute(&pool)
+            .await
+            .map_err(map_err)?;
+
+            Ok(entry)
+        })
+    }
+
+    fn count_audit_entries(&self, target: Option<&str>) -> StoreResult<usize> {
+        let pool = self.pool.clone();
+        let target = target.map(|target| target.to_string());
+
+        Box::pin(async move {
+            let (count,) = sqlx::query_as::<_, (i64,)>(
+                "SELECT COUNT(*) FROM audit_log WHERE $1::TEXT IS NULL OR target = $1",
+            )
+            .bind(target)
+            .fetch_one(&pool)
+            .await
+            .map_err(map_err)?;
+
+            Ok(count as usize)
+        })
+    }
+
+    fn find_audit_entries(
+        &self,
+        target: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<AuditEntry>> {
+        let pool = self.pool.clone();
+        let target = target.map(|target| target.to_string());
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (String, String, String, Option<String>, String)>(
+                "SELECT action, actor, target, reason, CAST(date AS TEXT) FROM audit_log \
+                 WHERE $1::TEXT IS NULL OR target = $1 ORDER BY id DESC LIMIT $2 OFFSET $3",
+            )
+            .bind(target)
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_audit_entry).collect()
+        })
+    }
+}
+
+impl ReportStore for PostgresStore {
+    fn save_report(&self, report: Report) -> StoreResult<usize> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let mut transaction = pool.begin().await.map_err(map_err)?;
+
+            sqlx::query(
+                "INSERT INTO reports (comment_hash, reporter, reason, date) \
+                 VALUES ($1, $2, $3, CAST($4 AS NUMERIC)) ON CONFLICT (comment_hash, reporter) \
+                 DO UPDATE SET reason = excluded.reason, date = excluded.date",
+            )
+            .bind(&report.comment_hash)
+            .bind(&report.reporter)
+            .bind(&report.reason)
+            .bind(report.date.to_string())
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_err)?;
+
+            let (count,) =
+                sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM reports WHERE comment_hash = $1")
+                    .bind(&report.comment_hash)
+                    .fetch_one(&mut *transaction)
+                    .await
+                    .map_err(map_err)?;
+
+            transaction.commit().await.map_err(map_err)?;
+
+            Ok(count as usize)
+        })
+    }
+
+    fn find_reports(&self, comment_hash: &str) -> StoreResult<Vec<Report>> {
+        let pool = self.pool.clone();
+        let comment_hash = comment_hash.to_string();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (String, String, String, String)>(
+                "SELECT comment_hash, reporter, reason, CAST(date AS TEXT) FROM reports \
+                 WHERE comment_hash = $1 ORDER BY id",
+            )
+            .bind(comment_hash)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_report).collect()
+        })
+    }
+
+    fn count_reported_comments(&self) -> StoreResult<usize> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let (count,) = sqlx::query_as::<_, (i64,)>(
+                "SELECT COUNT(*) FROM comments WHERE hash IN (SELECT comment_hash FROM reports)",
+            )
+            .fetch_one(&pool)
+            .await
+            .map_err(map_err)?;
+
+            Ok(count as usize)
+        })
+    }
+
+    fn find_reported_comments(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> StoreResult<Vec<(Comment, usize)>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, ReportedCommentRow>(concat!(
+                "SELECT ",
+                comment_columns!(),
+                ", report_count FROM comments JOIN ( \
+                     SELECT comment_hash, COUNT(*) AS report_count, MIN(id) AS first_report \
+                     FROM reports GROUP BY comment_hash \
+                 ) counts ON comment_hash = hash \
+                 ORDER BY report_count DESC, first_report LIMIT $1 OFFSET $2"
+            ))
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter()
+                .map(|row| Ok((to_comment(row.comment)?, row.report_count as usize)))
+                .collect()
+        })
+    }
+}
+
+impl BanStore for PostgresStore {
+    fn save_ban(&self, ban: Ban) -> StoreResult<Ban> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            sqlx::query(
+                "INSERT INTO bans \
+                 (ban_id, kind, value, shadow, reason, banned_by, date, expires_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, CAST($7 AS NUMERIC), CAST($8 AS NUMERIC))",
+            )
+            .bind(&ban.id)
+            .bind(ban.target.kind())
+            .bind(ban.target.value())
+            .bind(ban.shadow)
+            .bind(&ban.reason)
+            .bind(&ban.banned_by)
+            .bind(ban.date.to_string())
+            .bind(ban.expires_at.map(|expires_at| expires_at.to_string()))
+            .execute(&pool)
+            .await
+            .map_err(|error| map_ban_err(error, &ban.id))?;
+
+            Ok(ban)
+        })
+    }
+
+    fn delete_ban(&self, id: &str) -> StoreResult<Option<Ban>> {
+        let pool = self.pool.clone();
+        let id = id.to_string();
+
+        Box::pin(async move {
+            let row = sqlx::query_as::<_, BanRow>(concat!(
+                "DELETE FROM bans WHERE ban_id = $1 RETURNING ",
+                ban_columns!()
+            ))
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(map_err)?;
+
+            row.map(to_ban).transpose()
+        })
+    }
+
+    fn find_all_bans(&self) -> StoreResult<Vec<Ban>> {
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, BanRow>(concat!(
+                "SELECT ",
+                ban_columns!(),
+                " FROM bans ORDER BY id"
+            ))
+            .fetch_all(&pool)
+            .await
+            .map_err(map_err)?;
+
+            rows.into_iter().map(to_ban).collect()
+        })
+    }
+}
+
+fn to_user((email, name): (String, String)) -> User {
+    User { email, name }
+}
+
+fn to_thread((hash, name, require_approval, locked): ThreadRow) -> Thread {
+    Thread {
+        name,
+        hash,
+        require_approval,
+        locked,
+    }
+}
+
+fn to_comment(row: CommentRow) -> Result<Comment, StoreError> {
+    let deletion = match (row.deleted_by, row.deleted_at) {
+        (Some(deleted_by), Some(date)) => Some(Deletion {
+            deleted_by,
+            reason: row.deleted_reason,
+            date: parse_date(&date)?,
+        }),
+        _ => None,
+    };
+
+    Ok(Comment {
+        thread_hash: row.thread_hash,
+        email: row.email,
+        hash: row.hash,
+        parent_hash: row.parent_hash,
+        date: parse_date(&row.date)?,
+        content: row.content,
+        edited_at: row.edited_at.as_deref().map(parse_date).transpose()?,
+        deletion,
+        status: row.status.parse().map_err(StoreError::DatabaseError)?,
+        shadowed: row.shadowed,
+    })
+}
+
+fn to_role_grant(
+    (user_name, role, scope): (String, String, String),
+) -> Result<RoleGrant, StoreError> {
+    Ok(RoleGrant {
+        user_name,
+        role: role.parse().map_err(StoreError::DatabaseError)?,
+        scope: scope.parse().map_err(StoreError::DatabaseError)?,
+    })
+}
+
+fn to_audit_entry(
+    (action, actor, target, reason, date): (String, String, String, Option<String>, String),
+) -> Result<AuditEntry, StoreError> {
+    Ok(AuditEntry {
+        action: action.parse().map_err(StoreError::DatabaseError)?,
+        actor,
+        target,
+        reason,
+        date: parse_date(&date)?,
+    })
+}
+
+fn to_report(
+    (comment_hash, reporter, reason, date): (String, String, String, String),
+) -> Result<Report, StoreError> {
+    Ok(Report {
+        comment_hash,
+        reporter,
+        reason,
+        date: parse_date(&date)?,
+    })
+}
+
+fn to_ban(row: BanRow) -> Result<Ban, StoreError> {
+    Ok(Ban {
+        id: row.ban_id,
+        target: BanTarget::parse(&row.kind, &row.value).map_err(StoreError::DatabaseError)?,
+        shadow: row.shadow,
+        reason: row.reason,
+        banned_by: row.banned_by,
+        date: parse_date(&row.date)?,
+        expires_at: row.expires_at.as_deref().map(parse_date).transpose()?,
+    })
+}
+
+fn to_revision(
+    (comment_hash, content, date): (String, String, String),
+) -> Result<Revision, StoreError> {
+    Ok(Revision {
+        comment_hash,
+        content,
+        date: parse_date(&date)?,
+    })
+}
+
+fn parse_date(date: &str) -> Result<u128, StoreError> {
+    date.parse()
+        .map_err(|_| StoreError::DatabaseError(format!("Invalid comment date: {}", date)))
+}
+
+fn map_err(error: sqlx::Error) -> StoreError {
+    StoreError::DatabaseError(error.to_string())
+}
+
+fn map_user_err(error: sqlx::Error) -> StoreError {
+    match error {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+            if db.constraint() == Some("users_email_key") {
+                StoreError::EmailNotUnique
+            } else {
+                StoreError::NameNotUnique
+            }
+        }
+        error => map_err(error),
+    }
+}
+
+fn map_ban_err(error: sqlx::Error, id: &str) -> StoreError {
+    match error {
+        // the ban id is the only unique column of bans
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+            StoreError::BanNotUnique(id.to_string())
+        }
+        error => map_err(error),
+    }
+}
+
+fn map_migrate_err(error: MigrateError) -> StoreError {
+    StoreError::DatabaseError(error.to_string())
 }
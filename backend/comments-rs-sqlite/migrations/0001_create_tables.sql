@@ -0,0 +1,23 @@
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL
+);
+
+CREATE TABLE threads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    hash TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL
+);
+
+-- dates are u128 milliseconds which do not fit into sqlite integers, so they are kept as text
+CREATE TABLE comments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    hash TEXT NOT NULL UNIQUE,
+    thread_hash TEXT NOT NULL REFERENCES threads (hash) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    date TEXT NOT NULL,
+    content TEXT NOT NULL
+);
+
+CREATE INDEX comments_thread_hash_idx ON comments (thread_hash, id);
@@ -0,0 +1,11 @@
+ALTER TABLE comments ADD COLUMN edited_at TEXT;
+
+-- former contents of edited comments, dates are kept as text like those of comments
+CREATE TABLE revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    comment_hash TEXT NOT NULL REFERENCES comments (hash) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    date TEXT NOT NULL
+);
+
+CREATE INDEX revisions_comment_hash_idx ON revisions (comment_hash, id);
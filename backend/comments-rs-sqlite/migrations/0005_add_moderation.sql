@@ -0,0 +1,5 @@
+-- comments posted before moderation existed were public, so they count as approved
+ALTER TABLE comments ADD COLUMN status TEXT NOT NULL DEFAULT 'approved';
+ALTER TABLE threads ADD COLUMN require_approval BOOLEAN;
+
+CREATE INDEX comments_status_idx ON comments (status, id);
@@ -0,0 +1,15 @@
+ALTER TABLE comments ADD COLUMN shadowed BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- bans target a user name, an email domain or an ip range, dates are kept as text like those of
+-- comments
+CREATE TABLE bans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ban_id TEXT NOT NULL UNIQUE,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    shadow BOOLEAN NOT NULL,
+    reason TEXT,
+    banned_by TEXT NOT NULL,
+    date TEXT NOT NULL,
+    expires_at TEXT
+);
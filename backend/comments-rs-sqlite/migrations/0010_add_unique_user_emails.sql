@@ -0,0 +1,2 @@
+-- fails on databases holding several users with the same email, which have to be merged by hand
+CREATE UNIQUE INDEX users_email_key ON users (email);
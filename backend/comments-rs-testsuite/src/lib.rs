@@ -0,0 +1,1324 @@
+//! Conformance tests every store implementation has to pass.
+//!
+//! Each check takes a fresh, empty store. Store crates usually don't call them directly but
+//! generate one test per check with [`store_tests!`].
+
+use comments_rs_core_backend::{
+    data::{
+        AuditAction, AuditEntry, Ban, BanTarget, Comment, CommentStatus, CommentTree, Deletion,
+        Report, Revision, Role, RoleGrant, Scope, Thread, User,
+    },
+    error::StoreError,
+    events::CommentEvent,
+    traits::{AuditStore, BanStore, CommentStore, ReportStore, RoleStore, ThreadStore, UserStore},
+};
+use futures::StreamExt;
+
+/// Generates a `#[tokio::test]` for every check of this crate.
+///
+/// `$factory` is evaluated once per test and has to be a future resolving to an empty store
+/// implementing [`UserStore`], [`ThreadStore`], [`CommentStore`], [`BanStore`], [`ReportStore`],
+/// [`RoleStore`] and [`AuditStore`]. The calling crate needs `tokio` as a dev-dependency.
+///
+/// ```ignore
+/// mod conformance {
+///     comments_rs_testsuite_backend::store_tests!(async { MemDB::default() });
+/// }
+/// ```
+#[macro_export]
+macro_rules! store_tests {
+    ($factory:expr) => {
+        $crate::store_tests!($factory;
+            save_user,
+            save_user_name_not_unique,
+            save_user_email_not_unique,
+            find_user,
+            find_user_by_email,
+            find_all_users,
+            delete_user,
+            save_thread,
+            save_thread_existing,
+            find_thread_by_hash,
+            find_all_threads,
+            update_thread,
+            delete_thread,
+            delete_thread_deletes_comments,
+            save_comment,
+            save_comment_thread_not_exists,
+            save_reply,
+            save_reply_parent_not_exists,
+            edit_comment,
+            edit_comment_not_exists,
+            delete_comment,
+            delete_comment_deletes_replies,
+            soft_delete_comment,
+            soft_delete_comment_with_replies,
+            restore_comment,
+            set_comment_status,
+            find_comment,
+            find_thread_comments,
+            find_thread_comments_page,
+            find_thread_comments_page_approved_only,
+            find_thread_comments_page_shadowed,
+            find_comments_by_status,
+            find_replies,
+            find_reply_tree,
+            subscribe,
+            save_ban,
+            save_ban_not_unique,
+            delete_ban,
+            save_report,
+            find_reported_comments,
+            delete_comment_deletes_reports,
+            approve_comment_deletes_reports,
+            save_role,
+            delete_role,
+            delete_user_deletes_roles,
+            append_audit_entry,
+            find_audit_entries_by_target,
+            delete_thread_keeps_audit_entries
+        );
+    };
+    ($factory:expr; $($check:ident),+) => {
+        $(
+            #[tokio::test]
+            async fn $check() {
+                $crate::$check($factory.await).await;
+            }
+        )+
+    };
+}
+
+pub async fn save_user<S: UserStore>(store: S) {
+    let user = User::new("test@mail.com", "name");
+
+    assert_eq!(store.save_user(user.clone()).await, Ok(user));
+}
+
+pub async fn save_user_name_not_unique<S: UserStore>(store: S) {
+    let user = store
+        .save_user(User::new("test@mail.com", "name"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.save_user(User::new("test2@mail.com", "name")).await,
+        Err(StoreError::NameNotUnique)
+    );
+    assert_eq!(store.find_all_users().await.unwrap(), vec![user]);
+}
+
+pub async fn save_user_email_not_unique<S: UserStore>(store: S) {
+    let user = store
+        .save_user(User::new("test@mail.com", "name"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.save_user(User::new("test@mail.com", "name2")).await,
+        Err(StoreError::EmailNotUnique)
+    );
+    assert_eq!(store.find_all_users().await.unwrap(), vec![user]);
+}
+
+pub async fn find_user<S: UserStore>(store: S) {
+    let user = User::new("test@mail.com", "name");
+
+    store.save_user(user.clone()).await.unwrap();
+
+    assert_eq!(store.find_user("name").await.unwrap(), Some(user));
+    assert_eq!(store.find_user("name1").await.unwrap(), None);
+}
+
+pub async fn find_user_by_email<S: UserStore>(store: S) {
+    let user = User::new("test@mail.com", "name");
+
+    store.save_user(user.clone()).await.unwrap();
+
+    assert_eq!(
+        store.find_user_by_email("test@mail.com").await.unwrap(),
+        Some(user)
+    );
+    assert_eq!(
+        store.find_user_by_email("test1@mail.com").await.unwrap(),
+        None
+    );
+}
+
+pub async fn find_all_users<S: UserStore>(store: S) {
+    assert_eq!(store.find_all_users().await.unwrap(), vec![]);
+
+    let user = store
+        .save_user(User::new("test@mail.com", "name"))
+        .await
+        .unwrap();
+    let user1 = store
+        .save_user(User::new("test1@mail.com", "name1"))
+        .await
+        .unwrap();
+
+    assert_eq!(store.find_all_users().await.unwrap(), vec![user, user1]);
+}
+
+pub async fn delete_user<S: UserStore>(store: S) {
+    let user = User::new("test@mail.com", "name");
+    let user1 = User::new("test1@mail.com", "name1");
+
+    store.save_user(user.clone()).await.unwrap();
+    store.save_user(user1.clone()).await.unwrap();
+
+    assert_eq!(store.delete_user("name1").await.unwrap(), Some(user1));
+    assert_eq!(store.delete_user("name1").await.unwrap(), None);
+    assert_eq!(store.find_user("name1").await.unwrap(), None);
+    assert_eq!(store.find_all_users().await.unwrap(), vec![user]);
+}
+
+pub async fn save_thread<S: ThreadStore>(store: S) {
+    let thread = Thread::new("thread");
+
+    assert_eq!(store.save_thread(thread.clone()).await, Ok(thread));
+}
+
+pub async fn save_thread_existing<S: ThreadStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let updated = store
+        .update_thread(Thread {
+            require_approval: Some(true),
+            locked: true,
+            ..thread.clone()
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    // saving the thread again keeps the settings of the saved one
+    assert_eq!(store.save_thread(thread).await, Ok(updated.clone()));
+    assert_eq!(store.find_all_threads().await.unwrap(), vec![updated]);
+}
+
+pub async fn find_thread_by_hash<S: ThreadStore>(store: S) {
+    let thread = Thread::new("thread");
+
+    store.save_thread(thread.clone()).await.unwrap();
+
+    assert_eq!(
+        store.find_thread_by_hash(&thread.hash).await.unwrap(),
+        Some(thread)
+    );
+    assert_eq!(
+        store
+            .find_thread_by_hash(&Thread::new("thread_2").hash)
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+pub async fn find_all_threads<S: ThreadStore>(store: S) {
+    assert_eq!(store.find_all_threads().await.unwrap(), vec![]);
+
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let thread_2 = store.save_thread(Thread::new("thread_2")).await.unwrap();
+
+    assert_eq!(
+        store.find_all_threads().await.unwrap(),
+        vec![thread, thread_2]
+    );
+}
+
+pub async fn update_thread<S: ThreadStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let updated = Thread {
+        require_approval: Some(true),
+        locked: true,
+        ..thread.clone()
+    };
+
+    assert_eq!(
+        store.update_thread(updated.clone()).await,
+        Ok(Some(updated.clone()))
+    );
+    assert_eq!(
+        store.find_thread_by_hash(&thread.hash).await.unwrap(),
+        Some(updated)
+    );
+    assert_eq!(store.update_thread(Thread::new("thread_2")).await, Ok(None));
+}
+
+pub async fn delete_thread<S: ThreadStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+
+    assert_eq!(
+        store.delete_thread(&thread.hash).await.unwrap(),
+        Some(thread.clone())
+    );
+    assert_eq!(store.delete_thread(&thread.hash).await.unwrap(), None);
+    assert_eq!(store.find_thread_by_hash(&thread.hash).await.unwrap(), None);
+}
+
+pub async fn delete_thread_deletes_comments<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+
+    store.delete_thread(&thread.hash).await.unwrap();
+
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![]
+    );
+    assert_eq!(store.delete_comment(&comment.hash).await.unwrap(), None);
+}
+
+pub async fn save_comment<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = Comment::new(&thread.hash, "user@mail.com", u128::MAX, "content");
+
+    assert_eq!(store.save_comment(comment.clone()).await, Ok(comment));
+}
+
+pub async fn save_comment_thread_not_exists<S: ThreadStore + CommentStore>(store: S) {
+    let thread = Thread::new("thread");
+    let comment = Comment::new(&thread.hash, "user@mail.com", 17, "content");
+
+    assert_eq!(
+        store.save_comment(comment).await,
+        Err(StoreError::ThreadNotExists(thread.hash.clone()))
+    );
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![]
+    );
+}
+
+pub async fn save_reply<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let reply = Comment::new_reply(&thread.hash, &comment.hash, "user@mail.com", 18, "reply");
+
+    assert_eq!(store.save_comment(reply.clone()).await, Ok(reply.clone()));
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![comment, reply]
+    );
+}
+
+pub async fn save_reply_parent_not_exists<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let thread_2 = store.save_thread(Thread::new("thread_2")).await.unwrap();
+    let other_thread_comment = store
+        .save_comment(Comment::new(&thread_2.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let missing = Comment::new(&thread.hash, "user@mail.com", 18, "content");
+
+    assert_eq!(
+        store
+            .save_comment(Comment::new_reply(
+                &thread.hash,
+                &missing.hash,
+                "user@mail.com",
+                19,
+                "reply"
+            ))
+            .await,
+        Err(StoreError::ParentNotExists(missing.hash.clone()))
+    );
+    assert_eq!(
+        store
+            .save_comment(Comment::new_reply(
+                &thread.hash,
+                &other_thread_comment.hash,
+                "user@mail.com",
+                19,
+                "reply"
+            ))
+            .await,
+        Err(StoreError::ParentNotExists(
+            other_thread_comment.hash.clone()
+        ))
+    );
+    assert_eq!(
+        store
+            .save_comment(Comment::new_reply(
+                &Thread::new("thread_3").hash,
+                &other_thread_comment.hash,
+                "user@mail.com",
+                19,
+                "reply"
+            ))
+            .await,
+        Err(StoreError::ThreadNotExists(Thread::new("thread_3").hash))
+    );
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![]
+    );
+}
+
+pub async fn edit_comment<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let edited = comment.try_edit("edited", 18).unwrap();
+    let edited_2 = edited.try_edit("edited again", u128::MAX).unwrap();
+
+    assert_eq!(store.find_revisions(&comment.hash).await.unwrap(), vec![]);
+    assert_eq!(
+        store.edit_comment(edited.clone()).await,
+        Ok(Some(edited.clone()))
+    );
+    assert_eq!(
+        store.edit_comment(edited_2.clone()).await,
+        Ok(Some(edited_2.clone()))
+    );
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![edited_2]
+    );
+    assert_eq!(
+        store.find_revisions(&comment.hash).await.unwrap(),
+        vec![
+            Revision {
+                comment_hash: comment.hash.clone(),
+                content: "content".to_string(),
+                date: 17,
+            },
+            Revision {
+                comment_hash: comment.hash.clone(),
+                content: "edited".to_string(),
+                date: 18,
+            },
+        ]
+    );
+
+    store.delete_comment(&comment.hash).await.unwrap();
+
+    assert_eq!(store.find_revisions(&comment.hash).await.unwrap(), vec![]);
+}
+
+pub async fn edit_comment_not_exists<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = Comment::new(&thread.hash, "user@mail.com", 17, "content");
+
+    assert_eq!(
+        store
+            .edit_comment(comment.try_edit("edited", 18).unwrap())
+            .await,
+        Ok(None)
+    );
+    assert_eq!(store.find_revisions(&comment.hash).await.unwrap(), vec![]);
+}
+
+pub async fn delete_comment<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let comment_2 = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 18, "content"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.delete_comment(&comment.hash).await.unwrap(),
+        Some(comment.clone())
+    );
+    assert_eq!(store.delete_comment(&comment.hash).await.unwrap(), None);
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![comment_2]
+    );
+}
+
+pub async fn delete_comment_deletes_replies<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let reply = store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &comment.hash,
+            "user@mail.com",
+            18,
+            "reply",
+        ))
+        .await
+        .unwrap();
+    let nested = store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &reply.hash,
+            "user@mail.com",
+            19,
+            "nested",
+        ))
+        .await
+        .unwrap();
+    let comment_2 = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 20, "content"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.delete_comment(&reply.hash).await.unwrap(),
+        Some(reply)
+    );
+    assert_eq!(store.delete_comment(&nested.hash).await.unwrap(), None);
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![comment, comment_2]
+    );
+}
+
+fn deletion() -> Deletion {
+    Deletion {
+        deleted_by: "admin".to_string(),
+        reason: Some("spam".to_string()),
+        date: u128::MAX,
+    }
+}
+
+pub async fn soft_delete_comment<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.soft_delete_comment(&comment.hash, deletion()).await,
+        Ok(Some(Comment {
+            deletion: Some(deletion()),
+            ..comment.clone()
+        }))
+    );
+    assert_eq!(store.find_comment(&comment.hash).await.unwrap(), None);
+    assert_eq!(
+        store
+            .soft_delete_comment(&comment.hash, deletion())
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+pub async fn soft_delete_comment_with_replies<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let reply = store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &comment.hash,
+            "user@mail.com",
+            18,
+            "reply",
+        ))
+        .await
+        .unwrap();
+    let tombstone = Comment {
+        deletion: Some(deletion()),
+        ..comment.clone()
+    };
+
+    assert_eq!(
+        store.soft_delete_comment(&comment.hash, deletion()).await,
+        Ok(Some(tombstone.clone()))
+    );
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![tombstone, reply.clone()]
+    );
+    assert_eq!(
+        store.find_replies(&comment.hash).await.unwrap(),
+        vec![reply]
+    );
+}
+
+pub async fn restore_comment<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &comment.hash,
+            "user@mail.com",
+            18,
+            "reply",
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(store.restore_comment(&comment.hash).await, Ok(None));
+
+    store
+        .soft_delete_comment(&comment.hash, deletion())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.restore_comment(&comment.hash).await,
+        Ok(Some(comment.clone()))
+    );
+    assert_eq!(
+        store.find_comment(&comment.hash).await.unwrap(),
+        Some(comment)
+    );
+}
+
+pub async fn set_comment_status<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(
+            Comment::new(&thread.hash, "user@mail.com", 17, "content")
+                .with_status(CommentStatus::Pending),
+        )
+        .await
+        .unwrap();
+    let approved = comment.clone().with_status(CommentStatus::Approved);
+
+    assert_eq!(
+        store.find_comment(&comment.hash).await.unwrap(),
+        Some(comment.clone())
+    );
+    assert_eq!(
+        store
+            .set_comment_status(&comment.hash, CommentStatus::Approved)
+            .await,
+        Ok(Some(approved.clone()))
+    );
+    assert_eq!(
+        store.find_comment(&comment.hash).await.unwrap(),
+        Some(approved)
+    );
+    assert_eq!(
+        store
+            .set_comment_status(
+                &Comment::new(&thread.hash, "user@mail.com", 18, "content").hash,
+                CommentStatus::Spam
+            )
+            .await,
+        Ok(None)
+    );
+}
+
+pub async fn find_comment<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let reply = store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &comment.hash,
+            "user@mail.com",
+            18,
+            "reply",
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.find_comment(&comment.hash).await.unwrap(),
+        Some(comment)
+    );
+    assert_eq!(
+        store.find_comment(&reply.hash).await.unwrap(),
+        Some(reply.clone())
+    );
+
+    store.delete_thread(&thread.hash).await.unwrap();
+
+    assert_eq!(store.find_comment(&reply.hash).await.unwrap(), None);
+}
+
+pub async fn find_thread_comments<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let thread_2 = store.save_thread(Thread::new("thread_2")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let comment_2 = store
+        .save_comment(Comment::new(&thread.hash, "user1@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let comment_3 = store
+        .save_comment(Comment::new(
+            &thread_2.hash,
+            "user2@mail.com",
+            17,
+            "content",
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.find_thread_comments(&thread.hash).await.unwrap(),
+        vec![comment, comment_2]
+    );
+    assert_eq!(
+        store.find_thread_comments(&thread_2.hash).await.unwrap(),
+        vec![comment_3]
+    );
+    assert_eq!(
+        store
+            .find_thread_comments(&Thread::new("thread_3").hash)
+            .await
+            .unwrap(),
+        vec![]
+    );
+}
+
+pub async fn find_thread_comments_page<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let thread_2 = store.save_thread(Thread::new("thread_2")).await.unwrap();
+    let mut comments = vec![];
+
+    for date in 0..5 {
+        comments.push(
+            store
+                .save_comment(Comment::new(&thread.hash, "user@mail.com", date, "content"))
+                .await
+                .unwrap(),
+        );
+    }
+    store
+        .save_comment(Comment::new(&thread_2.hash, "user2@mail.com", 0, "content"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store
+            .count_thread_comments(&thread.hash, None)
+            .await
+            .unwrap(),
+        5
+    );
+    assert_eq!(
+        store
+            .count_thread_comments(&thread_2.hash, None)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        store
+            .find_thread_comments_page(&thread.hash, None, 0, 2)
+            .await
+            .unwrap(),
+        comments[0..2]
+    );
+    assert_eq!(
+        store
+            .find_thread_comments_page(&thread.hash, None, 3, 10)
+            .await
+            .unwrap(),
+        comments[3..5]
+    );
+    assert_eq!(
+        store
+            .find_thread_comments_page(&thread.hash, None, 5, 10)
+            .await
+            .unwrap(),
+        vec![]
+    );
+    assert_eq!(
+        store
+            .find_thread_comments_page(&thread.hash, None, 1, 0)
+            .await
+            .unwrap(),
+        vec![]
+    );
+}
+
+pub async fn find_thread_comments_page_approved_only<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let approved = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    store
+        .save_comment(
+            Comment::new(&thread.hash, "user@mail.com", 18, "content")
+                .with_status(CommentStatus::Pending),
+        )
+        .await
+        .unwrap();
+    store
+        .save_comment(
+            Comment::new(&thread.hash, "user@mail.com", 19, "content")
+                .with_status(CommentStatus::Rejected),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store
+            .count_thread_comments(&thread.hash, None)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        store
+            .find_thread_comments_page(&thread.hash, None, 0, 10)
+            .await
+            .unwrap(),
+        vec![approved]
+    );
+    assert_eq!(
+        store
+            .find_thread_comments(&thread.hash)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+}
+
+pub async fn find_thread_comments_page_shadowed<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let visible = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let shadowed = store
+        .save_comment(Comment {
+            shadowed: true,
+            ..Comment::new(&thread.hash, "troll@mail.com", 18, "content")
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store
+            .count_thread_comments(&thread.hash, None)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        store
+            .find_thread_comments_page(&thread.hash, Some("user@mail.com"), 0, 10)
+            .await
+            .unwrap(),
+        vec![visible.clone()]
+    );
+    // the author still sees the comment
+    assert_eq!(
+        store
+            .count_thread_comments(&thread.hash, Some("troll@mail.com"))
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        store
+            .find_thread_comments_page(&thread.hash, Some("troll@mail.com"), 0, 10)
+            .await
+            .unwrap(),
+        vec![visible, shadowed.clone()]
+    );
+    assert_eq!(
+        store.find_comment(&shadowed.hash).await.unwrap(),
+        Some(shadowed)
+    );
+}
+
+pub async fn find_comments_by_status<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let thread_2 = store.save_thread(Thread::new("thread_2")).await.unwrap();
+    let mut pending = vec![];
+
+    for (date, thread) in [(17, &thread), (18, &thread_2), (19, &thread)] {
+        pending.push(
+            store
+                .save_comment(
+                    Comment::new(&thread.hash, "user@mail.com", date, "content")
+                        .with_status(CommentStatus::Pending),
+                )
+                .await
+                .unwrap(),
+        );
+    }
+    store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 20, "content"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store
+            .count_comments_by_status(CommentStatus::Pending)
+            .await
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        store
+            .count_comments_by_status(CommentStatus::Spam)
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        store
+            .find_comments_by_status(CommentStatus::Pending, 0, 2)
+            .await
+            .unwrap(),
+        pending[0..2]
+    );
+    assert_eq!(
+        store
+            .find_comments_by_status(CommentStatus::Pending, 2, 2)
+            .await
+            .unwrap(),
+        pending[2..3]
+    );
+}
+
+pub async fn find_replies<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let mut replies = vec![];
+
+    for date in 18..21 {
+        let reply = Comment::new_reply(&thread.hash, &comment.hash, "user@mail.com", date, "reply");
+        replies.push(store.save_comment(reply).await.unwrap());
+    }
+    store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &replies[0].hash,
+            "user@mail.com",
+            21,
+            "nested",
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(store.find_replies(&comment.hash).await.unwrap(), replies);
+    assert_eq!(store.find_replies(&replies[1].hash).await.unwrap(), vec![]);
+}
+
+pub async fn find_reply_tree<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let reply = store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &comment.hash,
+            "user@mail.com",
+            18,
+            "reply",
+        ))
+        .await
+        .unwrap();
+    let reply_2 = store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &comment.hash,
+            "user@mail.com",
+            19,
+            "reply",
+        ))
+        .await
+        .unwrap();
+    let nested = store
+        .save_comment(Comment::new_reply(
+            &thread.hash,
+            &reply.hash,
+            "user@mail.com",
+            20,
+            "nested",
+        ))
+        .await
+        .unwrap();
+    store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 21, "content"))
+        .await
+        .unwrap();
+
+    let leaf = |comment: Comment| CommentTree {
+        comment,
+        replies: vec![],
+    };
+
+    assert_eq!(
+        store.find_reply_tree(&comment.hash).await.unwrap(),
+        Some(CommentTree {
+            comment,
+            replies: vec![
+                CommentTree {
+                    comment: reply.clone(),
+                    replies: vec![leaf(nested.clone())],
+                },
+                leaf(reply_2),
+            ],
+        })
+    );
+    assert_eq!(
+        store.find_reply_tree(&reply.hash).await.unwrap(),
+        Some(CommentTree {
+            comment: reply,
+            replies: vec![leaf(nested)],
+        })
+    );
+    assert_eq!(
+        store
+            .find_reply_tree(&Comment::new(&thread.hash, "user@mail.com", 22, "content").hash)
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+pub async fn subscribe<S: ThreadStore + CommentStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let events = store.subscribe();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+
+    store
+        .save_comment(Comment::new(
+            &Thread::new("thread_2").hash,
+            "user@mail.com",
+            18,
+            "content",
+        ))
+        .await
+        .unwrap_err();
+    let edited = comment.try_edit("edited", 19).unwrap();
+    store.edit_comment(edited.clone()).await.unwrap();
+    let spam = store
+        .set_comment_status(&comment.hash, CommentStatus::Spam)
+        .await
+        .unwrap()
+        .unwrap();
+    store.delete_comment(&comment.hash).await.unwrap();
+    store.delete_comment(&comment.hash).await.unwrap();
+    drop(store);
+
+    assert_eq!(
+        events.collect::<Vec<CommentEvent>>().await,
+        vec![
+            CommentEvent::Added(comment),
+            CommentEvent::Edited(edited),
+            CommentEvent::StatusChanged {
+                comment: spam.clone(),
+                previous: CommentStatus::Approved,
+            },
+            CommentEvent::Deleted(spam)
+        ]
+    );
+}
+
+pub async fn save_ban<S: BanStore>(store: S) {
+    let ban = Ban {
+        shadow: true,
+        reason: Some("spam".to_string()),
+        expires_at: Some(u64::MAX as u128 + 1),
+        ..Ban::new(BanTarget::User("name".to_string()), "admin", 17)
+    };
+    let range = Ban::new(
+        BanTarget::IpRange("192.0.2.0/24".parse().unwrap()),
+        "admin",
+        18,
+    );
+
+    assert_eq!(store.save_ban(ban.clone()).await, Ok(ban.clone()));
+    assert_eq!(store.save_ban(range.clone()).await, Ok(range.clone()));
+    assert_eq!(store.find_all_bans().await.unwrap(), vec![ban, range]);
+}
+
+pub async fn save_ban_not_unique<S: BanStore>(store: S) {
+    let ban = Ban::new(BanTarget::User("name".to_string()), "admin", 17);
+    let duplicate = Ban {
+        target: BanTarget::User("other".to_string()),
+        ..ban.clone()
+    };
+
+    store.save_ban(ban.clone()).await.unwrap();
+
+    assert_eq!(
+        store.save_ban(duplicate).await,
+        Err(StoreError::BanNotUnique(ban.id.clone()))
+    );
+    assert_eq!(store.find_all_bans().await.unwrap(), vec![ban]);
+}
+
+pub async fn delete_ban<S: BanStore>(store: S) {
+    let ban = Ban::new(BanTarget::EmailDomain("mail.com".to_string()), "admin", 17);
+
+    store.save_ban(ban.clone()).await.unwrap();
+
+    assert_eq!(store.delete_ban(&ban.id).await, Ok(Some(ban)));
+    assert_eq!(store.delete_ban("unknown").await, Ok(None));
+    assert_eq!(store.find_all_bans().await.unwrap(), vec![]);
+}
+
+pub async fn save_report<S: ThreadStore + CommentStore + ReportStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let report = |reporter: &str, reason: &str, date: u128| {
+        Report::try_new(&comment.hash, reporter, reason, date).unwrap()
+    };
+
+    assert_eq!(store.save_report(report("name", "spam", 18)).await, Ok(1));
+    assert_eq!(store.save_report(report("name2", "rude", 19)).await, Ok(2));
+    // reporting again replaces the former report
+    assert_eq!(store.save_report(report("name", "insult", 20)).await, Ok(2));
+    assert_eq!(
+        store.find_reports(&comment.hash).await.unwrap(),
+        vec![report("name", "insult", 20), report("name2", "rude", 19)]
+    );
+    assert_eq!(store.find_reports(&thread.hash).await.unwrap(), vec![]);
+}
+
+pub async fn find_reported_comments<S: ThreadStore + CommentStore + ReportStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let mut comments = vec![];
+
+    for date in 0..4 {
+        comments.push(
+            store
+                .save_comment(Comment::new(&thread.hash, "user@mail.com", date, "content"))
+                .await
+                .unwrap(),
+        );
+    }
+
+    let reports = [(1, "name"), (2, "name"), (2, "name2"), (0, "name")];
+
+    for (index, reporter) in reports {
+        store
+            .save_report(Report::try_new(&comments[index].hash, reporter, "spam", 17).unwrap())
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(store.count_reported_comments().await.unwrap(), 3);
+    assert_eq!(
+        store.find_reported_comments(0, 10).await.unwrap(),
+        vec![
+            (comments[2].clone(), 2),
+            (comments[1].clone(), 1),
+            (comments[0].clone(), 1)
+        ]
+    );
+    assert_eq!(
+        store.find_reported_comments(1, 1).await.unwrap(),
+        vec![(comments[1].clone(), 1)]
+    );
+}
+
+pub async fn delete_comment_deletes_reports<S: ThreadStore + CommentStore + ReportStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+
+    store
+        .save_report(Report::try_new(&comment.hash, "name", "spam", 18).unwrap())
+        .await
+        .unwrap();
+    store.delete_comment(&comment.hash).await.unwrap();
+
+    assert_eq!(store.find_reports(&comment.hash).await.unwrap(), vec![]);
+    assert_eq!(store.count_reported_comments().await.unwrap(), 0);
+}
+
+pub async fn approve_comment_deletes_reports<S: ThreadStore + CommentStore + ReportStore>(
+    store: S,
+) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let comment = store
+        .save_comment(Comment::new(&thread.hash, "user@mail.com", 17, "content"))
+        .await
+        .unwrap();
+    let report = |reporter: &str, date: u128| {
+        Report::try_new(&comment.hash, reporter, "spam", date).unwrap()
+    };
+
+    store.save_report(report("name", 18)).await.unwrap();
+    store
+        .set_comment_status(&comment.hash, CommentStatus::Pending)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.find_reports(&comment.hash).await.unwrap(),
+        vec![report("name", 18)]
+    );
+
+    store
+        .set_comment_status(&comment.hash, CommentStatus::Approved)
+        .await
+        .unwrap();
+
+    assert_eq!(store.find_reports(&comment.hash).await.unwrap(), vec![]);
+    assert_eq!(store.save_report(report("name2", 19)).await, Ok(1));
+}
+
+pub async fn save_role<S: UserStore + RoleStore>(store: S) {
+    store
+        .save_user(User::new("test@mail.com", "name"))
+        .await
+        .unwrap();
+    store
+        .save_user(User::new("test2@mail.com", "name2"))
+        .await
+        .unwrap();
+
+    let thread = Scope::Thread(Thread::new("thread").hash);
+    let moderator = RoleGrant::new("name", Role::Moderator, Scope::Site);
+    let thread_admin = RoleGrant::new("name", Role::Admin, thread.clone());
+    let owner = RoleGrant::new("name2", Role::Owner, Scope::Site);
+
+    for grant in [&moderator, &thread_admin, &owner] {
+        assert_eq!(store.save_role(grant.clone()).await, Ok(grant.clone()));
+    }
+
+    assert_eq!(
+        store.find_roles("name").await.unwrap(),
+        vec![moderator.clone(), thread_admin.clone()]
+    );
+
+    // granting another role in the same scope replaces the former one
+    let admin = RoleGrant::new("name", Role::Admin, Scope::Site);
+
+    store.save_role(admin.clone()).await.unwrap();
+
+    assert_eq!(
+        store.find_roles("name").await.unwrap(),
+        vec![admin.clone(), thread_admin.clone()]
+    );
+    assert_eq!(
+        store.find_all_roles().await.unwrap(),
+        vec![admin, thread_admin, owner]
+    );
+    assert_eq!(store.find_roles("name3").await.unwrap(), vec![]);
+}
+
+pub async fn delete_role<S: UserStore + RoleStore>(store: S) {
+    store
+        .save_user(User::new("test@mail.com", "name"))
+        .await
+        .unwrap();
+
+    let thread = Scope::Thread(Thread::new("thread").hash);
+    let moderator = RoleGrant::new("name", Role::Moderator, thread.clone());
+
+    store.save_role(moderator.clone()).await.unwrap();
+
+    assert_eq!(store.delete_role("name", &Scope::Site).await, Ok(None));
+    assert_eq!(
+        store.delete_role("name", &thread).await,
+        Ok(Some(moderator))
+    );
+    assert_eq!(store.find_roles("name").await.unwrap(), vec![]);
+}
+
+pub async fn delete_user_deletes_roles<S: UserStore + RoleStore>(store: S) {
+    store
+        .save_user(User::new("test@mail.com", "name"))
+        .await
+        .unwrap();
+    store
+        .save_role(RoleGrant::new("name", Role::Admin, Scope::Site))
+        .await
+        .unwrap();
+    store.delete_user("name").await.unwrap();
+
+    assert_eq!(store.find_all_roles().await.unwrap(), vec![]);
+}
+
+pub async fn append_audit_entry<S: AuditStore>(store: S) {
+    let entries: Vec<AuditEntry> = (0..3)
+        .map(|date| AuditEntry {
+            reason: Some(format!("reason {}", date)),
+            ..AuditEntry::new(AuditAction::Ban, "admin", &date.to_string(), date)
+        })
+        .collect();
+
+    for entry in &entries {
+        assert_eq!(
+            store.append_audit_entry(entry.clone()).await,
+            Ok(entry.clone())
+        );
+    }
+
+    assert_eq!(store.count_audit_entries(None).await, Ok(3));
+    // newest first
+    assert_eq!(
+        store.find_audit_entries(None, 0, 2).await.unwrap(),
+        vec![entries[2].clone(), entries[1].clone()]
+    );
+    assert_eq!(
+        store.find_audit_entries(None, 2, 2).await.unwrap(),
+        vec![entries[0].clone()]
+    );
+}
+
+pub async fn find_audit_entries_by_target<S: AuditStore>(store: S) {
+    let hash = Thread::new("thread").hash;
+    let approve = AuditEntry::new(AuditAction::ApproveComment, "moderator", &hash, 1);
+    let other = AuditEntry::new(AuditAction::Unban, "admin", "ban", 2);
+    let delete = AuditEntry {
+        reason: Some("spam".to_string()),
+        ..AuditEntry::new(AuditAction::DeleteComment, "admin", &hash, 3)
+    };
+
+    for entry in [&approve, &other, &delete] {
+        store.append_audit_entry(entry.clone()).await.unwrap();
+    }
+
+    assert_eq!(store.count_audit_entries(Some(&hash)).await, Ok(2));
+    assert_eq!(
+        store.find_audit_entries(Some(&hash), 0, 10).await.unwrap(),
+        vec![delete, approve]
+    );
+    assert_eq!(store.count_audit_entries(Some("unknown")).await, Ok(0));
+}
+
+pub async fn delete_thread_keeps_audit_entries<S: ThreadStore + AuditStore>(store: S) {
+    let thread = store.save_thread(Thread::new("thread")).await.unwrap();
+    let entry = AuditEntry::new(AuditAction::DeleteThread, "admin", &thread.hash, 1);
+
+    store.append_audit_entry(entry.clone()).await.unwrap();
+    store.delete_thread(&thread.hash).await.unwrap();
+
+    assert_eq!(
+        store.find_audit_entries(Some(&thread.hash), 0, 10).await,
+        Ok(vec![entry])
+    );
+}
@@ -0,0 +1,72 @@
+//! Hashcash style proof of work: a solution to a challenge is a nonce such that the SHA-256 hash
+//! of `<challenge>:<nonce>` starts with at least `difficulty` zero bits.
+//!
+//! Finding one takes about `2^difficulty` hashes while checking it takes a single one.
+
+use std::ops::Range;
+
+use sha2::{Digest, Sha256};
+
+pub fn solution_hash(challenge: &str, nonce: u64) -> [u8; 32] {
+    Sha256::digest(format!("{}:{}", challenge, nonce).as_bytes()).into()
+}
+
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+
+    for byte in hash {
+        bits += byte.leading_zeros();
+
+        if *byte != 0 {
+            break;
+        }
+    }
+
+    bits
+}
+
+pub fn verify(challenge: &str, difficulty: u32, nonce: u64) -> bool {
+    leading_zero_bits(&solution_hash(challenge, nonce)) >= difficulty
+}
+
+/// Finds the smallest nonce solving the challenge.
+pub fn solve(challenge: &str, difficulty: u32) -> u64 {
+    solve_in(challenge, difficulty, 0..u64::MAX)
+        .expect("Some nonce solves every challenge of at most 256 bits")
+}
+
+/// Finds the smallest nonce in `nonces` solving the challenge, so browsers can search in chunks
+/// and keep the page responsive in between.
+pub fn solve_in(challenge: &str, difficulty: u32, nonces: Range<u64>) -> Option<u64> {
+    nonces.into_iter().find(|nonce| verify(challenge, difficulty, *nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{leading_zero_bits, solution_hash, solve, solve_in, verify};
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff, 0x00]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x10, 0x00]), 11);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn test_solve_and_verify() {
+        let nonce = solve("challenge", 12);
+
+        assert!(verify("challenge", 12, nonce));
+        assert!(leading_zero_bits(&solution_hash("challenge", nonce)) >= 12);
+        assert!((0..nonce).all(|nonce| !verify("challenge", 12, nonce)));
+        assert!(verify("challenge", 0, 0));
+    }
+
+    #[test]
+    fn test_solve_in() {
+        let nonce = solve("challenge", 12);
+
+        assert_eq!(solve_in("challenge", 12, 0..nonce), None);
+        assert_eq!(solve_in("challenge", 12, nonce..nonce + 1), Some(nonce));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod structs;
 pub mod traits;
-pub mod error;
\ No newline at end of file
+pub mod error;
+/// Shared with the server, which checks the solutions
+pub use comments_rs_pow as pow;
\ No newline at end of file
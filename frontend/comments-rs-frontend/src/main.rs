@@ -1,9 +1,11 @@
 use std::rc::Rc;
 
-use comments_rs_core_frontend::structs::{
-    Comment as CommentData, Thread as ThreadData, User as UserData,
+use comments_rs_core_frontend::{
+    error::Error,
+    structs::{Comment as CommentData, Thread as ThreadData, User as UserData},
 };
-use web_sys::RequestInit;
+use comments_rs_graphql_frontend::GraphqlStore;
+use web_sys::{HtmlInputElement, HtmlTextAreaElement};
 use yew::prelude::*;
 
 enum Msg {
@@ -62,9 +64,109 @@ struct ThreadProps {
 
 #[derive(PartialEq, Properties, Clone)]
 struct AppProps {
+    api_url: String,
     thread_hash: String,
 }
 
+#[derive(PartialEq, Properties, Clone)]
+struct GuestCommentFormProps {
+    api_url: String,
+    thread_hash: String,
+}
+
+enum FormMsg {
+    Submit,
+    Posted(Result<Option<CommentData>, Error>),
+}
+
+enum FormState {
+    Editing,
+    /// Solving the proof of work challenge and posting
+    Posting,
+    Posted,
+    Failed(String),
+}
+
+/// Lets guests comment without signing in, solving the proof of work the server asks for while
+/// they wait.
+struct GuestCommentForm {
+    email: NodeRef,
+    content: NodeRef,
+    state: FormState,
+}
+
+impl Component for GuestCommentForm {
+    type Message = FormMsg;
+    type Properties = GuestCommentFormProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            email: NodeRef::default(),
+            content: NodeRef::default(),
+            state: FormState::Editing,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        self.state = match msg {
+            FormMsg::Submit => {
+                let email = self
+                    .email
+                    .cast::<HtmlInputElement>()
+                    .map(|input| input.value())
+                    .unwrap_or_default();
+                let content = self
+                    .content
+                    .cast::<HtmlTextAreaElement>()
+                    .map(|input| input.value())
+                    .unwrap_or_default();
+                let props = ctx.props().clone();
+
+                ctx.link().send_future(async move {
+                    let store = GraphqlStore::new(&props.api_url);
+
+                    FormMsg::Posted(
+                        store
+                            .post_guest_comment(&props.thread_hash, &email, &content)
+                            .await,
+                    )
+                });
+
+                FormState::Posting
+            }
+            FormMsg::Posted(Ok(Some(_))) => FormState::Posted,
+            FormMsg::Posted(Ok(None)) => {
+                FormState::Failed("Guests can't comment here, please sign in".to_string())
+            }
+            FormMsg::Posted(Err(Error::StoreError(message))) => FormState::Failed(message),
+        };
+
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onsubmit = ctx.link().callback(|event: FocusEvent| {
+            event.prevent_default();
+            FormMsg::Submit
+        });
+        let posting = matches!(self.state, FormState::Posting);
+
+        html! {
+            <form {onsubmit}>
+                <input type="email" placeholder="Email" ref={self.email.clone()} />
+                <textarea placeholder="Comment" ref={self.content.clone()} />
+                <button type="submit" disabled={posting}>{ "Post" }</button>
+                { match &self.state {
+                    FormState::Editing => html! {},
+                    FormState::Posting => html! { <p>{ "Posting ..." }</p> },
+                    FormState::Posted => html! { <p>{ "Your comment was posted" }</p> },
+                    FormState::Failed(message) => html! { <p>{ message }</p> },
+                } }
+            </form>
+        }
+    }
+}
+
 #[function_component(Comment)]
 fn comment(comment: &CommentProps) -> Html {
     html! {
@@ -122,6 +224,9 @@ impl Component for App {
                         <h1>{ "loading ..." }</h1>
                     }
                 } }
+                <GuestCommentForm
+                    api_url={ ctx.props().api_url.clone() }
+                    thread_hash={ ctx.props().thread_hash.clone() } />
             </div>
         }
     }
@@ -132,6 +237,7 @@ struct AppContext;
 
 fn main() {
     let props = AppProps {
+        api_url: "http://localhost:8000".to_string(),
         thread_hash: "test_hash".to_string(),
     };
     yew::start_app_with_props::<App>(props);
@@ -1,8 +1,10 @@
 use comments_rs_core_frontend::{
     error::Error,
-    structs::Thread,
-    traits::{StoreResult, ThreadStore},
+    pow,
+    structs::{Comment, Thread},
+    traits::{CommentStore, Page, StoreResult, ThreadStore},
 };
+use gloo_timers::future::TimeoutFuture;
 use graphql_client::{GraphQLQuery, Response};
 use wasm_bindgen::prelude::wasm_bindgen;
 
@@ -19,6 +21,50 @@ extern "C" {
 )]
 pub struct Query;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../../backend/comments-rs-graphql/schema.graphql",
+    query_path = "query/comments_query.graphql"
+)]
+pub struct CommentsQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../../backend/comments-rs-graphql/schema.graphql",
+    query_path = "query/challenge_query.graphql"
+)]
+pub struct ChallengeQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "../../backend/comments-rs-graphql/schema.graphql",
+    query_path = "query/post_guest_comment_mutation.graphql"
+)]
+pub struct PostGuestCommentMutation;
+
+/// Shown as the name of comments without a registered author.
+const GUEST_NAME: &str = "Guest";
+
+/// Nonces tried before yielding to the browser, a few milliseconds of hashing.
+const NONCES_PER_CHUNK: u64 = 5_000;
+
+/// Solves a proof of work challenge in chunks, giving the browser the chance to render and
+/// handle input in between.
+async fn solve(challenge: &str, difficulty: u32) -> u64 {
+    let mut start = 0;
+
+    loop {
+        let end = start + NONCES_PER_CHUNK;
+
+        if let Some(nonce) = pow::solve_in(challenge, difficulty, start..end) {
+            return nonce;
+        }
+
+        start = end;
+        TimeoutFuture::new(0).await;
+    }
+}
+
 pub struct GraphqlStore {
     base_url: String,
 }
@@ -29,6 +75,69 @@ impl GraphqlStore {
             base_url: base_url.to_string(),
         }
     }
+
+    /// Posts a comment without signing in, solving the proof of work challenge of the server
+    /// first. Returns `None` if the server doesn't accept guest comments.
+    pub async fn post_guest_comment(
+        &self,
+        thread_hash: &str,
+        guest_email: &str,
+        content: &str,
+    ) -> Result<Option<Comment>, Error> {
+        let client = reqwest::Client::new();
+        let url = format!("{}/", self.base_url);
+
+        let response_body: Response<challenge_query::ResponseData> = client
+            .post(&url)
+            .json(&ChallengeQuery::build_query(challenge_query::Variables {}))
+            .send()
+            .await
+            .map_err(|e| Error::StoreError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| Error::StoreError(e.to_string()))?;
+
+        let challenge = match response_body.data.and_then(|data| data.challenge) {
+            Some(challenge) => challenge,
+            None => return Ok(None),
+        };
+        let nonce = solve(&challenge.challenge, challenge.difficulty as u32).await;
+
+        let request_body =
+            PostGuestCommentMutation::build_query(post_guest_comment_mutation::Variables {
+                thread_hash: thread_hash.to_string(),
+                content: content.to_string(),
+                guest_email: guest_email.to_string(),
+                proof: post_guest_comment_mutation::GraphQLProof {
+                    challenge: challenge.challenge,
+                    nonce: nonce.to_string(),
+                },
+            });
+        let response_body: Response<post_guest_comment_mutation::ResponseData> = client
+            .post(&url)
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| Error::StoreError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| Error::StoreError(e.to_string()))?;
+
+        if let Some(error) = response_body
+            .errors
+            .and_then(|errors| errors.into_iter().next())
+        {
+            return Err(Error::StoreError(error.message));
+        }
+
+        Ok(response_body.data.map(|data| Comment {
+            user_name: data
+                .post_comment
+                .author
+                .unwrap_or_else(|| GUEST_NAME.to_string()),
+            content: data.post_comment.content,
+        }))
+    }
 }
 
 impl ThreadStore for GraphqlStore {
@@ -58,3 +167,50 @@ impl ThreadStore for GraphqlStore {
         })
     }
 }
+
+impl CommentStore for GraphqlStore {
+    fn load(&self, hash: &str, offset: i64, limit: i64) -> StoreResult<Page<Comment>> {
+        let hash = hash.to_string();
+        let base = self.base_url.clone();
+
+        Box::pin(async move {
+            // cursors are comment positions, so the page starts after the comment before `offset`
+            let request_body = CommentsQuery::build_query(comments_query::Variables {
+                hash,
+                first: limit,
+                after: (offset > 0).then(|| (offset - 1).to_string()),
+            });
+
+            let response_body: Response<comments_query::ResponseData> = reqwest::Client::new()
+                .post(format!("{}/", base))
+                .json(&request_body)
+                .send()
+                .await
+                .map_err(|e| Error::StoreError(e.to_string()))?
+                .json()
+                .await
+                .map_err(|e| Error::StoreError(e.to_string()))?;
+
+            let comments = match response_body.data.and_then(|data| data.thread) {
+                Some(thread) => thread.comments,
+                None => return Ok(None),
+            };
+            let limit = limit.max(1);
+
+            Ok(Some(Page {
+                page_no: offset / limit,
+                page_count: (comments.total_count + limit - 1) / limit,
+                elements: comments
+                    .edges
+                    .unwrap_or_default()
+                    .into_iter()
+                    .flatten()
+                    .map(|edge| Comment {
+                        user_name: edge.node.author.unwrap_or_else(|| GUEST_NAME.to_string()),
+                        content: edge.node.content,
+                    })
+                    .collect(),
+            }))
+        })
+    }
+}